@@ -0,0 +1,129 @@
+// Benchmarks for the parts of a game that run every tick: stepping the
+// snakes, placing food and building the vertices to draw.
+//
+// The crate is a binary only, so the modules are pulled in by path. Most of
+// what they export goes unused here, as do the imports of their tests.
+
+#[allow(dead_code, unused_imports)]
+#[path = "../src/entity.rs"]
+mod entity;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/event.rs"]
+mod event;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/game.rs"]
+mod game;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/level.rs"]
+mod level;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/mode.rs"]
+mod mode;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/shader.rs"]
+mod shader;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/topology.rs"]
+mod topology;
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use game::{Direction, Game, GameOptions, Pos};
+
+const SIZES: [usize; 3] = [32, 128, 512];
+
+// A `size` by `size` wrapping board with a snake of `length` tiles along the
+// top row, heading right
+fn game_with_snake(size: usize, length: usize) -> Game {
+    let options = GameOptions {
+        wrap: true,
+        seed: Some(1),
+        ..Default::default()
+    };
+    let mut game = Game::with_options(size, size, 1., options);
+    let body: Vec<Pos> = (0..length).rev().map(|x| Pos { x, y: 0 }).collect();
+    game.place_snake(0, &body);
+    game.change_direction(Direction::Right);
+    game
+}
+
+// A board covered by one snake, back and forth along the rows, except for a
+// single free tile
+fn full_game(size: usize) -> Game {
+    let mut game = Game::with_options(size, size, 1., GameOptions::default());
+    let body: Vec<Pos> = (0..size)
+        .flat_map(|y| {
+            (0..size).map(move |i| {
+                let x = if y % 2 == 0 { i } else { size - 1 - i };
+                Pos { x, y }
+            })
+        })
+        .take(size * size - 1)
+        .collect();
+    game.place_snake(0, &body);
+    game
+}
+
+// Times `iters` updates of `start`, starting over from it whenever the snake
+// dies. Cloning a fresh game per step would cost more than the step itself.
+fn steps(start: &Game, iters: u64) -> Duration {
+    let mut game = start.clone();
+    let mut total = Duration::ZERO;
+    for _ in 0..iters {
+        let now = Instant::now();
+        let alive = black_box(&mut game).update();
+        total += now.elapsed();
+        if !alive {
+            game = start.clone();
+        }
+    }
+    total
+}
+
+fn update(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update");
+    for size in SIZES {
+        // Short snake, and one as long as a row can hold
+        for length in [4, size - 1] {
+            let game = game_with_snake(size, length);
+            group.bench_with_input(
+                BenchmarkId::new(format!("length {length}"), size),
+                &game,
+                |b, game| b.iter_custom(|iters| steps(game, iters)),
+            );
+        }
+    }
+    group.finish();
+}
+
+fn food(c: &mut Criterion) {
+    let mut group = c.benchmark_group("respawn_food");
+    for size in SIZES {
+        group.bench_with_input(BenchmarkId::new("empty", size), &size, |b, &size| {
+            let mut game = Game::with_options(size, size, 1., GameOptions::default());
+            b.iter(|| game.respawn_food())
+        });
+        group.bench_with_input(BenchmarkId::new("full", size), &size, |b, &size| {
+            let mut game = full_game(size);
+            b.iter(|| game.respawn_food())
+        });
+    }
+    group.finish();
+}
+
+fn render_data(c: &mut Criterion) {
+    let mut group = c.benchmark_group("render_data");
+    for size in SIZES {
+        let game = game_with_snake(size, size - 1);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &game, |b, game| {
+            b.iter(|| game.render_data())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, update, food, render_data);
+criterion_main!(benches);
@@ -0,0 +1,208 @@
+// Built-in bots. Only use the public `Game` API, same as any other
+// controller would have to.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::bot::{Controller, Forfeit};
+use crate::event::Event;
+use crate::game::{Direction, Game, Pos, SnakeId, Tile};
+
+fn is_free(game: &Game, pos: Pos) -> bool {
+    matches!(game.tile(pos), Tile::Empty | Tile::Food | Tile::PowerUp)
+        && game.entity_at(pos).is_none()
+}
+
+// Free tiles reachable from `from`, stops counting at `limit`
+fn reachable(game: &Game, from: Pos, limit: usize) -> usize {
+    let topology = game.topology();
+    let mut seen = vec![false; game.tiles().len()];
+    let mut queue = VecDeque::from([from]);
+    seen[topology.index(from)] = true;
+
+    let mut count = 0;
+    while let Some(pos) = queue.pop_front() {
+        count += 1;
+        if count >= limit {
+            break;
+        }
+
+        for &direction in topology.directions() {
+            if let Some(next) = game.step(pos, direction) {
+                let i = topology.index(next);
+                if !seen[i] && is_free(game, next) {
+                    seen[i] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    count
+}
+
+/// Heads for the food along moves that don't box the snake in. If every
+/// move is deadly it keeps going straight.
+pub fn greedy(game: &Game) -> Direction {
+    greedy_for(game, 0)
+}
+
+// Keeps going straight, or up if the snake hasn't moved yet
+fn straight_on(game: &Game, id: SnakeId) -> Direction {
+    match game.snake_direction(id) {
+        Some(Direction::Stationary) | None => Direction::Up,
+        Some(direction) => direction,
+    }
+}
+
+/// `greedy` for snake `id` of a multiplayer game
+pub fn greedy_for(game: &Game, id: SnakeId) -> Direction {
+    let Some(head) = game.snake_head(id) else {
+        return Direction::Up;
+    };
+    let food = game.food();
+    // Counting the tile a snake that just ate is about to grow into
+    let length = game.snakes().find(|s| s.id == id).map_or(0, |s| s.length);
+    let room_needed = length + 1;
+
+    let mut best: Option<(bool, usize, usize, Direction)> = None;
+    for &direction in game.topology().directions() {
+        let Some(next) = game.step(head, direction) else {
+            continue;
+        };
+        if !is_free(game, next) {
+            continue;
+        }
+
+        let room = reachable(game, next, room_needed);
+        // Ordered by: enough room first, then closest to food, then most room
+        let candidate = (
+            room < room_needed,
+            game.topology().distance(next, food),
+            usize::MAX - room,
+        );
+        if best.is_none_or(|(a, b, c, _)| candidate < (a, b, c)) {
+            best = Some((candidate.0, candidate.1, candidate.2, direction));
+        }
+    }
+
+    match best {
+        Some((_, _, _, direction)) => direction,
+        None => straight_on(game, id),
+    }
+}
+
+/// Any move that doesn't run into something right away, for a baseline to
+/// compare other strategies with
+pub fn random_for(game: &Game, id: SnakeId, rng: &mut impl Rng) -> Direction {
+    let Some(head) = game.snake_head(id) else {
+        return Direction::Up;
+    };
+    let safe: Vec<Direction> = game
+        .topology()
+        .directions()
+        .iter()
+        .copied()
+        .filter(|direction| {
+            game.step(head, *direction)
+                .is_some_and(|next| is_free(game, next))
+        })
+        .collect();
+    safe.choose(rng)
+        .copied()
+        .unwrap_or_else(|| straight_on(game, id))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    Greedy,
+    Random,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 2] = [Strategy::Greedy, Strategy::Random];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Strategy::Greedy => "greedy",
+            Strategy::Random => "random",
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.name() == name)
+    }
+}
+
+/// A built-in strategy as a `bot::Controller`, moves are worked out when
+/// the state is sent
+pub struct Builtin {
+    name: String,
+    strategy: Strategy,
+    // Only used by Random, seeded so games can be repeated
+    rng: ChaCha8Rng,
+    next: Option<(Direction, Duration)>,
+}
+
+impl Builtin {
+    pub fn new(strategy: Strategy, seed: u64) -> Self {
+        Self {
+            name: format!("ai:{}", strategy.name()),
+            strategy,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            next: None,
+        }
+    }
+}
+
+impl Controller for Builtin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn send_state(
+        &mut self,
+        game: &Game,
+        _tick: u64,
+        you: SnakeId,
+        _events: &[Event],
+    ) -> Result<(), Forfeit> {
+        let start = Instant::now();
+        let direction = match self.strategy {
+            Strategy::Greedy => greedy_for(game, you),
+            Strategy::Random => random_for(game, you, &mut self.rng),
+        };
+        self.next = Some((direction, start.elapsed()));
+        Ok(())
+    }
+
+    fn read_move(&mut self, _deadline: Instant) -> Result<(Direction, Duration), Forfeit> {
+        Ok(self.next.take().expect("send_state comes first"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameOptions;
+
+    #[test]
+    fn greedy_survives_a_while_and_eats() {
+        let options = GameOptions {
+            seed: Some(1),
+            ..Default::default()
+        };
+        let mut game = Game::with_options(12, 12, 10., options);
+
+        for _ in 0..200 {
+            game.change_direction(greedy(&game));
+            if !game.update() {
+                break;
+            }
+        }
+        assert!(game.score() >= 5, "only ate {}", game.score());
+    }
+}
@@ -0,0 +1,898 @@
+// App level state machine: title screen, level select menu, playing, paused,
+// game over and key rebinding. Every screen handles its own input and decides
+// which screen comes next, the window code only forwards keys and ticks.
+// Keys are turned into `input::Action`s before screens see them, except
+// while rebinding where the raw key is what we want.
+
+use std::path::PathBuf;
+
+use crate::daily::{Daily, Date};
+use crate::event::Event;
+use crate::game::{Direction, Game, GameOptions, Turn};
+use crate::input::{self, Action, Bindings, ControlScheme};
+use crate::level::Level;
+use crate::mode::{Ending, GameMode};
+use crate::replay::Replay;
+use crate::scene;
+use crate::settings::Settings;
+use crate::theme::Theme;
+
+use winit::keyboard::KeyCode;
+
+/// Text drawn centered over the board. The first line is the title.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overlay {
+    pub lines: Vec<String>,
+}
+
+impl Overlay {
+    pub fn new(title: &str, lines: Vec<String>) -> Self {
+        let mut all = vec![title.to_string()];
+        all.extend(lines);
+        Self { lines: all }
+    }
+}
+
+pub enum Transition {
+    Stay,
+    To(Box<dyn Screen>),
+    Quit,
+    // The screen doesn't care about this action, the next action bound to
+    // the same key (if any) gets a go
+    Unhandled,
+}
+
+/// Everything screens share
+pub struct Context {
+    pub game: Game,
+    pub levels: Vec<Level>,
+    pub selected_level: usize,
+    pub wrap: bool,
+    pub power_ups: bool,
+    pub mode: GameMode,
+    pub seed: Option<u64>,
+
+    pub scheme: ControlScheme,
+    pub bindings: Bindings,
+    // Where rebinding saves to, None to not persist
+    pub bindings_path: Option<PathBuf>,
+
+    pub themes: Vec<Theme>,
+    pub selected_theme: usize,
+
+    // Time between game ticks
+    pub tick_ms: u64,
+    // Where finished games are saved as replays, None to not save them
+    pub record: Option<PathBuf>,
+    // The day's challenge, when started with --daily
+    pub daily: Option<Daily>,
+    // What to share about the last daily challenge, until the frontend takes it
+    pub share: Option<String>,
+}
+
+impl Context {
+    fn game_options(&self) -> GameOptions {
+        GameOptions {
+            wrap: self.wrap,
+            level: self.levels[self.selected_level].clone(),
+            seed: self.seed,
+            grid: self.game.grid(),
+            power_ups: self.power_ups,
+            mode: self.mode,
+            tail: self.game.tail_rule(),
+        }
+    }
+
+    /// Starts a fresh game with the currently selected options
+    fn new_game(&mut self) {
+        self.game = Game::with_options(
+            self.game.cols(),
+            self.game.rows(),
+            self.game.tile_size(),
+            self.game_options(),
+        );
+    }
+
+    /// Replay of the game so far, marked as the daily challenge if that's
+    /// still what's being played (the menu can change it)
+    fn replay(&self) -> Replay {
+        let mut replay = Replay::record(&self.game, self.tick_ms);
+        if let Some(daily) = &self.daily {
+            if daily.check(&replay).is_ok() {
+                replay.daily = Some(daily.date.to_string());
+            }
+        }
+        replay
+    }
+
+    /// Saves the finished game and, if it was the daily challenge, what to
+    /// share about it. Returns the challenge's date then.
+    fn finish_game(&mut self) -> Option<Date> {
+        let replay = self.replay();
+        if let Some(path) = &self.record {
+            match replay.save(path) {
+                Ok(()) => log::info!("saved replay to {}", path.display()),
+                Err(e) => log::error!("{e}"),
+            }
+        }
+
+        let daily = self.daily.as_ref().filter(|_| replay.daily.is_some())?;
+        self.share = Some(daily.share(&self.game));
+        Some(daily.date)
+    }
+
+    fn save_bindings(&self) {
+        if let Some(path) = &self.bindings_path {
+            if let Err(e) = self.bindings.save(path) {
+                log::error!("couldn't save key bindings to {}: {e}", path.display());
+            }
+        }
+    }
+}
+
+pub trait Screen {
+    fn on_action(&mut self, action: Action, ctx: &mut Context) -> Transition;
+
+    /// Sees keys before they're mapped to actions, returning Some skips the mapping
+    fn on_key(&mut self, _key: KeyCode, _ctx: &mut Context) -> Option<Transition> {
+        None
+    }
+
+    /// Called once per game tick
+    fn tick(&mut self, _ctx: &mut Context) -> Transition {
+        Transition::Stay
+    }
+
+    fn overlay(&self, ctx: &Context) -> Option<Overlay>;
+}
+
+// Vertical list of menu entries, the selected one is marked with '>'
+struct MenuCursor {
+    selected: usize,
+    len: usize,
+}
+
+impl MenuCursor {
+    fn new(len: usize) -> Self {
+        Self { selected: 0, len }
+    }
+
+    // Returns true if the action was a navigation action
+    fn navigate(&mut self, action: Action) -> bool {
+        match action {
+            Action::MoveUp => self.selected = (self.selected + self.len - 1) % self.len,
+            Action::MoveDown => self.selected = (self.selected + 1) % self.len,
+            _ => return false,
+        }
+        true
+    }
+
+    fn lines(&self, items: &[String]) -> Vec<String> {
+        items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                if i == self.selected {
+                    format!("> {item} <")
+                } else {
+                    item.clone()
+                }
+            })
+            .collect()
+    }
+}
+
+pub struct Title {
+    cursor: MenuCursor,
+}
+
+impl Title {
+    const PLAY: usize = 0;
+    const CONTROLS: usize = 1;
+    const QUIT: usize = 2;
+
+    pub fn new() -> Self {
+        Self {
+            cursor: MenuCursor::new(3),
+        }
+    }
+}
+
+impl Screen for Title {
+    fn on_action(&mut self, action: Action, _ctx: &mut Context) -> Transition {
+        if self.cursor.navigate(action) {
+            return Transition::Stay;
+        }
+
+        match (self.cursor.selected, action) {
+            (Self::PLAY, Action::Confirm) => Transition::To(Box::new(Menu::new())),
+            (Self::CONTROLS, Action::Confirm) => Transition::To(Box::new(Rebind::new())),
+            (Self::QUIT, Action::Confirm) | (_, Action::Back) => Transition::Quit,
+            _ => Transition::Unhandled,
+        }
+    }
+
+    fn overlay(&self, _ctx: &Context) -> Option<Overlay> {
+        let items = ["PLAY".into(), "CONTROLS".into(), "QUIT".into()];
+        Some(Overlay::new("SNAKE", self.cursor.lines(&items)))
+    }
+}
+
+pub struct Menu {
+    cursor: MenuCursor,
+}
+
+impl Menu {
+    const LEVEL: usize = 0;
+    const MODE: usize = 1;
+    const WALLS: usize = 2;
+    const POWER_UPS: usize = 3;
+    const STEERING: usize = 4;
+    const START: usize = 5;
+    const BACK: usize = 6;
+
+    pub fn new() -> Self {
+        Self {
+            cursor: MenuCursor::new(7),
+        }
+    }
+
+    fn items(ctx: &Context) -> Vec<String> {
+        vec![
+            format!("LEVEL: {}", ctx.levels[ctx.selected_level].name),
+            format!("MODE: {}", ctx.mode.name()),
+            format!("WALLS: {}", if ctx.wrap { "WRAP" } else { "SOLID" }),
+            format!("POWER-UPS: {}", if ctx.power_ups { "ON" } else { "OFF" }),
+            format!(
+                "STEERING: {}",
+                match ctx.scheme {
+                    ControlScheme::Absolute => "ABSOLUTE",
+                    ControlScheme::Relative => "RELATIVE",
+                }
+            ),
+            "START".into(),
+            "BACK".into(),
+        ]
+    }
+}
+
+impl Screen for Menu {
+    fn on_action(&mut self, action: Action, ctx: &mut Context) -> Transition {
+        if self.cursor.navigate(action) {
+            return Transition::Stay;
+        }
+
+        let levels = ctx.levels.len();
+        let modes = &GameMode::ALL;
+        let mode = modes.iter().position(|m| *m == ctx.mode).unwrap_or(0);
+        match (self.cursor.selected, action) {
+            (Self::LEVEL, Action::MoveRight | Action::Confirm) => {
+                ctx.selected_level = (ctx.selected_level + 1) % levels;
+                ctx.new_game();
+            }
+            (Self::LEVEL, Action::MoveLeft) => {
+                ctx.selected_level = (ctx.selected_level + levels - 1) % levels;
+                ctx.new_game();
+            }
+            (Self::MODE, Action::MoveRight | Action::Confirm) => {
+                ctx.mode = modes[(mode + 1) % modes.len()];
+                ctx.new_game();
+            }
+            (Self::MODE, Action::MoveLeft) => {
+                ctx.mode = modes[(mode + modes.len() - 1) % modes.len()];
+                ctx.new_game();
+            }
+            (Self::WALLS, Action::MoveLeft | Action::MoveRight | Action::Confirm) => {
+                ctx.wrap = !ctx.wrap;
+                ctx.new_game();
+            }
+            (Self::POWER_UPS, Action::MoveLeft | Action::MoveRight | Action::Confirm) => {
+                ctx.power_ups = !ctx.power_ups;
+                ctx.new_game();
+            }
+            (Self::STEERING, Action::MoveLeft | Action::MoveRight | Action::Confirm) => {
+                ctx.scheme = match ctx.scheme {
+                    ControlScheme::Absolute => ControlScheme::Relative,
+                    ControlScheme::Relative => ControlScheme::Absolute,
+                };
+            }
+            (Self::START, Action::Confirm) => {
+                ctx.new_game();
+                return Transition::To(Box::new(Playing));
+            }
+            (Self::BACK, Action::Confirm) | (_, Action::Back) => {
+                return Transition::To(Box::new(Title::new()));
+            }
+            _ => return Transition::Unhandled,
+        }
+        Transition::Stay
+    }
+
+    fn overlay(&self, ctx: &Context) -> Option<Overlay> {
+        Some(Overlay::new("SELECT", self.cursor.lines(&Self::items(ctx))))
+    }
+}
+
+pub struct Playing;
+
+impl Screen for Playing {
+    fn on_action(&mut self, action: Action, ctx: &mut Context) -> Transition {
+        match (ctx.scheme, action) {
+            (ControlScheme::Absolute, Action::MoveUp) => ctx.game.change_direction(Direction::Up),
+            (ControlScheme::Absolute, Action::MoveDown) => {
+                ctx.game.change_direction(Direction::Down)
+            }
+            (ControlScheme::Absolute, Action::MoveLeft) => {
+                ctx.game.change_direction(Direction::Left)
+            }
+            (ControlScheme::Absolute, Action::MoveRight) => {
+                ctx.game.change_direction(Direction::Right)
+            }
+            (ControlScheme::Relative, Action::MoveLeft) => ctx.game.turn(Turn::Left),
+            (ControlScheme::Relative, Action::MoveRight) => ctx.game.turn(Turn::Right),
+            (ControlScheme::Relative, Action::MoveUp | Action::MoveDown) => {}
+            (_, Action::Pause) => return Transition::To(Box::new(Paused)),
+            (_, Action::Restart) => ctx.game.reset(),
+            _ => return Transition::Unhandled,
+        }
+        Transition::Stay
+    }
+
+    fn tick(&mut self, ctx: &mut Context) -> Transition {
+        if ctx.game.update() {
+            Transition::Stay
+        } else {
+            let daily = ctx.finish_game();
+            Transition::To(Box::new(GameOver::new(daily)))
+        }
+    }
+
+    fn overlay(&self, _ctx: &Context) -> Option<Overlay> {
+        None
+    }
+}
+
+pub struct Paused;
+
+impl Screen for Paused {
+    fn on_action(&mut self, action: Action, ctx: &mut Context) -> Transition {
+        match action {
+            Action::Pause | Action::Confirm => Transition::To(Box::new(Playing)),
+            Action::Restart => {
+                ctx.game.reset();
+                Transition::To(Box::new(Playing))
+            }
+            Action::Quit => Transition::To(Box::new(Menu::new())),
+            _ => Transition::Unhandled,
+        }
+    }
+
+    fn overlay(&self, ctx: &Context) -> Option<Overlay> {
+        let keys = |action| {
+            ctx.bindings
+                .keys_for(action)
+                .first()
+                .map_or("-".into(), |key| input::key_label(*key))
+        };
+        Some(Overlay::new(
+            "PAUSED",
+            vec![
+                format!("{} TO RESUME", keys(Action::Pause)),
+                format!("{} FOR MENU", keys(Action::Quit)),
+            ],
+        ))
+    }
+}
+
+pub struct GameOver {
+    cursor: MenuCursor,
+    daily: Option<Date>,
+}
+
+impl GameOver {
+    const RETRY: usize = 0;
+    const MENU: usize = 1;
+
+    pub fn new(daily: Option<Date>) -> Self {
+        Self {
+            cursor: MenuCursor::new(2),
+            daily,
+        }
+    }
+}
+
+impl Screen for GameOver {
+    fn on_action(&mut self, action: Action, ctx: &mut Context) -> Transition {
+        if self.cursor.navigate(action) {
+            return Transition::Stay;
+        }
+
+        match (self.cursor.selected, action) {
+            (Self::RETRY, Action::Confirm) | (_, Action::Restart) => {
+                ctx.game.reset();
+                Transition::To(Box::new(Playing))
+            }
+            (Self::MENU, Action::Confirm) | (_, Action::Back) => {
+                ctx.game.reset();
+                Transition::To(Box::new(Menu::new()))
+            }
+            _ => Transition::Unhandled,
+        }
+    }
+
+    fn overlay(&self, ctx: &Context) -> Option<Overlay> {
+        let mut lines = vec![format!("SCORE {}", ctx.game.score())];
+        if let Some(date) = self.daily {
+            lines.push(format!("DAILY {date}"));
+        }
+        lines.push(String::new());
+        lines.extend(self.cursor.lines(&["RETRY".into(), "MENU".into()]));
+        let title = ctx.game.ending().unwrap_or(Ending::Died).title();
+        Some(Overlay::new(title, lines))
+    }
+}
+
+/// Lists every action with its keys. Confirm on one waits for a key to add
+/// to it, Restart clears it. The last entry resets everything to defaults.
+pub struct Rebind {
+    cursor: MenuCursor,
+    capturing: bool,
+}
+
+impl Rebind {
+    pub fn new() -> Self {
+        Self {
+            cursor: MenuCursor::new(Action::ALL.len() + 1),
+            capturing: false,
+        }
+    }
+
+    fn selected_action(&self) -> Option<Action> {
+        Action::ALL.get(self.cursor.selected).copied()
+    }
+}
+
+impl Screen for Rebind {
+    fn on_key(&mut self, key: KeyCode, ctx: &mut Context) -> Option<Transition> {
+        if !self.capturing {
+            return None;
+        }
+
+        self.capturing = false;
+        if key != KeyCode::Escape {
+            if let Some(action) = self.selected_action() {
+                ctx.bindings.bind(action, key);
+            }
+        }
+        Some(Transition::Stay)
+    }
+
+    fn on_action(&mut self, action: Action, ctx: &mut Context) -> Transition {
+        if self.cursor.navigate(action) {
+            return Transition::Stay;
+        }
+
+        match (self.selected_action(), action) {
+            (Some(_), Action::Confirm) => self.capturing = true,
+            (Some(selected), Action::Restart) => ctx.bindings.clear(selected),
+            (None, Action::Confirm) => ctx.bindings = Bindings::default(),
+            (_, Action::Back) => {
+                ctx.save_bindings();
+                return Transition::To(Box::new(Title::new()));
+            }
+            _ => return Transition::Unhandled,
+        }
+        Transition::Stay
+    }
+
+    fn overlay(&self, ctx: &Context) -> Option<Overlay> {
+        let mut items: Vec<String> = Action::ALL
+            .iter()
+            .map(|action| {
+                let keys: Vec<String> = ctx
+                    .bindings
+                    .keys_for(*action)
+                    .iter()
+                    .map(|key| input::key_label(*key))
+                    .collect();
+                format!("{}: {}", action.label(), keys.join(" "))
+            })
+            .collect();
+        items.push("RESET DEFAULTS".into());
+
+        let hint = if self.capturing {
+            "PRESS A KEY, ESC TO CANCEL"
+        } else {
+            "CONFIRM ADDS A KEY, RESTART CLEARS"
+        };
+        let mut lines = self.cursor.lines(&items);
+        lines.push(String::new());
+        lines.push(hint.into());
+        Some(Overlay::new("CONTROLS", lines))
+    }
+}
+
+pub struct App {
+    ctx: Context,
+    screen: Box<dyn Screen>,
+    quit: bool,
+    // Set by the screenshot key until the frontend takes it
+    screenshot: bool,
+}
+
+impl App {
+    pub fn new(settings: &Settings, bindings: Bindings, bindings_path: Option<PathBuf>) -> Self {
+        // Custom levels and themes go first in their lists
+        let mut levels = Level::builtin();
+        if !levels.contains(&settings.level) {
+            levels.insert(0, settings.level.clone());
+        }
+        let mut themes = Theme::builtin();
+        if !themes.contains(&settings.theme) {
+            themes.insert(0, settings.theme.clone());
+        }
+
+        Self {
+            ctx: Context {
+                game: settings.new_game(),
+                selected_level: levels.iter().position(|l| *l == settings.level).unwrap(),
+                levels,
+                wrap: settings.wrap,
+                power_ups: settings.power_ups,
+                mode: settings.mode,
+                seed: settings.seed,
+                scheme: settings.steering,
+                bindings,
+                bindings_path,
+                selected_theme: themes.iter().position(|t| *t == settings.theme).unwrap(),
+                themes,
+                tick_ms: settings.tick_ms.max(1),
+                record: settings.record.clone(),
+                daily: settings.daily.map(Daily::new),
+                share: None,
+            },
+            screen: Box::new(Title::new()),
+            quit: false,
+            screenshot: false,
+        }
+    }
+
+    // Returns false if the transition was Unhandled
+    fn apply(&mut self, transition: Transition) -> bool {
+        match transition {
+            Transition::Stay => {}
+            Transition::To(screen) => self.screen = screen,
+            Transition::Quit => self.quit = true,
+            Transition::Unhandled => return false,
+        }
+        true
+    }
+
+    pub fn on_key(&mut self, key: KeyCode) {
+        if let Some(transition) = self.screen.on_key(key, &mut self.ctx) {
+            self.apply(transition);
+            return;
+        }
+
+        for action in self.ctx.bindings.actions_for(key) {
+            let transition = self.screen.on_action(action, &mut self.ctx);
+            if self.apply(transition) {
+                return;
+            }
+        }
+
+        // Nothing on screen wanted it, try the global actions
+        for action in self.ctx.bindings.actions_for(key) {
+            match action {
+                Action::ToggleTheme => {
+                    self.ctx.selected_theme = (self.ctx.selected_theme + 1) % self.ctx.themes.len();
+                    return;
+                }
+                Action::Quit => {
+                    self.quit = true;
+                    return;
+                }
+                Action::Screenshot => {
+                    self.screenshot = true;
+                    return;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn tick(&mut self) {
+        let transition = self.screen.tick(&mut self.ctx);
+        self.apply(transition);
+    }
+
+    pub fn overlay(&self) -> Option<Overlay> {
+        self.screen.overlay(&self.ctx)
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.ctx.game
+    }
+
+    /// Status line shown above the board
+    pub fn hud_text(&self) -> String {
+        scene::hud_text(&self.ctx.game, self.ctx.tick_ms)
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.ctx.themes[self.ctx.selected_theme]
+    }
+
+    /// Switches to `theme`, replacing the one with the same name if any
+    pub fn set_theme(&mut self, theme: Theme) {
+        let themes = &mut self.ctx.themes;
+        self.ctx.selected_theme = match themes.iter().position(|t| t.name == theme.name) {
+            Some(i) => {
+                themes[i] = theme;
+                i
+            }
+            None => {
+                themes.insert(0, theme);
+                0
+            }
+        };
+    }
+
+    pub fn set_bindings(&mut self, bindings: Bindings) {
+        self.ctx.bindings = bindings;
+    }
+
+    /// Time between game ticks, longer while the snake has slow motion and
+    /// shorter while it's sped up
+    pub fn tick_ms(&self) -> u64 {
+        self.ctx.game.move_ms(self.ctx.tick_ms)
+    }
+
+    pub fn set_tick_ms(&mut self, tick_ms: u64) {
+        self.ctx.tick_ms = tick_ms.max(1);
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// What happened in the game since the last call
+    pub fn take_events(&mut self) -> Vec<Event> {
+        self.ctx.game.drain_events().collect()
+    }
+
+    /// Text to share about a finished daily challenge, once
+    pub fn take_share(&mut self) -> Option<String> {
+        self.ctx.share.take()
+    }
+
+    /// True once after the screenshot key was pressed
+    pub fn take_screenshot_request(&mut self) -> bool {
+        std::mem::take(&mut self.screenshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_settings() -> Settings {
+        Settings {
+            cols: 10,
+            rows: 10,
+            tile_size: 10.,
+            ..Default::default()
+        }
+    }
+
+    fn app() -> App {
+        App::new(&test_settings(), Bindings::default(), None)
+    }
+
+    fn title(app: &App) -> Option<String> {
+        app.overlay().map(|o| o.lines[0].clone())
+    }
+
+    #[test]
+    fn title_leads_to_menu_and_game() {
+        let mut app = app();
+        assert_eq!(title(&app).as_deref(), Some("SNAKE"));
+
+        app.on_key(KeyCode::Enter);
+        assert_eq!(title(&app).as_deref(), Some("SELECT"));
+
+        // Down five times to START
+        app.on_key(KeyCode::ArrowDown);
+        app.on_key(KeyCode::ArrowDown);
+        app.on_key(KeyCode::ArrowDown);
+        app.on_key(KeyCode::ArrowDown);
+        app.on_key(KeyCode::ArrowDown);
+        app.on_key(KeyCode::Enter);
+        assert_eq!(title(&app), None);
+    }
+
+    #[test]
+    fn menu_selects_level_and_walls() {
+        let mut app = app();
+        app.on_key(KeyCode::Enter);
+        app.on_key(KeyCode::ArrowRight);
+        assert_eq!(app.game().level_name(), "BOX");
+
+        app.on_key(KeyCode::ArrowDown);
+        app.on_key(KeyCode::ArrowDown);
+        app.on_key(KeyCode::Enter);
+        assert!(app.ctx.wrap);
+        assert_eq!(app.ctx.game_options().level.name, "BOX");
+    }
+
+    #[test]
+    fn menu_picks_the_mode_and_game_over_names_the_ending() {
+        let mut app = app();
+        app.on_key(KeyCode::Enter);
+        app.on_key(KeyCode::ArrowDown);
+        app.on_key(KeyCode::ArrowLeft);
+        assert_eq!(app.game().mode(), GameMode::Puzzle);
+        app.on_key(KeyCode::Enter);
+        assert_eq!(app.game().mode(), GameMode::Classic);
+        app.on_key(KeyCode::Enter);
+        assert_eq!(app.game().mode(), GameMode::TimeAttack);
+        assert!(app.hud_text().contains("TIME 60"));
+
+        // Round and round until the time is up
+        app.screen = Box::new(Playing);
+        app.ctx.wrap = true;
+        app.ctx.seed = Some(1);
+        app.ctx.new_game();
+        app.on_key(KeyCode::ArrowRight);
+        for _ in 0..crate::mode::TIME_ATTACK_TICKS {
+            app.tick();
+        }
+        assert_eq!(title(&app).as_deref(), Some("TIME UP"));
+    }
+
+    #[test]
+    fn daily_challenges_end_with_something_to_share() {
+        // A day without wrapping, so running left hits the edge
+        let daily = (1..=28)
+            .map(|day| Daily::new(format!("2026-10-{day:02}").parse().unwrap()))
+            .find(|daily| !daily.wrap)
+            .unwrap();
+        let mut settings = test_settings();
+        daily.apply(&mut settings);
+        let mut app = App::new(&settings, Bindings::default(), None);
+        app.screen = Box::new(Playing);
+        app.on_key(KeyCode::ArrowLeft);
+        for _ in 0..settings.cols {
+            app.tick();
+        }
+        let overlay = app.overlay().unwrap();
+        assert!(overlay.lines.contains(&format!("DAILY {}", daily.date)));
+        assert_eq!(app.take_share(), Some(daily.share(app.game())));
+        assert_eq!(app.take_share(), None);
+
+        // Another mode isn't the day's challenge any more
+        app.screen = Box::new(Menu::new());
+        app.on_key(KeyCode::ArrowDown);
+        app.on_key(KeyCode::Enter);
+        app.screen = Box::new(Playing);
+        app.on_key(KeyCode::ArrowLeft);
+        for _ in 0..settings.cols {
+            app.tick();
+        }
+        assert!(app.overlay().is_some());
+        assert_eq!(app.take_share(), None);
+    }
+
+    #[test]
+    fn pause_and_resume() {
+        let mut app = app();
+        app.screen = Box::new(Playing);
+
+        app.on_key(KeyCode::Space);
+        assert_eq!(title(&app).as_deref(), Some("PAUSED"));
+        app.on_key(KeyCode::Escape);
+        assert_eq!(title(&app), None);
+    }
+
+    #[test]
+    fn rebinding_adds_and_clears_keys() {
+        let mut app = app();
+        // CONTROLS
+        app.on_key(KeyCode::ArrowDown);
+        app.on_key(KeyCode::Enter);
+        assert_eq!(title(&app).as_deref(), Some("CONTROLS"));
+
+        // First entry is MoveUp, bind F5 to it
+        app.on_key(KeyCode::Enter);
+        app.on_key(KeyCode::F5);
+        assert_eq!(
+            app.ctx.bindings.actions_for(KeyCode::F5),
+            vec![Action::MoveUp]
+        );
+
+        // Keys pressed while capturing aren't treated as actions
+        app.on_key(KeyCode::Enter);
+        app.on_key(KeyCode::ArrowDown);
+        assert_eq!(
+            app.ctx.bindings.keys_for(Action::MoveUp).last(),
+            Some(&KeyCode::ArrowDown)
+        );
+
+        app.on_key(KeyCode::KeyR);
+        assert!(app.ctx.bindings.keys_for(Action::MoveUp).is_empty());
+
+        app.on_key(KeyCode::Escape);
+        assert_eq!(title(&app).as_deref(), Some("SNAKE"));
+    }
+
+    #[test]
+    fn rebound_keys_drive_the_snake() {
+        let mut bindings = Bindings::default();
+        bindings.clear(Action::MoveLeft);
+        bindings.bind(Action::MoveLeft, KeyCode::KeyZ);
+        let mut app = App::new(&test_settings(), bindings, None);
+        app.screen = Box::new(Playing);
+
+        // Arrow left isn't bound anymore, so the snake never starts moving
+        app.on_key(KeyCode::ArrowLeft);
+        for _ in 0..10 {
+            app.tick();
+        }
+        assert_eq!(title(&app), None);
+
+        app.on_key(KeyCode::KeyZ);
+        for _ in 0..10 {
+            app.tick();
+        }
+        assert_eq!(title(&app).as_deref(), Some("GAME OVER"));
+    }
+
+    #[test]
+    fn relative_steering_turns_the_snake() {
+        let mut app = app();
+        app.ctx.scheme = ControlScheme::Relative;
+        app.screen = Box::new(Playing);
+
+        app.on_key(KeyCode::ArrowUp);
+        assert_eq!(app.game().current_direction(), Direction::Stationary);
+        app.on_key(KeyCode::ArrowRight);
+        assert_eq!(app.game().current_direction(), Direction::Right);
+        app.tick();
+        app.on_key(KeyCode::ArrowRight);
+        assert_eq!(app.game().current_direction(), Direction::Down);
+    }
+
+    #[test]
+    fn toggle_theme_works_on_any_screen() {
+        let mut app = app();
+        let first = app.theme().name;
+        app.on_key(KeyCode::KeyT);
+        assert_ne!(app.theme().name, first);
+    }
+
+    #[test]
+    fn dying_shows_game_over_and_retry_restarts() {
+        let mut app = app();
+        app.screen = Box::new(Playing);
+
+        app.on_key(KeyCode::ArrowLeft);
+        for _ in 0..10 {
+            app.tick();
+        }
+        let overlay = app.overlay().unwrap();
+        assert_eq!(overlay.lines[0], "GAME OVER");
+        assert!(overlay.lines[1].starts_with("SCORE"));
+
+        app.on_key(KeyCode::Enter);
+        assert_eq!(title(&app), None);
+        assert_eq!(app.game().snake_len(), 1);
+        assert_eq!(app.game().score(), 0);
+    }
+
+    #[test]
+    fn escape_on_title_quits() {
+        let mut app = app();
+        app.on_key(KeyCode::Escape);
+        assert!(app.should_quit());
+    }
+}
@@ -0,0 +1,675 @@
+// Sound effects, made up on the spot by a tiny synth rather than loaded from
+// files. Every sound is a few notes one after another, each a waveform
+// sliding from one pitch to another with a sharp attack and a fade out.
+// Game events pick the sounds, see `Sound::for_event`.
+//
+// Playing them needs the `audio` feature and an output device. Without
+// either `Audio` is just quiet, and `snake sounds` still renders every sound
+// to a WAV file, along with the soundtrack of a replay if asked, so how they
+// sound can be checked anywhere.
+
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::cli::SoundsArgs;
+use crate::event::Event;
+use crate::game::Effect;
+use crate::mode::Ending;
+use crate::replay::Replay;
+
+/// Sample rate of WAV files, output devices may use their own
+pub const SAMPLE_RATE: u32 = 44_100;
+
+// Sounds playing at once, the oldest is cut off for a new one past that
+const MAX_VOICES: usize = 8;
+// Milliseconds a note takes to reach full volume, so it doesn't click
+const ATTACK_MS: f32 = 2.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Wave {
+    Square,
+    Triangle,
+    Sine,
+    // A new random level every cycle, so it still has a pitch
+    Noise,
+}
+
+// Slides from `from` to `to` Hz over `ms`
+#[derive(Debug, Clone, Copy)]
+struct Note {
+    wave: Wave,
+    from: f32,
+    to: f32,
+    ms: u32,
+    volume: f32,
+}
+
+const fn note(wave: Wave, from: f32, to: f32, ms: u32, volume: f32) -> Note {
+    Note {
+        wave,
+        from,
+        to,
+        ms,
+        volume,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sound {
+    Eat,
+    Turn,
+    Death,
+    /// A jingle for each kind, rising from its own note
+    PowerUp(Effect),
+    PowerDown,
+    Shield,
+    Wall,
+    /// A game that ended well, solving a puzzle
+    Win,
+    /// A game the clock or the moves ran out on
+    TimeUp,
+}
+
+impl Sound {
+    pub const ALL: [Sound; 14] = [
+        Sound::Eat,
+        Sound::Turn,
+        Sound::Death,
+        Sound::PowerUp(Effect::Ghost),
+        Sound::PowerUp(Effect::Slow),
+        Sound::PowerUp(Effect::DoubleScore),
+        Sound::PowerUp(Effect::Magnet),
+        Sound::PowerUp(Effect::Shield),
+        Sound::PowerUp(Effect::Speed),
+        Sound::PowerDown,
+        Sound::Shield,
+        Sound::Wall,
+        Sound::Win,
+        Sound::TimeUp,
+    ];
+
+    /// For file names
+    pub fn name(self) -> &'static str {
+        match self {
+            Sound::Eat => "eat",
+            Sound::Turn => "turn",
+            Sound::Death => "death",
+            Sound::PowerUp(Effect::Ghost) => "power_up_ghost",
+            Sound::PowerUp(Effect::Slow) => "power_up_slow",
+            Sound::PowerUp(Effect::DoubleScore) => "power_up_double_score",
+            Sound::PowerUp(Effect::Magnet) => "power_up_magnet",
+            Sound::PowerUp(Effect::Shield) => "power_up_shield",
+            Sound::PowerUp(Effect::Speed) => "power_up_speed",
+            Sound::PowerDown => "power_down",
+            Sound::Shield => "shield",
+            Sound::Wall => "wall",
+            Sound::Win => "win",
+            Sound::TimeUp => "time_up",
+        }
+    }
+
+    /// What `event` sounds like, if anything. Only the player's snake makes
+    /// a sound, the others would drown it out.
+    pub fn for_event(event: &Event) -> Option<Sound> {
+        match *event {
+            Event::Turned { snake: 0, .. } => Some(Sound::Turn),
+            Event::FoodEaten { snake: 0, .. } => Some(Sound::Eat),
+            Event::PowerUpStarted { snake: 0, effect } => Some(Sound::PowerUp(effect)),
+            Event::PowerUpEnded { snake: 0, .. } => Some(Sound::PowerDown),
+            Event::Shielded { snake: 0 } => Some(Sound::Shield),
+            Event::Died { snake: 0, .. } => Some(Sound::Death),
+            Event::WallRaised { .. } => Some(Sound::Wall),
+            Event::Ended {
+                ending: Ending::Solved | Ending::Filled,
+            } => Some(Sound::Win),
+            Event::Ended {
+                ending: Ending::TimeUp | Ending::OutOfMoves,
+            } => Some(Sound::TimeUp),
+            _ => None,
+        }
+    }
+
+    fn notes(self) -> Vec<Note> {
+        use Wave::*;
+
+        // Major arpeggio up from `root`
+        let jingle = |root: f32| {
+            [1., 1.26, 1.5, 2.]
+                .map(|step| note(Square, root * step, root * step, 60, 0.25))
+                .to_vec()
+        };
+        match self {
+            Sound::Eat => vec![note(Square, 520., 1040., 70, 0.3)],
+            Sound::Turn => vec![note(Triangle, 180., 140., 25, 0.2)],
+            Sound::Death => vec![
+                note(Square, 330., 110., 180, 0.35),
+                note(Noise, 900., 60., 420, 0.4),
+            ],
+            Sound::PowerUp(effect) => jingle(match effect {
+                Effect::Ghost => 392.,
+                Effect::Slow => 262.,
+                Effect::DoubleScore => 523.,
+                Effect::Magnet => 330.,
+                Effect::Shield => 294.,
+                Effect::Speed => 440.,
+            }),
+            Sound::PowerDown => vec![
+                note(Triangle, 660., 660., 60, 0.25),
+                note(Triangle, 440., 440., 90, 0.25),
+            ],
+            Sound::Shield => vec![
+                note(Sine, 1400., 900., 40, 0.35),
+                note(Noise, 3000., 1500., 120, 0.2),
+            ],
+            Sound::Wall => vec![note(Noise, 160., 60., 150, 0.35)],
+            Sound::Win => {
+                let mut notes = jingle(523.);
+                notes.push(note(Square, 1046., 1046., 300, 0.25));
+                notes
+            }
+            Sound::TimeUp => vec![
+                note(Square, 440., 440., 150, 0.25),
+                note(Square, 330., 330., 150, 0.25),
+                note(Square, 220., 200., 350, 0.25),
+            ],
+        }
+    }
+
+    /// Mono samples between -1 and 1, at `sample_rate`. The same sound
+    /// always comes out the same.
+    pub fn render(self, sample_rate: u32) -> Vec<f32> {
+        let rate = sample_rate as f32;
+        let mut samples = vec![];
+        // Xorshift, for noise that doesn't depend on anything else
+        let mut noise_state: u32 = 0x9e37_79b9;
+        let mut noise = || {
+            noise_state ^= noise_state << 13;
+            noise_state ^= noise_state >> 17;
+            noise_state ^= noise_state << 5;
+            noise_state as f32 / u32::MAX as f32 * 2. - 1.
+        };
+
+        for note in self.notes() {
+            let len = (note.ms as f32 / 1000. * rate) as usize;
+            let attack = (ATTACK_MS / 1000. * rate).max(1.);
+            let mut phase = 0f32;
+            let mut level = noise();
+            for i in 0..len {
+                let t = i as f32 / len as f32;
+                // Slides evenly in pitch, not in Hz
+                let freq = note.from * (note.to / note.from).powf(t);
+                phase += freq / rate;
+                if phase >= 1. {
+                    phase -= 1.;
+                    level = noise();
+                }
+
+                let wave = match note.wave {
+                    Wave::Square => {
+                        if phase < 0.5 {
+                            1.
+                        } else {
+                            -1.
+                        }
+                    }
+                    Wave::Triangle => 1. - 4. * (phase - 0.5).abs(),
+                    Wave::Sine => (phase * std::f32::consts::TAU).sin(),
+                    Wave::Noise => level,
+                };
+                let envelope = (i as f32 / attack).min(1.) * (1. - t) * (1. - t);
+                samples.push(wave * envelope * note.volume);
+            }
+        }
+        samples
+    }
+}
+
+// Every sound, rendered once at one sample rate
+struct Bank(Vec<Arc<[f32]>>);
+
+impl Bank {
+    fn new(sample_rate: u32) -> Self {
+        Self(
+            Sound::ALL
+                .iter()
+                .map(|sound| sound.render(sample_rate).into())
+                .collect(),
+        )
+    }
+
+    fn get(&self, sound: Sound) -> Arc<[f32]> {
+        let i = Sound::ALL.iter().position(|s| *s == sound).unwrap();
+        Arc::clone(&self.0[i])
+    }
+}
+
+/// Adds up whatever's playing into one stream of samples
+#[derive(Default)]
+pub struct Mixer {
+    // Samples of each sound and how far into them it is
+    voices: Vec<(Arc<[f32]>, usize)>,
+}
+
+impl Mixer {
+    pub fn play(&mut self, samples: Arc<[f32]>) {
+        if self.voices.len() == MAX_VOICES {
+            self.voices.remove(0);
+        }
+        self.voices.push((samples, 0));
+    }
+
+    /// Fills `out` with the next samples, silence once everything's done
+    pub fn fill(&mut self, out: &mut [f32]) {
+        out.fill(0.);
+        for (samples, at) in &mut self.voices {
+            let rest = &samples[*at..];
+            for (out, sample) in out.iter_mut().zip(rest) {
+                *out += sample;
+            }
+            *at += rest.len().min(out.len());
+        }
+        self.voices.retain(|(samples, at)| *at < samples.len());
+        // Several loud sounds at once clip instead of wrapping around
+        for sample in out {
+            *sample = sample.clamp(-1., 1.);
+        }
+    }
+}
+
+/// Writes mono `samples` as a 16 bit PCM WAV file
+pub fn write_wav(out: &mut impl Write, samples: &[f32], sample_rate: u32) -> io::Result<()> {
+    let data_len = samples.len() as u32 * 2;
+    out.write_all(b"RIFF")?;
+    out.write_all(&(36 + data_len).to_le_bytes())?;
+    out.write_all(b"WAVEfmt ")?;
+    out.write_all(&16u32.to_le_bytes())?;
+    // PCM, one channel
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&sample_rate.to_le_bytes())?;
+    out.write_all(&(sample_rate * 2).to_le_bytes())?;
+    // Bytes per frame, bits per sample
+    out.write_all(&2u16.to_le_bytes())?;
+    out.write_all(&16u16.to_le_bytes())?;
+    out.write_all(b"data")?;
+    out.write_all(&data_len.to_le_bytes())?;
+    for sample in samples {
+        let sample = (sample.clamp(-1., 1.) * i16::MAX as f32) as i16;
+        out.write_all(&sample.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn save_wav(path: &Path, samples: &[f32]) -> Result<(), String> {
+    let mut wav = vec![];
+    write_wav(&mut wav, samples, SAMPLE_RATE)
+        .and_then(|()| std::fs::write(path, wav))
+        .map_err(|e| format!("couldn't write {}: {e}", path.display()))
+}
+
+/// What the game of `replay` sounds like, moves taking as long as they did
+/// when it was recorded
+pub fn soundtrack(replay: &Replay, sample_rate: u32) -> Result<Vec<f32>, String> {
+    let bank = Bank::new(sample_rate);
+    let mut mixer = Mixer::default();
+    let mut samples = vec![];
+    let mut playback = replay.play(1.)?;
+    // Setting up the board doesn't make a sound
+    playback.drain_events().for_each(drop);
+    loop {
+        let more = playback.step();
+        for sound in playback.drain_events().filter_map(|e| Sound::for_event(&e)) {
+            mixer.play(bank.get(sound));
+        }
+        // Until the next move, or after the last one until the last sounds
+        // have rung out
+        let len = if more {
+            let ms = playback.game().move_ms(replay.tick_ms);
+            (ms * sample_rate as u64 / 1000) as usize
+        } else {
+            mixer
+                .voices
+                .iter()
+                .map(|(voice, at)| voice.len() - at)
+                .max()
+                .unwrap_or(0)
+        };
+        let start = samples.len();
+        samples.resize(start + len, 0.);
+        mixer.fill(&mut samples[start..]);
+        if !more {
+            return Ok(samples);
+        }
+    }
+}
+
+/// Renders every sound to DIR/NAME.wav, and the soundtrack of a replay if
+/// there is one, for `snake sounds`
+pub fn run(args: &SoundsArgs) -> Result<(), String> {
+    let dir = &args.out;
+    std::fs::create_dir_all(dir).map_err(|e| format!("couldn't create {}: {e}", dir.display()))?;
+    for sound in Sound::ALL {
+        save_wav(
+            &dir.join(format!("{}.wav", sound.name())),
+            &sound.render(SAMPLE_RATE),
+        )?;
+    }
+
+    if let Some(path) = &args.replay {
+        let replay = Replay::load(path)?;
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        save_wav(
+            &dir.join(format!("{name}.wav")),
+            &soundtrack(&replay, SAMPLE_RATE)?,
+        )?;
+    }
+    Ok(())
+}
+
+/// Plays the sounds of game events on the default output device, or does
+/// nothing if there isn't one
+pub struct Audio {
+    // With every sound rendered at the device's sample rate
+    output: Option<(output::Output, Bank)>,
+}
+
+impl Audio {
+    /// Opens the default output device, unless `mute`
+    pub fn new(mute: bool) -> Self {
+        if mute {
+            return Self::off();
+        }
+        match output::Output::open() {
+            Ok(output) => {
+                let bank = Bank::new(output.sample_rate());
+                Self {
+                    output: Some((output, bank)),
+                }
+            }
+            Err(e) => {
+                log::info!("no sound: {e}");
+                Self::off()
+            }
+        }
+    }
+
+    /// Plays nothing
+    pub fn off() -> Self {
+        Self { output: None }
+    }
+
+    pub fn play_events(&mut self, events: &[Event]) {
+        let Some((output, bank)) = &self.output else {
+            return;
+        };
+        for sound in events.iter().filter_map(Sound::for_event) {
+            output.play(bank.get(sound));
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+mod output {
+    use std::sync::{Arc, Mutex};
+
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use cpal::{FromSample, SizedSample};
+
+    use super::Mixer;
+
+    pub struct Output {
+        mixer: Arc<Mutex<Mixer>>,
+        sample_rate: u32,
+        // Sound stops when it's dropped
+        _stream: cpal::Stream,
+    }
+
+    impl Output {
+        pub fn open() -> Result<Self, String> {
+            let device = cpal::default_host()
+                .default_output_device()
+                .ok_or("no output device")?;
+            let supported = device.default_output_config().map_err(|e| e.to_string())?;
+            let mixer = Arc::new(Mutex::new(Mixer::default()));
+            let format = supported.sample_format();
+            let config = supported.config();
+            let stream = match format {
+                cpal::SampleFormat::F32 => stream::<f32>(&device, &config, &mixer),
+                cpal::SampleFormat::I16 => stream::<i16>(&device, &config, &mixer),
+                cpal::SampleFormat::U16 => stream::<u16>(&device, &config, &mixer),
+                other => return Err(format!("unsupported sample format {other}")),
+            }?;
+            stream.play().map_err(|e| e.to_string())?;
+            Ok(Self {
+                mixer,
+                sample_rate: config.sample_rate.0,
+                _stream: stream,
+            })
+        }
+
+        pub fn sample_rate(&self) -> u32 {
+            self.sample_rate
+        }
+
+        pub fn play(&self, samples: Arc<[f32]>) {
+            if let Ok(mut mixer) = self.mixer.lock() {
+                mixer.play(samples);
+            }
+        }
+    }
+
+    // Mixes into every channel of a stream of `T`s
+    fn stream<T: SizedSample + FromSample<f32>>(
+        device: &cpal::Device,
+        config: &cpal::StreamConfig,
+        mixer: &Arc<Mutex<Mixer>>,
+    ) -> Result<cpal::Stream, String> {
+        let channels = config.channels as usize;
+        let mixer = Arc::clone(mixer);
+        let mut mono = vec![];
+        device
+            .build_output_stream(
+                config,
+                move |data: &mut [T], _| {
+                    mono.resize(data.len() / channels, 0.);
+                    match mixer.lock() {
+                        Ok(mut mixer) => mixer.fill(&mut mono),
+                        Err(_) => mono.fill(0.),
+                    }
+                    for (frame, sample) in data.chunks_mut(channels).zip(&mono) {
+                        frame.fill(T::from_sample(*sample));
+                    }
+                },
+                |e| log::warn!("sound output failed: {e}"),
+                None,
+            )
+            .map_err(|e| e.to_string())
+    }
+}
+
+// Built without sound, there's never a device
+#[cfg(not(feature = "audio"))]
+mod output {
+    use std::sync::Arc;
+
+    pub enum Output {}
+
+    impl Output {
+        pub fn open() -> Result<Self, String> {
+            Err("built without the audio feature".into())
+        }
+
+        pub fn sample_rate(&self) -> u32 {
+            match *self {}
+        }
+
+        pub fn play(&self, _samples: Arc<[f32]>) {
+            match *self {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai;
+    use crate::game::{DeathCause, Game, GameOptions};
+
+    #[test]
+    fn sounds_are_loud_enough_but_never_clip() {
+        for sound in Sound::ALL {
+            let samples = sound.render(SAMPLE_RATE);
+            let ms: u32 = sound.notes().iter().map(|note| note.ms).sum();
+            assert_eq!(
+                samples.len(),
+                sound
+                    .notes()
+                    .iter()
+                    .map(|note| (note.ms as f32 / 1000. * SAMPLE_RATE as f32) as usize)
+                    .sum::<usize>()
+            );
+            assert!((20..=1000).contains(&ms), "{sound:?} is {ms} ms");
+
+            let peak = samples.iter().fold(0f32, |peak, s| peak.max(s.abs()));
+            assert!(peak > 0.1 && peak <= 1., "{sound:?} peaks at {peak}");
+            // Fades out instead of stopping with a click
+            assert!(samples.last().unwrap().abs() < 0.01, "{sound:?}");
+
+            assert_eq!(samples, sound.render(SAMPLE_RATE));
+        }
+    }
+
+    #[test]
+    fn events_pick_sounds_for_the_player() {
+        let died = |snake| Event::Died {
+            snake,
+            cause: DeathCause::Wall,
+        };
+        assert_eq!(Sound::for_event(&died(0)), Some(Sound::Death));
+        assert_eq!(Sound::for_event(&died(1)), None);
+        assert_eq!(
+            Sound::for_event(&Event::PowerUpStarted {
+                snake: 0,
+                effect: Effect::Magnet
+            }),
+            Some(Sound::PowerUp(Effect::Magnet))
+        );
+        assert_eq!(
+            Sound::for_event(&Event::Ended {
+                ending: Ending::Solved
+            }),
+            Some(Sound::Win)
+        );
+        // Dying already made a sound
+        assert_eq!(
+            Sound::for_event(&Event::Ended {
+                ending: Ending::Died
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn the_mixer_adds_up_sounds_until_they_end() {
+        let mut mixer = Mixer::default();
+        mixer.play(vec![0.5; 3].into());
+        mixer.play(vec![0.25, 0.75].into());
+
+        let mut out = [1.; 4];
+        mixer.fill(&mut out);
+        // Clipped where both are loud, silent after
+        assert_eq!(out, [0.75, 1., 0.5, 0.]);
+        assert!(mixer.voices.is_empty());
+
+        for _ in 0..MAX_VOICES + 1 {
+            mixer.play(vec![0.1; 10].into());
+        }
+        mixer.fill(&mut out);
+        assert!((out[0] - 0.1 * MAX_VOICES as f32).abs() < 1e-5);
+    }
+
+    #[test]
+    fn wav_files_have_a_pcm_header() {
+        let mut wav = vec![];
+        write_wav(&mut wav, &[0., 1., -1., 2.], 8000).unwrap();
+        assert_eq!(wav.len(), 44 + 8);
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(u32::from_le_bytes(wav[24..28].try_into().unwrap()), 8000);
+        assert_eq!(&wav[36..40], b"data");
+        let samples: Vec<i16> = wav[44..]
+            .chunks(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        assert_eq!(samples, [0, i16::MAX, -i16::MAX, i16::MAX]);
+    }
+
+    fn replay() -> Replay {
+        let options = GameOptions {
+            seed: Some(3),
+            ..Default::default()
+        };
+        let mut game = Game::with_options(10, 10, 4., options);
+        for _ in 0..40 {
+            game.change_direction(ai::greedy(&game));
+            if !game.update() {
+                break;
+            }
+        }
+        Replay::record(&game, 100)
+    }
+
+    #[test]
+    fn replays_have_a_soundtrack() {
+        let replay = replay();
+        let rate = 8000;
+        let samples = soundtrack(&replay, rate).unwrap();
+        // A tick of samples per move, and then some for the last sounds
+        assert!(samples.len() >= replay.moves as usize * 800);
+        assert!(samples.iter().any(|s| *s != 0.));
+        assert!(samples.iter().all(|s| s.abs() <= 1.));
+        // Nothing's left playing at the end
+        assert_eq!(*samples.last().unwrap(), 0.);
+        assert_eq!(samples, soundtrack(&replay, rate).unwrap());
+
+        // Moves that take no time still end with the sounds' tails
+        let instant = Replay {
+            tick_ms: 0,
+            ..replay
+        };
+        let samples = soundtrack(&instant, rate).unwrap();
+        assert!(!samples.is_empty());
+    }
+
+    #[test]
+    fn every_sound_renders_to_a_file() {
+        let dir = std::env::temp_dir().join(format!("snake-sounds-{}", std::process::id()));
+        let replay_path = dir.join("game.toml");
+        std::fs::create_dir_all(&dir).unwrap();
+        replay().save(&replay_path).unwrap();
+        let args = SoundsArgs {
+            out: dir.clone(),
+            replay: Some(replay_path),
+        };
+        run(&args).unwrap();
+        for sound in Sound::ALL {
+            let wav = std::fs::read(dir.join(format!("{}.wav", sound.name()))).unwrap();
+            assert_eq!(wav.len(), 44 + sound.render(SAMPLE_RATE).len() * 2);
+        }
+        let wav = std::fs::read(dir.join("game.wav")).unwrap();
+        assert_eq!(&wav[..4], b"RIFF");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn muted_audio_plays_nothing() {
+        let mut audio = Audio::new(true);
+        assert!(audio.output.is_none());
+        audio.play_events(&[Event::FoodEaten {
+            snake: 0,
+            pos: crate::game::Pos { x: 0, y: 0 },
+            points: 1,
+        }]);
+    }
+}
@@ -0,0 +1,542 @@
+// External bots: programs in any language that play over stdin/stdout. Every
+// tick each bot gets the game as one line of JSON,
+//
+//     {"tick":12,"you":0,"cols":40,"rows":30,"wrap":false,"grid":"square",
+//      "food":{"x":3,"y":7},"walls":[{"x":0,"y":0}],
+//      "portals":[[{"x":1,"y":1},{"x":9,"y":9}]],
+//      "power_up":{"x":8,"y":2,"effect":"ghost"},
+//      "entities":[{"kind":"ball","pos":{"x":2,"y":4},"direction":"left"}],
+//      "events":[{"event":"food_eaten","snake":0,"pos":{"x":5,"y":6},"points":1}],
+//      "snakes":[{"id":0,"alive":true,"score":2,"direction":"up",
+//                 "body":[{"x":5,"y":6},{"x":5,"y":5}],
+//                 "effects":[{"effect":"shield","ticks_left":40}]}]}
+//
+// and answers with one line, `{"move":"up"}` (or down, left, right). y goes
+// up, bodies are head first, cells that aren't part of an irregular board
+// are listed with the walls. A snake heading into a portal comes out of the
+// other one of its pair, on the tile past it. `power_up` is null unless
+// there's one to pick up, see `POWER_UPS` in game.rs. Running into one of
+// the `entities` kills, see entity.rs for how each kind moves. `events` are
+// what happened on the last tick, see event.rs. On a "hex" grid the moves are
+// left, right, up_left, up_right, down_left and down_right, see topology.rs
+// for how the rows line up. A bot that takes longer than the move timeout,
+// exits, or answers anything else forfeits: its snake is taken off the board
+// and the bot is killed.
+//
+// Output is read on a thread per bot so a bot that hangs can be timed out.
+// `play` runs a game between any `Controller`s, external bots being one kind.
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ai;
+use crate::entity::Entity;
+use crate::event::Event;
+use crate::game::{ActiveEffect, DeathCause, Direction, Effect, Game, Pos, SnakeId, Tile};
+use crate::topology::Grid;
+
+/// Why a bot was taken out of the game
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Forfeit {
+    Timeout,
+    Crashed(String),
+    InvalidOutput(String),
+}
+
+impl fmt::Display for Forfeit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Forfeit::Timeout => write!(f, "took too long to move"),
+            Forfeit::Crashed(why) => write!(f, "crashed: {why}"),
+            Forfeit::InvalidOutput(line) => write!(f, "answered {line:?}"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SnakeState<'a> {
+    id: SnakeId,
+    alive: bool,
+    score: u32,
+    direction: Direction,
+    body: Vec<Pos>,
+    effects: &'a [ActiveEffect],
+}
+
+#[derive(Serialize)]
+struct PowerUpState {
+    x: usize,
+    y: usize,
+    effect: Effect,
+}
+
+#[derive(Serialize)]
+struct State<'a> {
+    tick: u64,
+    you: SnakeId,
+    cols: usize,
+    rows: usize,
+    wrap: bool,
+    grid: Grid,
+    food: Pos,
+    walls: &'a [Pos],
+    portals: &'a [[Pos; 2]],
+    power_up: Option<PowerUpState>,
+    entities: &'a [Entity],
+    events: &'a [Event],
+    snakes: &'a [SnakeState<'a>],
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Reply {
+    #[serde(rename = "move")]
+    direction: Direction,
+}
+
+/// Starts a controller from its command line, or `ai:NAME` for a built-in
+/// one (see `ai::Strategy`). `seed` makes the built-in ones repeatable.
+pub fn start(spec: &str, seed: u64) -> Result<Box<dyn Controller>, String> {
+    if let Some(name) = spec.strip_prefix("ai:") {
+        let Some(strategy) = ai::Strategy::by_name(name) else {
+            let names: Vec<_> = ai::Strategy::ALL.iter().map(|s| s.name()).collect();
+            return Err(format!(
+                "no built-in AI called '{name}', try {}",
+                names.join(", ")
+            ));
+        };
+        return Ok(Box::new(ai::Builtin::new(strategy, seed)));
+    }
+    match ExternalBot::spawn(spec) {
+        Ok(bot) => Ok(Box::new(bot)),
+        Err(e) => Err(format!("couldn't start {spec}: {e}")),
+    }
+}
+
+/// Something that steers a snake in `play`
+pub trait Controller {
+    fn name(&self) -> &str;
+
+    /// Shows the controller the game, playing snake `you`, and what happened
+    /// on the last tick. The answer is picked up with `read_move`, so every
+    /// controller can think at the same time.
+    fn send_state(
+        &mut self,
+        game: &Game,
+        tick: u64,
+        you: SnakeId,
+        events: &[Event],
+    ) -> Result<(), Forfeit>;
+
+    /// The answer to the last state and how long it took to come up with,
+    /// waiting until `deadline` at most
+    fn read_move(&mut self, deadline: Instant) -> Result<(Direction, Duration), Forfeit>;
+}
+
+pub struct ExternalBot {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    // Lines the bot printed and when they arrived, an error once it stops
+    lines: Receiver<(Instant, io::Result<String>)>,
+    // When the last state went out
+    sent: Instant,
+}
+
+impl ExternalBot {
+    /// Starts `command_line`, a program and its arguments separated by spaces
+    pub fn spawn(command_line: &str) -> io::Result<Self> {
+        let mut words = command_line.split_whitespace();
+        let program = words
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty bot command"))?;
+        let mut command = Command::new(program);
+        command.args(words);
+        Self::with_command(command_line, command)
+    }
+
+    /// Starts `command`, called `name` in results
+    pub fn with_command(name: &str, mut command: Command) -> io::Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let failed = line.is_err();
+                if sender.send((Instant::now(), line)).is_err() || failed {
+                    return;
+                }
+            }
+            let eof = Err(io::ErrorKind::UnexpectedEof.into());
+            let _ = sender.send((Instant::now(), eof));
+        });
+
+        Ok(Self {
+            name: name.into(),
+            child,
+            stdin,
+            lines,
+            sent: Instant::now(),
+        })
+    }
+
+    /// Sends the state and waits up to `timeout` for the answer
+    #[cfg(test)]
+    pub fn next_move(
+        &mut self,
+        game: &Game,
+        tick: u64,
+        you: SnakeId,
+        timeout: Duration,
+    ) -> Result<Direction, Forfeit> {
+        let deadline = Instant::now() + timeout;
+        self.send_state(game, tick, you, &[])?;
+        self.read_move(deadline).map(|(direction, _)| direction)
+    }
+
+    // Prefers the exit status to whatever went wrong with the pipe, it
+    // says more
+    fn crashed(&mut self, error: io::Error) -> Forfeit {
+        // Give a bot that's exiting a moment to finish
+        let deadline = Instant::now() + Duration::from_millis(100);
+        while Instant::now() < deadline {
+            if let Ok(Some(status)) = self.child.try_wait() {
+                return Forfeit::Crashed(format!("exited ({status})"));
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        Forfeit::Crashed(error.to_string())
+    }
+}
+
+impl Controller for ExternalBot {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn send_state(
+        &mut self,
+        game: &Game,
+        tick: u64,
+        you: SnakeId,
+        events: &[Event],
+    ) -> Result<(), Forfeit> {
+        let walls: Vec<Pos> = game
+            .tiles()
+            .iter()
+            .enumerate()
+            .filter(|(_, tile)| matches!(tile, Tile::Wall | Tile::Void))
+            .map(|(i, _)| game.topology().pos(i))
+            .collect();
+        let topology = game.topology();
+        let portals: Vec<[Pos; 2]> = game
+            .portals()
+            .iter()
+            .map(|&(a, b)| [topology.pos(a), topology.pos(b)])
+            .collect();
+        let snakes: Vec<SnakeState> = game
+            .snakes()
+            .map(|info| SnakeState {
+                id: info.id,
+                alive: info.alive,
+                score: info.score,
+                direction: game.snake_direction(info.id).unwrap(),
+                body: game.snake_tiles(info.id).unwrap().collect(),
+                effects: game.snake_effects(info.id).unwrap(),
+            })
+            .collect();
+        let state = State {
+            tick,
+            you,
+            cols: game.cols(),
+            rows: game.rows(),
+            wrap: game.wraps(),
+            grid: game.grid(),
+            food: game.food(),
+            walls: &walls,
+            portals: &portals,
+            power_up: game.power_up().map(|(pos, effect)| PowerUpState {
+                x: pos.x,
+                y: pos.y,
+                effect,
+            }),
+            entities: game.entities(),
+            events,
+            snakes: &snakes,
+        };
+
+        let mut line = serde_json::to_string(&state).expect("the state is always valid JSON");
+        line.push('\n');
+        self.sent = Instant::now();
+        self.stdin
+            .write_all(line.as_bytes())
+            .and_then(|()| self.stdin.flush())
+            .map_err(|e| self.crashed(e))
+    }
+
+    fn read_move(&mut self, deadline: Instant) -> Result<(Direction, Duration), Forfeit> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let (arrived, line) = match self.lines.recv_timeout(timeout) {
+            Ok((arrived, Ok(line))) => (arrived, line),
+            Ok((_, Err(e))) => return Err(self.crashed(e)),
+            Err(RecvTimeoutError::Timeout) => return Err(Forfeit::Timeout),
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(self.crashed(io::ErrorKind::UnexpectedEof.into()))
+            }
+        };
+        match serde_json::from_str::<Reply>(&line) {
+            Ok(Reply {
+                direction: Direction::Stationary,
+            })
+            | Err(_) => Err(Forfeit::InvalidOutput(line)),
+            Ok(reply) => Ok((reply.direction, arrived.saturating_duration_since(self.sent))),
+        }
+    }
+}
+
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// How a bot's game ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Still alive when the game ended
+    Survived,
+    Died {
+        tick: u64,
+        cause: DeathCause,
+    },
+    Forfeited {
+        tick: u64,
+        reason: Forfeit,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BotResult {
+    pub name: String,
+    pub snake: SnakeId,
+    pub outcome: Outcome,
+    pub score: u32,
+    pub length: usize,
+    // Moves answered in time, and how long they took altogether and at worst
+    pub moves: u64,
+    pub think_time: Duration,
+    pub slowest_move: Duration,
+}
+
+impl fmt::Display for BotResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "snake {} {}: ", self.snake, self.name)?;
+        match &self.outcome {
+            Outcome::Survived => write!(f, "survived")?,
+            Outcome::Died { tick, cause } => write!(f, "died on tick {tick}, hit {cause:?}")?,
+            Outcome::Forfeited { tick, reason } => write!(f, "forfeited on tick {tick}, {reason}")?,
+        }
+        write!(f, ", score {} length {}", self.score, self.length)
+    }
+}
+
+/// Plays `bots` against each other on `game`, bot i steering snake i, for
+/// at most `max_ticks`. A game of several bots ends when one is left.
+/// `on_tick` sees the board after every tick, e.g. to stream it.
+pub fn play(
+    game: &mut Game,
+    bots: &mut [Box<dyn Controller>],
+    move_timeout: Duration,
+    max_ticks: u64,
+    mut on_tick: impl FnMut(&Game),
+) -> Vec<BotResult> {
+    for id in 1..bots.len() {
+        game.spawn_snake(id);
+    }
+    let mut outcomes: Vec<Option<Outcome>> = vec![None; bots.len()];
+    // Forfeited snakes are off the board, remember how they were doing
+    let mut final_sizes = vec![(0, 0); bots.len()];
+    let mut timings = vec![(0, Duration::ZERO, Duration::ZERO); bots.len()];
+
+    let playing = |outcomes: &[Option<Outcome>]| outcomes.iter().filter(|o| o.is_none()).count();
+    let mut tick = 0;
+    // What happened on the last tick, or to set up the board before the first
+    let mut events: Vec<Event> = game.drain_events().collect();
+    on_tick(game);
+    while tick < max_ticks && playing(&outcomes) > (bots.len() > 1) as usize {
+        let deadline = Instant::now() + move_timeout;
+        let mut moves = vec![];
+        for (id, bot) in bots.iter_mut().enumerate() {
+            if outcomes[id].is_none() {
+                moves.push((id, bot.send_state(game, tick, id, &events)));
+            }
+        }
+        for (id, sent) in moves {
+            match sent.and_then(|()| bots[id].read_move(deadline)) {
+                Ok((direction, took)) => {
+                    let (moves, total, slowest) = &mut timings[id];
+                    *moves += 1;
+                    *total += took;
+                    *slowest = took.max(*slowest);
+                    game.steer(id, direction);
+                }
+                Err(reason) => {
+                    log::info!("{} forfeits: {reason}", bots[id].name());
+                    if let Some(info) = game.snakes().find(|s| s.id == id) {
+                        final_sizes[id] = (info.score, info.length);
+                    }
+                    game.remove_snake(id);
+                    outcomes[id] = Some(Outcome::Forfeited { tick, reason });
+                }
+            }
+        }
+
+        game.update_snakes();
+        events = game.drain_events().collect();
+        for event in &events {
+            if let Event::Died { snake, cause } = *event {
+                outcomes[snake] = Some(Outcome::Died { tick, cause });
+            }
+        }
+        tick += 1;
+        on_tick(game);
+    }
+
+    bots.iter()
+        .enumerate()
+        .map(|(id, bot)| {
+            let (score, length) = game
+                .snakes()
+                .find(|s| s.id == id)
+                .map_or(final_sizes[id], |s| (s.score, s.length));
+            let (moves, think_time, slowest_move) = timings[id];
+            BotResult {
+                name: bot.name().into(),
+                snake: id,
+                outcome: outcomes[id].clone().unwrap_or(Outcome::Survived),
+                score,
+                length,
+                moves,
+                think_time,
+                slowest_move,
+            }
+        })
+        .collect()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::game::GameOptions;
+
+    fn sh(name: &str, script: &str) -> Box<dyn Controller> {
+        Box::new(external(name, script))
+    }
+
+    fn external(name: &str, script: &str) -> ExternalBot {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        ExternalBot::with_command(name, command).unwrap()
+    }
+
+    fn game() -> Game {
+        let options = GameOptions {
+            seed: Some(5),
+            ..Default::default()
+        };
+        Game::with_options(8, 8, 1., options)
+    }
+
+    #[test]
+    fn bots_steer_their_snakes() {
+        let mut bots = [sh(
+            "up",
+            r#"while read state; do echo '{"move":"up"}'; done"#,
+        )];
+        let mut game = game();
+        let start = game.head();
+        let results = play(&mut game, &mut bots, Duration::from_secs(5), 100, |_| {});
+
+        // Walks off the top of the board
+        let moves = (game.rows() - start.y) as u64;
+        assert_eq!(
+            results[0].outcome,
+            Outcome::Died {
+                tick: moves - 1,
+                cause: DeathCause::Edge
+            }
+        );
+        assert_eq!(results[0].moves, moves);
+        assert_eq!(game.head().x, start.x);
+    }
+
+    #[test]
+    fn bots_see_the_game_as_json() {
+        // Only goes left if the state is what it should be
+        let mut bot = external(
+            "picky",
+            r#"read state
+            case "$state" in
+                '{"tick":3,"you":0,"cols":8,"rows":8,"wrap":false,'*'"body":[{"x":4,"y":4}],"effects":[]}]}')
+                    echo '{"move":"left"}';;
+                *) echo "$state";;
+            esac"#,
+        );
+        assert_eq!(
+            bot.next_move(&game(), 3, 0, Duration::from_secs(5)),
+            Ok(Direction::Left)
+        );
+    }
+
+    #[test]
+    fn misbehaving_bots_forfeit() {
+        let mut bots = [
+            sh(
+                "fine",
+                r#"while read state; do echo '{"move":"right"}'; done"#,
+            ),
+            sh("garbage", "while read state; do echo nope; done"),
+            sh("slow", "sleep 5"),
+            sh("quitter", "exit 3"),
+        ];
+        let mut game = game();
+        let results = play(
+            &mut game,
+            &mut bots,
+            Duration::from_millis(300),
+            100,
+            |_| {},
+        );
+
+        let reasons: Vec<_> = results
+            .iter()
+            .map(|r| match &r.outcome {
+                Outcome::Forfeited { tick: 0, reason } => Some(reason.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(reasons[0], None);
+        assert_eq!(reasons[1], Some(Forfeit::InvalidOutput("nope".into())));
+        assert_eq!(reasons[2], Some(Forfeit::Timeout));
+        assert!(
+            matches!(&reasons[3], Some(Forfeit::Crashed(why)) if why.contains('3')),
+            "{:?}",
+            reasons[3]
+        );
+        // The last one standing wins without having to move again
+        assert_eq!(results[0].outcome, Outcome::Survived);
+        assert_eq!(game.snakes().count(), 1);
+    }
+}
@@ -0,0 +1,474 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::daily::{Daily, Date};
+use crate::game::TailRule;
+use crate::input::ControlScheme;
+use crate::level::Level;
+use crate::mode::GameMode;
+use crate::settings::{Frontend, PresentMode, Settings};
+use crate::theme::Theme;
+use crate::topology::Grid;
+use crate::tournament::Format;
+
+#[derive(Parser, Debug, Clone, Default)]
+#[command(
+    name = "snake",
+    version,
+    about = "Snake, in a window or in the terminal"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Config file read after the ones in the config directories
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Board width in tiles
+    #[arg(long)]
+    pub cols: Option<usize>,
+
+    /// Board height in tiles
+    #[arg(long)]
+    pub rows: Option<usize>,
+
+    /// Size of a tile in pixels
+    #[arg(long, value_name = "PIXELS")]
+    pub tile_size: Option<f32>,
+
+    /// Time between game ticks
+    #[arg(long, value_name = "MS")]
+    pub tick_ms: Option<u64>,
+
+    /// Seed for food placement, the same seed and inputs replay the same game
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Leaving the board comes back in on the other side
+    #[arg(long)]
+    pub wrap: bool,
+
+    /// Spawn power-ups: ghost, slow motion, double score, magnet, shield and speed
+    #[arg(long)]
+    pub power_ups: bool,
+
+    /// Rules to play by
+    #[arg(long, value_enum)]
+    pub mode: Option<GameMode>,
+
+    /// Whether the head may move into the tile the tail is leaving
+    #[arg(long, value_enum)]
+    pub tail: Option<TailRule>,
+
+    /// Shape of the tiles, hex boards have six directions to move in
+    #[arg(long, value_enum)]
+    pub grid: Option<Grid>,
+
+    /// Built-in level name (classic, box, pillars, cross, circle, donut,
+    /// portals, hazards) or path to a level file.
+    /// Without --cols/--rows the board is sized to fit a level file.
+    #[arg(long, value_name = "NAME|FILE")]
+    pub level: Option<String>,
+
+    /// Color theme (classic, nokia, ocean)
+    #[arg(long)]
+    pub theme: Option<String>,
+
+    /// Absolute or relative (turn left/right) steering
+    #[arg(long, value_enum)]
+    pub steering: Option<ControlScheme>,
+
+    #[arg(long, value_enum, conflicts_with = "headless")]
+    pub frontend: Option<Frontend>,
+
+    #[arg(long)]
+    pub fullscreen: bool,
+
+    #[arg(long, value_enum)]
+    pub present_mode: Option<PresentMode>,
+
+    /// No sound
+    #[arg(long)]
+    pub mute: bool,
+
+    /// Don't show anything, let the built-in bot play and print the result
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Stop a headless game after this many ticks
+    #[arg(long, requires = "headless")]
+    pub max_ticks: Option<u64>,
+
+    /// Save the final board of a headless game as a PNG
+    #[arg(long, value_name = "FILE", requires = "headless")]
+    pub snapshot: Option<PathBuf>,
+
+    /// Shrink the snapshot to fit in a square this many pixels wide
+    #[arg(long, value_name = "PIXELS", requires = "snapshot")]
+    pub snapshot_size: Option<u32>,
+
+    /// Save a replay of every finished game here, for `snake export`
+    #[arg(long, value_name = "FILE")]
+    pub record: Option<PathBuf>,
+
+    /// Play a headless game in real time for `snake watch` viewers, on
+    /// HOST:PORT or unix:PATH
+    #[arg(long, value_name = "ADDR", requires = "headless")]
+    pub stream: Option<String>,
+
+    /// Play the day's challenge: the same board, mode and seed for everyone
+    /// that UTC day, whatever else is set. Finished games print a result to
+    /// share.
+    #[arg(long)]
+    pub daily: bool,
+
+    /// Play the challenge of another day instead
+    #[arg(long, value_name = "YYYY-MM-DD", requires = "daily")]
+    pub date: Option<Date>,
+}
+
+/// Options before the subcommand (board size, speed, level, theme...) still
+/// apply to it
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Turn a replay into an animated GIF or PNG, no window needed
+    Export(ExportArgs),
+    /// Host a multiplayer game
+    Server(ServerArgs),
+    /// Join a multiplayer game
+    Connect(ConnectArgs),
+    /// Watch a multiplayer game or a --stream
+    Watch(WatchArgs),
+    /// Play bots written in any language against each other, over
+    /// stdin/stdout (see bot.rs for the protocol)
+    Bots(BotsArgs),
+    /// Rank bots and built-in AIs by playing them against each other
+    Tournament(TournamentArgs),
+    /// Render every sound effect to a WAV file, no sound device needed
+    Sounds(SoundsArgs),
+    /// Check a daily challenge replay by playing it back, and print its result
+    Verify(VerifyArgs),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ExportArgs {
+    /// Replay saved with --record
+    #[arg(long, value_name = "FILE")]
+    pub replay: PathBuf,
+
+    /// Animation to write, .gif or .png (APNG)
+    #[arg(long, value_name = "FILE")]
+    pub out: PathBuf,
+
+    /// Only keep every Nth move as a frame
+    #[arg(long, value_name = "N", default_value_t = 1,
+          value_parser = clap::value_parser!(u64).range(1..))]
+    pub skip: u64,
+
+    /// Multiplies the tile size the replay was recorded with
+    #[arg(long, default_value_t = 1.)]
+    pub scale: f32,
+
+    /// Draw the score bar above the board
+    #[arg(long)]
+    pub hud: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct SoundsArgs {
+    /// Directory to write them to, as NAME.wav
+    #[arg(long, value_name = "DIR")]
+    pub out: PathBuf,
+
+    /// Also render what the game of this replay sounds like, named after it
+    #[arg(long, value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct VerifyArgs {
+    /// Replay saved with --daily --record
+    #[arg(value_name = "FILE")]
+    pub replay: PathBuf,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ServerArgs {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:7878")]
+    pub bind: String,
+
+    #[arg(long, default_value_t = 8,
+          value_parser = clap::value_parser!(u8).range(1..=254))]
+    pub max_players: u8,
+
+    /// Ticks between a player pressing a key and their snake turning, the
+    /// same for everyone so slow connections aren't at a disadvantage
+    #[arg(long, value_name = "TICKS", default_value_t = 2)]
+    pub input_delay: u64,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ConnectArgs {
+    /// Server address, host:port or unix:PATH
+    pub addr: String,
+
+    /// Name the other players see
+    #[arg(long, default_value = "player")]
+    pub name: String,
+
+    /// Watch without a snake of your own
+    #[arg(long)]
+    pub spectate: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct WatchArgs {
+    /// Server or stream address, host:port or unix:PATH
+    pub addr: String,
+
+    /// Name the server logs
+    #[arg(long, default_value = "viewer")]
+    pub name: String,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct BotsArgs {
+    /// Bot commands, a program and its arguments each, e.g. "python3 bot.py",
+    /// or ai:greedy / ai:random for a built-in one
+    #[arg(required = true, value_name = "COMMAND")]
+    pub bots: Vec<String>,
+
+    /// Time a bot has to answer each tick before it forfeits
+    #[arg(long, value_name = "MS", default_value_t = 200)]
+    pub move_timeout: u64,
+
+    /// Play in real time for `snake watch` viewers, on HOST:PORT or unix:PATH
+    #[arg(long, value_name = "ADDR")]
+    pub stream: Option<String>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct TournamentArgs {
+    /// Bot commands or built-in AIs, as for `snake bots`
+    #[arg(required = true, num_args = 2.., value_name = "COMMAND")]
+    pub entrants: Vec<String>,
+
+    #[arg(long, value_enum, default_value_t)]
+    pub format: Format,
+
+    /// Rounds of a Swiss tournament, enough to tell everyone apart by default
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub rounds: Option<u64>,
+
+    /// Games per pairing, on seeds counting up from --seed
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+    pub games: u64,
+
+    /// Stop a game after this many ticks, the longer snake wins then
+    #[arg(long)]
+    pub max_ticks: Option<u64>,
+
+    /// Games played at once, one per CPU by default
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub jobs: Option<u64>,
+
+    /// Time a bot has to answer each tick before it forfeits
+    #[arg(long, value_name = "MS", default_value_t = 200)]
+    pub move_timeout: u64,
+
+    /// Also write the standings as CSV
+    #[arg(long, value_name = "FILE")]
+    pub csv: Option<PathBuf>,
+
+    /// Also write the standings and settings as JSON
+    #[arg(long, value_name = "FILE")]
+    pub json: Option<PathBuf>,
+}
+
+impl Cli {
+    /// Applies the arguments on top of `settings` and validates the result
+    pub fn apply(&self, mut settings: Settings) -> Result<Settings, String> {
+        if let Some(level) = &self.level {
+            settings.level = Level::find(level)?;
+
+            // Board sized to fit the level unless asked otherwise
+            if let Some((width, height)) = settings.level.size() {
+                settings.cols = width;
+                settings.rows = height;
+            }
+        }
+        if let Some(theme) = &self.theme {
+            settings.theme = Theme::find(theme)?;
+        }
+
+        if let Some(cols) = self.cols {
+            settings.cols = cols;
+        }
+        if let Some(rows) = self.rows {
+            settings.rows = rows;
+        }
+        if let Some(tile_size) = self.tile_size {
+            settings.tile_size = tile_size;
+        }
+        if let Some(tick_ms) = self.tick_ms {
+            settings.tick_ms = tick_ms;
+        }
+        if self.seed.is_some() {
+            settings.seed = self.seed;
+        }
+        if self.wrap {
+            settings.wrap = true;
+        }
+        if self.power_ups {
+            settings.power_ups = true;
+        }
+        if let Some(mode) = self.mode {
+            settings.mode = mode;
+        }
+        if let Some(tail) = self.tail {
+            settings.tail = tail;
+        }
+        if let Some(grid) = self.grid {
+            settings.grid = grid;
+        }
+        if let Some(steering) = self.steering {
+            settings.steering = steering;
+        }
+        if let Some(frontend) = self.frontend {
+            settings.frontend = frontend;
+        }
+        if self.fullscreen {
+            settings.fullscreen = true;
+        }
+        if let Some(present_mode) = self.present_mode {
+            settings.present_mode = present_mode;
+        }
+        if self.mute {
+            settings.mute = true;
+        }
+        if self.headless {
+            settings.headless = true;
+        }
+        if let Some(max_ticks) = self.max_ticks {
+            settings.max_ticks = max_ticks;
+        }
+        if self.snapshot.is_some() {
+            settings.snapshot.clone_from(&self.snapshot);
+        }
+        if self.snapshot_size.is_some() {
+            settings.snapshot_size = self.snapshot_size;
+        }
+        if self.record.is_some() {
+            settings.record.clone_from(&self.record);
+        }
+        if self.stream.is_some() {
+            settings.stream.clone_from(&self.stream);
+        }
+        // Last, so nothing above changes the day's game
+        if self.daily {
+            Daily::new(self.date.unwrap_or_else(Date::today)).apply(&mut settings);
+        }
+
+        settings.validate()?;
+        Ok(settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Settings, String> {
+        let cli = Cli::try_parse_from(std::iter::once("snake").chain(args.iter().copied()))
+            .map_err(|e| e.to_string())?;
+        cli.apply(Settings::default())
+    }
+
+    #[test]
+    fn arguments_override_defaults() {
+        let settings = parse(&[
+            "--cols",
+            "12",
+            "--rows",
+            "8",
+            "--tick-ms",
+            "50",
+            "--seed",
+            "3",
+            "--wrap",
+            "--grid",
+            "hex",
+            "--theme",
+            "nokia",
+            "--level",
+            "box",
+            "--mode",
+            "time-attack",
+            "--tail",
+            "strict",
+        ])
+        .unwrap();
+        assert_eq!((settings.cols, settings.rows), (12, 8));
+        assert_eq!(settings.tick_ms, 50);
+        assert_eq!(settings.seed, Some(3));
+        assert!(settings.wrap);
+        assert_eq!(settings.grid, Grid::Hex);
+        assert_eq!(settings.theme.name, "NOKIA");
+        assert_eq!(settings.level.name, "BOX");
+        assert_eq!(settings.mode, GameMode::TimeAttack);
+        assert_eq!(settings.tail, TailRule::Strict);
+    }
+
+    #[test]
+    fn impossible_combinations_are_errors() {
+        assert!(parse(&["--cols", "1", "--rows", "1"]).is_err());
+        assert!(parse(&["--grid", "hex", "--wrap", "--rows", "9"]).is_err());
+        assert!(parse(&["--theme", "plaid"])
+            .unwrap_err()
+            .contains("classic"));
+        assert!(parse(&["--level", "nowhere"]).is_err());
+        assert!(parse(&["--headless", "--frontend", "terminal"]).is_err());
+        assert!(parse(&["--max-ticks", "5"]).is_err());
+        assert!(parse(&["--stream", "127.0.0.1:7879"]).is_err());
+    }
+
+    #[test]
+    fn daily_overrides_the_game_but_not_the_speed() {
+        let settings = parse(&[
+            "--cols",
+            "12",
+            "--mode",
+            "zen",
+            "--tick-ms",
+            "50",
+            "--daily",
+            "--date",
+            "2026-10-19",
+        ])
+        .unwrap();
+        let date = "2026-10-19".parse().unwrap();
+        let daily = Daily::new(date);
+        assert_eq!(settings.daily, Some(date));
+        assert_eq!(settings.seed, Some(daily.seed));
+        assert_eq!(settings.mode, daily.mode);
+        assert_eq!(settings.cols, crate::daily::COLS);
+        assert_eq!(settings.tick_ms, 50);
+
+        assert!(parse(&["--daily", "--date", "2026-02-30"]).is_err());
+        assert!(parse(&["--date", "2026-10-19"]).is_err());
+    }
+
+    #[test]
+    fn level_files_size_the_board() {
+        let path = std::env::temp_dir().join(format!("snake-cli-test-{}.txt", std::process::id()));
+        std::fs::write(&path, "#####\n#...#\n#...#\n#####\n").unwrap();
+
+        let settings = parse(&["--level", path.to_str().unwrap()]).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((settings.cols, settings.rows), (5, 4));
+    }
+}
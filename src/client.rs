@@ -0,0 +1,410 @@
+// Client side of `snake server` and `--stream`: keeps a mirror of the
+// board up to date and sends the player's inputs, tagged with the tick they
+// should apply on. Messages are read on a thread of their own so frontends
+// can poll without blocking. `Session` is what frontends show.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+use winit::keyboard::KeyCode;
+
+use crate::app::Overlay;
+use crate::entity::{Entity, EntityKind};
+use crate::game::{Direction, Game, SnakeId, SnakeInfo};
+use crate::input::{self, Action, Bindings};
+use crate::protocol::{ClientMessage, Role, ServerMessage, VERSION};
+use crate::scene::{Scene, View};
+use crate::theme::Theme;
+use crate::transport::{self, Stream};
+
+// How often frontends look for new messages, per server tick
+const POLLS_PER_TICK: u64 = 4;
+// A keyframe has a byte per tile and messages are at most a MiB, so a
+// server can't send a bigger board anyway
+const MAX_TILES: usize = 1 << 20;
+
+pub struct Client {
+    stream: Stream,
+    messages: Receiver<io::Result<ServerMessage>>,
+    connected: bool,
+
+    // None when spectating
+    snake: Option<SnakeId>,
+    input_delay: u64,
+    tick_ms: u64,
+
+    game: Game,
+    tick: u64,
+    snakes: Vec<SnakeInfo>,
+    names: BTreeMap<SnakeId, String>,
+}
+
+impl Client {
+    /// Joins the server (or stream) at `addr` as `name`, with `tile_size`
+    /// pixel tiles on the mirrored board
+    pub fn connect(addr: &str, role: Role, name: &str, tile_size: f32) -> io::Result<Self> {
+        let mut stream = transport::connect(addr)?;
+        ClientMessage::Hello {
+            version: VERSION,
+            role,
+            name: name.into(),
+        }
+        .write(&mut stream)?;
+
+        let (snake, cols, rows, grid, tick_ms, input_delay, portals) =
+            match ServerMessage::read(&mut stream, 0)? {
+                ServerMessage::Welcome {
+                    version,
+                    snake,
+                    cols,
+                    rows,
+                    grid,
+                    tick_ms,
+                    input_delay,
+                    portals,
+                } if version == VERSION => (snake, cols, rows, grid, tick_ms, input_delay, portals),
+                ServerMessage::Welcome { version, .. } => {
+                    return Err(io::Error::other(format!(
+                        "the server speaks protocol version {version}, we speak {VERSION}"
+                    )))
+                }
+                ServerMessage::Reject { reason } => {
+                    return Err(io::Error::new(io::ErrorKind::ConnectionRefused, reason))
+                }
+                message => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("expected a welcome, got {message:?}"),
+                    ))
+                }
+            };
+
+        // Don't trust the server with how much we allocate for the mirror
+        if cols == 0 || rows == 0 || cols * rows > MAX_TILES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("the server's {cols}x{rows} board can't be mirrored"),
+            ));
+        }
+
+        let (sender, messages) = mpsc::channel();
+        let mut reader = stream.try_clone()?;
+        std::thread::spawn(move || loop {
+            let message = ServerMessage::read(&mut reader, cols * rows);
+            let failed = message.is_err();
+            if sender.send(message).is_err() || failed {
+                return;
+            }
+        });
+
+        if portals.iter().any(|&(a, b)| a.max(b) >= cols * rows) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "portal off the board",
+            ));
+        }
+        let mut game = Game::mirror(cols, rows, tile_size, grid);
+        game.set_portals(&portals);
+        Ok(Self {
+            stream,
+            messages,
+            connected: true,
+            snake,
+            input_delay,
+            tick_ms,
+            game,
+            tick: 0,
+            snakes: vec![],
+            names: BTreeMap::new(),
+        })
+    }
+
+    /// Asks for our snake to head in `direction`, `input_delay` ticks from
+    /// the last one we've seen
+    pub fn steer(&mut self, direction: Direction) -> io::Result<()> {
+        if self.snake.is_none() {
+            return Ok(());
+        }
+        ClientMessage::Input {
+            tick: self.tick + self.input_delay,
+            direction,
+        }
+        .write(&mut self.stream)
+    }
+
+    pub fn leave(&mut self) -> io::Result<()> {
+        ClientMessage::Leave.write(&mut self.stream)?;
+        self.stream.shutdown()
+    }
+
+    /// Applies whatever the server sent since the last poll. False once
+    /// the connection is gone.
+    pub fn poll(&mut self) -> bool {
+        while self.connected {
+            let message = match self.messages.try_recv() {
+                Ok(Ok(message)) => message,
+                Ok(Err(e)) => {
+                    if e.kind() != io::ErrorKind::UnexpectedEof {
+                        log::warn!("lost the server: {e}");
+                    }
+                    self.connected = false;
+                    break;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.connected = false;
+                    break;
+                }
+            };
+
+            match message {
+                ServerMessage::Keyframe {
+                    tick,
+                    tiles,
+                    snakes,
+                    entities,
+                } => {
+                    for (i, tile) in tiles.into_iter().enumerate() {
+                        self.game.set_tile(i, tile);
+                    }
+                    self.tick = tick;
+                    self.snakes = snakes;
+                    self.set_entities(entities);
+                }
+                ServerMessage::Delta {
+                    tick,
+                    changes,
+                    snakes,
+                    entities,
+                } => {
+                    let size = self.game.tiles().len();
+                    for (i, tile) in changes.into_iter().filter(|(i, _)| *i < size) {
+                        self.game.set_tile(i, tile);
+                    }
+                    self.tick = tick;
+                    self.snakes = snakes;
+                    self.set_entities(entities);
+                }
+                ServerMessage::Joined { snake, name } => {
+                    self.names.insert(snake, name);
+                }
+                ServerMessage::Left { snake } => {
+                    self.names.remove(&snake);
+                }
+                message => log::warn!("unexpected message from the server: {message:?}"),
+            }
+        }
+        self.connected
+    }
+
+    // Entities only come with their tile, which is all there is to draw
+    fn set_entities(&mut self, entities: Vec<(usize, EntityKind)>) {
+        let size = self.game.tiles().len();
+        let entities = entities
+            .into_iter()
+            .filter(|(i, _)| *i < size)
+            .map(|(i, kind)| Entity {
+                kind,
+                pos: self.game.topology().pos(i),
+                direction: Direction::Stationary,
+            })
+            .collect();
+        self.game.set_entities(entities);
+    }
+
+    /// Board as of the last tick we've seen
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    #[cfg(test)]
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Time between the server's ticks
+    pub fn tick_ms(&self) -> u64 {
+        self.tick_ms
+    }
+
+    #[cfg(test)]
+    pub fn snake(&self) -> Option<SnakeId> {
+        self.snake
+    }
+
+    #[cfg(test)]
+    pub fn snakes(&self) -> &[SnakeInfo] {
+        &self.snakes
+    }
+
+    pub fn name(&self, snake: SnakeId) -> Option<&str> {
+        self.names.get(&snake).map(String::as_str)
+    }
+
+    /// Status line with our score, or everyone's when spectating
+    pub fn hud_text(&self) -> String {
+        let players = self.names.len();
+        match self.snake {
+            Some(id) => match self.snakes.iter().find(|s| s.id == id) {
+                Some(snake) => format!(
+                    "SCORE {}  LENGTH {}  PLAYERS {players}",
+                    snake.score, snake.length
+                ),
+                None => format!("RESPAWNING  PLAYERS {players}"),
+            },
+            None => {
+                let best = self.snakes.iter().max_by_key(|s| s.score);
+                match best.and_then(|s| Some((self.name(s.id)?, s.score))) {
+                    Some((name, score)) => {
+                        format!(
+                            "SPECTATING  PLAYERS {players}  BEST {} {score}",
+                            name.to_uppercase()
+                        )
+                    }
+                    None => format!("SPECTATING  PLAYERS {players}"),
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Client {
+    // The reader thread holds a clone of the stream, shutting it down is what
+    // tells the server we're gone
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown();
+    }
+}
+
+/// A network game on screen: movement keys steer with absolute directions,
+/// Quit or Back leaves, and losing the server says so until then
+pub struct Session {
+    client: Client,
+    theme: Theme,
+    bindings: Bindings,
+    connected: bool,
+    quit: bool,
+    screenshot: bool,
+}
+
+impl Session {
+    pub fn new(client: Client, theme: Theme, bindings: Bindings) -> Self {
+        Self {
+            client,
+            theme,
+            bindings,
+            connected: true,
+            quit: false,
+            screenshot: false,
+        }
+    }
+}
+
+impl View for Session {
+    fn on_key(&mut self, key: KeyCode) {
+        for action in self.bindings.actions_for(key) {
+            let direction = match action {
+                Action::MoveUp => Direction::Up,
+                Action::MoveDown => Direction::Down,
+                Action::MoveLeft => Direction::Left,
+                Action::MoveRight => Direction::Right,
+                Action::Quit | Action::Back => {
+                    if self.connected {
+                        let _ = self.client.leave();
+                    }
+                    self.quit = true;
+                    return;
+                }
+                Action::Screenshot => {
+                    self.screenshot = true;
+                    continue;
+                }
+                _ => continue,
+            };
+            if self.connected {
+                if let Err(e) = self.client.steer(direction) {
+                    log::warn!("couldn't send an input: {e}");
+                }
+            }
+        }
+    }
+
+    fn tick(&mut self) {
+        self.connected = self.client.poll();
+    }
+
+    fn tick_ms(&self) -> u64 {
+        (self.client.tick_ms() / POLLS_PER_TICK).max(1)
+    }
+
+    fn scene(&self) -> Scene<'_> {
+        let overlay = (!self.connected).then(|| {
+            let key = self.bindings.keys_for(Action::Quit).first();
+            Overlay::new(
+                "DISCONNECTED",
+                vec![format!(
+                    "{} TO QUIT",
+                    key.map_or("-".into(), |key| input::key_label(*key))
+                )],
+            )
+        });
+        Scene {
+            game: self.client.game(),
+            theme: &self.theme,
+            hud: Some(self.client.hud_text()),
+            overlay,
+            particles: None,
+        }
+    }
+
+    fn should_quit(&self) -> bool {
+        self.quit
+    }
+
+    fn take_screenshot_request(&mut self) -> bool {
+        std::mem::take(&mut self.screenshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::Grid;
+    use std::net::TcpListener;
+
+    // Connects to a server that welcomes us to a `cols` by `rows` board
+    fn welcome(cols: usize, rows: usize) -> io::Result<Client> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            ClientMessage::read(&mut stream).unwrap();
+            ServerMessage::Welcome {
+                version: VERSION,
+                snake: None,
+                cols,
+                rows,
+                grid: Grid::Square,
+                tick_ms: 10,
+                input_delay: 0,
+                portals: vec![],
+            }
+            .write(&mut stream)
+            .unwrap();
+            stream
+        });
+        let client = Client::connect(&addr, Role::Spectator, "viewer", 1.);
+        drop(server.join().unwrap());
+        client
+    }
+
+    #[test]
+    fn boards_the_server_cant_send_are_refused() {
+        for (cols, rows) in [(0, 8), (8, 0), (65535, 65535)] {
+            let error = welcome(cols, rows).err().expect("connected anyway");
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+        assert_eq!(welcome(12, 8).unwrap().game().tiles().len(), 96);
+    }
+}
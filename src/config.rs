@@ -0,0 +1,542 @@
+// Layered `snake.toml` config. Files are read lowest priority first: the
+// system config dirs ($XDG_CONFIG_DIRS), the user config dir, then the one
+// given with --config. Each file only overrides what it sets and the command
+// line goes over all of them. Broken files, and ones that would leave the
+// settings invalid, are logged and skipped.
+//
+//     [game]
+//     cols = 30
+//     tick_ms = 80
+//     level = "pillars"        # or a level file, relative to this file
+//     grid = "hex"
+//     mode = "time_attack"
+//     tail = "strict"
+//
+//     [window]
+//     tile_size = 24
+//     fullscreen = false
+//     present_mode = "mailbox"
+//     mute = true
+//
+//     [colors]
+//     theme = "ocean"
+//     food = "#ff00ff"
+//
+//     [bindings]
+//     pause = ["KeyP"]
+//
+// Colors, key bindings and speed are reloaded when a file changes, the rest
+// apply on the next start.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+use notify::Watcher as _;
+use serde::Deserialize;
+
+use crate::app::App;
+use crate::cli::Cli;
+use crate::game::TailRule;
+use crate::input::{Bindings, ControlScheme};
+use crate::level::Level;
+use crate::mode::GameMode;
+use crate::settings::{Frontend, PresentMode, Settings};
+use crate::theme::{self, Theme};
+use crate::topology::Grid;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GameSection {
+    cols: Option<usize>,
+    rows: Option<usize>,
+    tick_ms: Option<u64>,
+    seed: Option<u64>,
+    wrap: Option<bool>,
+    power_ups: Option<bool>,
+    mode: Option<GameMode>,
+    tail: Option<TailRule>,
+    grid: Option<Grid>,
+    level: Option<String>,
+    steering: Option<ControlScheme>,
+    frontend: Option<Frontend>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WindowSection {
+    tile_size: Option<f32>,
+    fullscreen: Option<bool>,
+    present_mode: Option<PresentMode>,
+    mute: Option<bool>,
+}
+
+// Starts from `theme` and replaces whichever colors are set
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ColorsSection {
+    theme: Option<String>,
+    snake: Option<String>,
+    background: Option<String>,
+    food: Option<String>,
+    wall: Option<String>,
+    power_up: Option<String>,
+    hazard: Option<String>,
+    hud_background: Option<String>,
+    text: Option<String>,
+}
+
+// Later values win, fields left unset keep the earlier ones
+macro_rules! merge {
+    ($into:expr, $from:expr, $($field:ident),* $(,)?) => {
+        $(if $from.$field.is_some() {
+            $into.$field = $from.$field;
+        })*
+    };
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    game: GameSection,
+    window: WindowSection,
+    colors: ColorsSection,
+    bindings: Option<Bindings>,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Config, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    /// Reads `path`, Ok(None) if there's no such file. Relative level paths
+    /// are taken relative to the file.
+    pub fn load(path: &Path) -> Result<Option<Config>, String> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("couldn't read {}: {e}", path.display())),
+        };
+        let mut config =
+            Self::parse(&text).map_err(|e| format!("invalid config {}: {e}", path.display()))?;
+
+        if let (Some(level), Some(dir)) = (&mut config.game.level, path.parent()) {
+            if Level::by_name(level).is_none() && Path::new(level).is_relative() {
+                *level = dir.join(&*level).to_string_lossy().into_owned();
+            }
+        }
+        Ok(Some(config))
+    }
+
+    /// All of `paths` layered in order, along with the errors of the ones
+    /// that had to be skipped. A file that makes the settings invalid is
+    /// skipped as a whole, the others still count.
+    pub fn load_all(paths: &[PathBuf]) -> (Config, Vec<String>) {
+        let mut config = Config::default();
+        let mut errors = vec![];
+        for path in paths {
+            match Self::load(path) {
+                Ok(Some(layer)) => {
+                    let mut merged = config.clone();
+                    merged.merge(layer);
+                    match merged.apply(Settings::default()).and_then(|s| s.validate()) {
+                        Ok(()) => config = merged,
+                        Err(e) => errors.push(format!("ignoring {}: {e}", path.display())),
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => errors.push(e),
+            }
+        }
+        (config, errors)
+    }
+
+    pub fn merge(&mut self, other: Config) {
+        let (game, window, colors) = (other.game, other.window, other.colors);
+        merge!(
+            self.game, game, cols, rows, tick_ms, seed, wrap, power_ups, mode, tail, grid, level,
+            steering, frontend
+        );
+        merge!(
+            self.window,
+            window,
+            tile_size,
+            fullscreen,
+            present_mode,
+            mute
+        );
+        merge!(
+            self.colors,
+            colors,
+            theme,
+            snake,
+            background,
+            food,
+            wall,
+            power_up,
+            hazard,
+            hud_background,
+            text
+        );
+
+        match (&mut self.bindings, other.bindings) {
+            (Some(bindings), Some(other)) => bindings.merge(other),
+            (bindings, other) => *bindings = other.or(bindings.take()),
+        }
+    }
+
+    /// Applies the config on top of `settings`, without validating
+    pub fn apply(&self, mut settings: Settings) -> Result<Settings, String> {
+        let game = &self.game;
+        if let Some(level) = &game.level {
+            settings.level = Level::find(level)?;
+            if let Some((width, height)) = settings.level.size() {
+                settings.cols = width;
+                settings.rows = height;
+            }
+        }
+        if game.seed.is_some() {
+            settings.seed = game.seed;
+        }
+        if let Some(cols) = game.cols {
+            settings.cols = cols;
+        }
+        if let Some(rows) = game.rows {
+            settings.rows = rows;
+        }
+        if let Some(tick_ms) = game.tick_ms {
+            settings.tick_ms = tick_ms;
+        }
+        if let Some(wrap) = game.wrap {
+            settings.wrap = wrap;
+        }
+        if let Some(power_ups) = game.power_ups {
+            settings.power_ups = power_ups;
+        }
+        if let Some(mode) = game.mode {
+            settings.mode = mode;
+        }
+        if let Some(tail) = game.tail {
+            settings.tail = tail;
+        }
+        if let Some(grid) = game.grid {
+            settings.grid = grid;
+        }
+        if let Some(steering) = game.steering {
+            settings.steering = steering;
+        }
+        if let Some(frontend) = game.frontend {
+            settings.frontend = frontend;
+        }
+
+        let window = &self.window;
+        if let Some(tile_size) = window.tile_size {
+            settings.tile_size = tile_size;
+        }
+        if let Some(fullscreen) = window.fullscreen {
+            settings.fullscreen = fullscreen;
+        }
+        if let Some(present_mode) = window.present_mode {
+            settings.present_mode = present_mode;
+        }
+        if let Some(mute) = window.mute {
+            settings.mute = mute;
+        }
+
+        settings.theme = self.theme(settings.theme)?;
+        Ok(settings)
+    }
+
+    fn theme(&self, mut theme: Theme) -> Result<Theme, String> {
+        let colors = &self.colors;
+        if let Some(name) = &colors.theme {
+            theme = Theme::find(name)?;
+        }
+
+        let overrides = [
+            (&colors.snake, &mut theme.snake),
+            (&colors.background, &mut theme.background),
+            (&colors.food, &mut theme.food),
+            (&colors.wall, &mut theme.wall),
+            (&colors.power_up, &mut theme.power_up),
+            (&colors.hazard, &mut theme.hazard),
+            (&colors.hud_background, &mut theme.hud_background),
+            (&colors.text, &mut theme.text),
+        ];
+        let mut custom = false;
+        for (text, color) in overrides {
+            if let Some(text) = text {
+                *color = theme::parse_color(text)?;
+                custom = true;
+            }
+        }
+        if custom {
+            theme.name = "CUSTOM";
+        }
+        Ok(theme)
+    }
+
+    /// Default bindings with the config's on top
+    pub fn bindings(&self) -> Bindings {
+        let mut bindings = Bindings::default();
+        if let Some(config) = &self.bindings {
+            bindings.merge(config.clone());
+        }
+        bindings
+    }
+}
+
+/// Where config files are looked for, lowest priority first
+pub fn search_paths(cli: &Cli) -> Vec<PathBuf> {
+    // The first of $XDG_CONFIG_DIRS is the most important one
+    let system = std::env::var_os("XDG_CONFIG_DIRS")
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/etc/xdg".into());
+    let mut dirs: Vec<PathBuf> = std::env::split_paths(&system).collect();
+    dirs.reverse();
+    dirs.extend(dirs::config_dir());
+
+    let mut paths: Vec<PathBuf> = dirs
+        .into_iter()
+        .map(|dir| dir.join("snake").join("snake.toml"))
+        .collect();
+    if let Some(path) = &cli.config {
+        paths.push(std::path::absolute(path).unwrap_or_else(|_| path.clone()));
+    }
+    paths
+}
+
+// Settings with the command line over `config`, and bindings with the ones
+// saved by the controls screen over the config's
+fn layer(
+    config: &Config,
+    cli: &Cli,
+    bindings_path: Option<&Path>,
+) -> Result<(Settings, Bindings), String> {
+    // `load_all` already left out the files that don't validate
+    let base = config.apply(Settings::default())?;
+    let settings = cli.apply(base)?;
+
+    let mut bindings = config.bindings();
+    if let Some(path) = bindings_path {
+        bindings = bindings.load_over(path);
+    }
+    Ok((settings, bindings))
+}
+
+/// Reads the config files and puts the command line on top. Only an
+/// invalid command line is an error, broken config files are logged.
+pub fn load(cli: &Cli, bindings_path: Option<&Path>) -> Result<(Settings, Bindings), String> {
+    let (config, errors) = Config::load_all(&search_paths(cli));
+    for e in errors {
+        log::error!("{e}");
+    }
+    layer(&config, cli, bindings_path)
+}
+
+/// Watches the config files and swaps in the settings that are safe to
+/// change mid game: colors, key bindings and speed.
+pub struct Watcher {
+    cli: Cli,
+    paths: Vec<PathBuf>,
+    bindings_path: Option<PathBuf>,
+
+    // What the last (re)load came up with
+    settings: Settings,
+    bindings: Bindings,
+
+    changes: Receiver<()>,
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl Watcher {
+    /// None (and logged) if the files can't be watched
+    pub fn new(
+        cli: &Cli,
+        settings: &Settings,
+        bindings: &Bindings,
+        bindings_path: Option<PathBuf>,
+    ) -> Option<Self> {
+        let paths = search_paths(cli);
+        let (sender, changes) = mpsc::channel();
+
+        let files = paths.clone();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if let Ok(event) = event {
+                if !event.kind.is_access() && event.paths.iter().any(|p| files.contains(p)) {
+                    let _ = sender.send(());
+                }
+            }
+        });
+        let mut watcher = match watcher {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                log::error!("can't watch the config files: {e}");
+                return None;
+            }
+        };
+
+        // Editors often replace the file rather than write to it, so watch
+        // the directories. Ones that don't exist yet are skipped.
+        for dir in paths.iter().filter_map(|p| p.parent()) {
+            if dir.is_dir() {
+                if let Err(e) = watcher.watch(dir, notify::RecursiveMode::NonRecursive) {
+                    log::warn!("can't watch {}: {e}", dir.display());
+                }
+            }
+        }
+
+        Some(Self {
+            cli: cli.clone(),
+            paths,
+            bindings_path,
+            settings: settings.clone(),
+            bindings: bindings.clone(),
+            changes,
+            _watcher: watcher,
+        })
+    }
+
+    /// Reloads the config if a file changed since the last call
+    pub fn poll(&mut self, app: &mut App) {
+        if self.changes.try_iter().count() == 0 {
+            return;
+        }
+
+        let (config, errors) = Config::load_all(&self.paths);
+        if !errors.is_empty() {
+            for e in errors {
+                log::error!("{e}");
+            }
+            log::error!("not reloading the config until it's fixed");
+            return;
+        }
+        let (settings, bindings) = match layer(&config, &self.cli, self.bindings_path.as_deref()) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                log::error!("not reloading the config: {e}");
+                return;
+            }
+        };
+
+        if settings.theme != self.settings.theme {
+            app.set_theme(settings.theme.clone());
+        }
+        if settings.tick_ms != self.settings.tick_ms {
+            app.set_tick_ms(settings.tick_ms);
+        }
+        if bindings != self.bindings {
+            app.set_bindings(bindings.clone());
+        }
+
+        let unsafe_changes = Settings {
+            theme: self.settings.theme.clone(),
+            tick_ms: self.settings.tick_ms,
+            ..settings.clone()
+        };
+        if unsafe_changes != self.settings {
+            log::warn!("config reloaded, board and window changes apply after a restart");
+        } else {
+            log::info!("config reloaded");
+        }
+
+        self.settings = settings;
+        self.bindings = bindings;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::keyboard::KeyCode;
+
+    use crate::input::Action;
+
+    #[test]
+    fn later_layers_override_earlier_ones() {
+        let mut config = Config::parse(
+            r#"
+            [game]
+            cols = 20
+            rows = 15
+            [colors]
+            theme = "nokia"
+            [bindings]
+            pause = ["KeyP"]
+            "#,
+        )
+        .unwrap();
+        config.merge(
+            Config::parse(
+                r#"
+                [game]
+                cols = 25
+                [window]
+                present_mode = "mailbox"
+                [bindings]
+                quit = ["F10"]
+                "#,
+            )
+            .unwrap(),
+        );
+
+        let settings = config.apply(Settings::default()).unwrap();
+        assert_eq!((settings.cols, settings.rows), (25, 15));
+        assert_eq!(settings.theme.name, "NOKIA");
+        assert_eq!(settings.present_mode, PresentMode::Mailbox);
+
+        let bindings = config.bindings();
+        assert_eq!(bindings.keys_for(Action::Pause), &[KeyCode::KeyP]);
+        assert_eq!(bindings.keys_for(Action::Quit), &[KeyCode::F10]);
+        assert_eq!(
+            bindings.keys_for(Action::Restart),
+            Bindings::default().keys_for(Action::Restart)
+        );
+    }
+
+    #[test]
+    fn colors_override_the_theme() {
+        let config = Config::parse(
+            r##"
+            [colors]
+            theme = "ocean"
+            food = "#ff0000"
+            "##,
+        )
+        .unwrap();
+        let theme = config.apply(Settings::default()).unwrap().theme;
+        let ocean = Theme::by_name("ocean").unwrap();
+        assert_eq!(theme.name, "CUSTOM");
+        assert_eq!(theme.food, [1., 0., 0.]);
+        assert_eq!(theme.snake, ocean.snake);
+
+        let bad = Config::parse("[colors]\nfood = \"red\"").unwrap();
+        assert!(bad.apply(Settings::default()).is_err());
+    }
+
+    #[test]
+    fn broken_files_are_skipped() {
+        let dir = std::env::temp_dir().join(format!("snake-config-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let good = dir.join("good.toml");
+        let broken = dir.join("broken.toml");
+        let invalid = dir.join("invalid.toml");
+        std::fs::write(&good, "[game]\ntick_ms = 50\nlevel = \"level.txt\"\n").unwrap();
+        std::fs::write(&broken, "[game]\ntick_ms = \"fast\"\n").unwrap();
+        // Parses, but leaves no room on the board
+        std::fs::write(&invalid, "[game]\ncols = 1\n[colors]\ntheme = \"ocean\"\n").unwrap();
+        std::fs::write(dir.join("level.txt"), "####\n#..#\n####\n").unwrap();
+
+        let paths = [good, broken, invalid, dir.join("missing.toml")];
+        let (config, errors) = Config::load_all(&paths);
+        let settings = config.apply(Settings::default());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Only the broken and invalid files are left out
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert!(errors[1].contains("invalid.toml"), "{errors:?}");
+        let settings = settings.unwrap();
+        assert_eq!(settings.tick_ms, 50);
+        assert_eq!((settings.cols, settings.rows), (4, 3));
+        assert_eq!(settings.theme, Theme::default());
+    }
+}
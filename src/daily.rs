@@ -0,0 +1,386 @@
+// The daily challenge: one board a day, the same for everyone. The seed,
+// mode, level and options all come from the UTC date, so two players on the
+// same day only differ by how they steer. A finished game can be shared as a
+// few lines of text, and anyone with its replay can check the score by
+// playing the inputs back on that day's board.
+//
+//     SNAKE DAILY 2026-10-19  SURVIVAL  PILLARS
+//     SCORE 12  LENGTH 9  TICKS 340
+//     ⬛⬛⬛⬛⬛⬛⬛⬛⬛⬛
+//     ⬛⬜⬛⬛🟩🟩⬛⬛⬜⬛
+//     ...
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::game::{Game, Pos, TailRule, Tile};
+use crate::level::Level;
+use crate::mode::GameMode;
+use crate::replay::Replay;
+use crate::settings::Settings;
+use crate::topology::Grid;
+
+/// Board size of every daily challenge
+pub const COLS: usize = 30;
+pub const ROWS: usize = 20;
+
+// Modes that end on their own, zen would go on forever
+const MODES: [GameMode; 4] = [
+    GameMode::Classic,
+    GameMode::TimeAttack,
+    GameMode::Survival,
+    GameMode::Puzzle,
+];
+
+// Widest the emoji board gets, in emoji
+const SHARE_WIDTH: usize = 10;
+
+/// A day, in UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    // Since 1970-01-01
+    days: i64,
+}
+
+impl Date {
+    pub fn today() -> Self {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        Self {
+            days: (secs / 86_400) as i64,
+        }
+    }
+
+    fn from_ymd(year: i64, month: i64, day: i64) -> Self {
+        // Howard Hinnant's days_from_civil, with years starting in March so
+        // the leap day comes last
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        Self {
+            days: era * 146_097 + day_of_era - 719_468,
+        }
+    }
+
+    /// (year, month, day)
+    pub fn ymd(self) -> (i64, i64, i64) {
+        let days = self.days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + (month <= 2) as i64;
+        (year, month, day)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (year, month, day) = self.ymd();
+        write!(f, "{year:04}-{month:02}-{day:02}")
+    }
+}
+
+impl FromStr for Date {
+    type Err = String;
+
+    /// Parses YYYY-MM-DD
+    fn from_str(text: &str) -> Result<Self, String> {
+        let invalid = || format!("'{text}' isn't a date, expected YYYY-MM-DD");
+        let parts: Vec<i64> = text
+            .split('-')
+            .map(|part| part.parse().map_err(|_| invalid()))
+            .collect::<Result<_, _>>()?;
+        let [year, month, day] = parts[..] else {
+            return Err(invalid());
+        };
+        // Before any arithmetic, so nothing can overflow
+        let in_range =
+            (0..=9999).contains(&year) && (1..=12).contains(&month) && (1..=31).contains(&day);
+        if !in_range {
+            return Err(invalid());
+        }
+        let date = Self::from_ymd(year, month, day);
+        // Days past the end of the month roll over into the next one
+        if date.ymd() != (year, month, day) {
+            return Err(invalid());
+        }
+        Ok(date)
+    }
+}
+
+// SplitMix64, so the seeds don't depend on how any library hashes
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Everything about a day's game that isn't up to the player
+#[derive(Debug, Clone, PartialEq)]
+pub struct Daily {
+    pub date: Date,
+    pub seed: u64,
+    pub mode: GameMode,
+    pub level: Level,
+    pub wrap: bool,
+    pub power_ups: bool,
+}
+
+impl Daily {
+    pub fn new(date: Date) -> Self {
+        let seed = mix(date.days as u64);
+        // Picked with their own rng, so the game's starts fresh from the seed
+        let mut rng = ChaCha8Rng::seed_from_u64(mix(seed));
+        let levels = Level::builtin();
+        Self {
+            date,
+            seed,
+            mode: *MODES.choose(&mut rng).unwrap(),
+            level: levels.choose(&mut rng).unwrap().clone(),
+            wrap: rng.gen_bool(0.3),
+            power_ups: rng.gen_bool(0.5),
+        }
+    }
+
+    /// Makes `settings` play the day's game, keeping the ones that don't
+    /// change it (speed, theme, frontend...)
+    pub fn apply(&self, settings: &mut Settings) {
+        settings.cols = COLS;
+        settings.rows = ROWS;
+        settings.grid = Grid::Square;
+        settings.seed = Some(self.seed);
+        settings.mode = self.mode;
+        settings.tail = TailRule::Follow;
+        settings.level = self.level.clone();
+        settings.wrap = self.wrap;
+        settings.power_ups = self.power_ups;
+        settings.daily = Some(self.date);
+    }
+
+    /// Checks `replay` was played on the day's board
+    pub fn check(&self, replay: &Replay) -> Result<(), String> {
+        let mismatch = |what: &str| Err(format!("the {what} isn't {}'s", self.date));
+        if replay.seed != self.seed {
+            return mismatch("seed");
+        }
+        if (replay.cols, replay.rows, replay.grid) != (COLS, ROWS, Grid::Square) {
+            return mismatch("board");
+        }
+        if replay.level != self.level.name || replay.level_map.is_some() {
+            return mismatch("level");
+        }
+        if (replay.mode, replay.wrap, replay.power_ups) != (self.mode, self.wrap, self.power_ups)
+            || replay.tail != TailRule::Follow
+        {
+            return mismatch("mode");
+        }
+        Ok(())
+    }
+
+    /// Plays a daily replay back on the board of the day it says it's from
+    /// and returns what to share, if it checks out
+    pub fn verify(replay: &Replay) -> Result<String, String> {
+        let Some(date) = &replay.daily else {
+            return Err("not a daily challenge replay".into());
+        };
+        let daily = Self::new(date.parse()?);
+        daily.check(replay)?;
+
+        let mut playback = replay.play(1.)?;
+        while playback.step() {}
+        Ok(daily.share(playback.game()))
+    }
+
+    /// Few lines of text about how `game` went, with a tiny picture of the
+    /// board
+    pub fn share(&self, game: &Game) -> String {
+        let mut text = format!(
+            "SNAKE DAILY {}  {}  {}\nSCORE {}  LENGTH {}  TICKS {}\n",
+            self.date,
+            self.mode.name(),
+            self.level.name,
+            game.score(),
+            game.snake_len(),
+            game.moves(),
+        );
+
+        // Each emoji stands for a block of tiles, showing the most
+        // interesting thing in it
+        let block = game.cols().div_ceil(SHARE_WIDTH).max(1);
+        for top in (0..game.rows())
+            .step_by(block)
+            .map(|row| game.rows() - 1 - row)
+        {
+            for left in (0..game.cols()).step_by(block) {
+                let tiles: Vec<Tile> = (left..(left + block).min(game.cols()))
+                    .flat_map(|x| (top.saturating_sub(block - 1)..=top).map(move |y| Pos { x, y }))
+                    .map(|pos| game.tile(pos))
+                    .collect();
+                text += if tiles
+                    .iter()
+                    .any(|t| matches!(t, Tile::SnakeHead | Tile::SnakeBody))
+                {
+                    "🟩"
+                } else if tiles.contains(&Tile::Food) {
+                    "🟥"
+                } else if tiles.contains(&Tile::Wall) {
+                    "⬜"
+                } else {
+                    "⬛"
+                };
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai;
+
+    #[test]
+    fn dates_parse_and_print_the_same() {
+        assert_eq!("1970-01-01".parse::<Date>(), Ok(Date { days: 0 }));
+        assert_eq!("1969-12-31".parse::<Date>(), Ok(Date { days: -1 }));
+        for days in (-700_000..800_000).step_by(997) {
+            let date = Date { days };
+            assert_eq!(date.to_string().parse::<Date>(), Ok(date));
+        }
+        assert_eq!(
+            "2000-02-29".parse::<Date>().unwrap().to_string(),
+            "2000-02-29"
+        );
+
+        for bad in [
+            "2001-02-29",
+            "2026-13-01",
+            "2026-00-10",
+            "2026-10",
+            "2026-9223372036854775807-01",
+            "2026-01-9223372036854775807",
+            "9223372036854775807-01-01",
+            "today",
+            "",
+        ] {
+            assert!(bad.parse::<Date>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn a_day_is_the_same_game_for_everyone() {
+        let date: Date = "2026-10-19".parse().unwrap();
+        assert_eq!(Daily::new(date), Daily::new(date));
+
+        let mut a = Settings::default();
+        let mut b = Settings {
+            cols: 12,
+            seed: Some(3),
+            mode: GameMode::Zen,
+            wrap: true,
+            tick_ms: 50,
+            ..Default::default()
+        };
+        Daily::new(date).apply(&mut a);
+        Daily::new(date).apply(&mut b);
+        let game = |s: &Settings| (s.cols, s.rows, s.grid, s.seed, s.mode, s.wrap, s.power_ups);
+        assert_eq!(game(&a), game(&b));
+        assert_eq!(a.level, b.level);
+        assert_eq!(b.tick_ms, 50);
+        assert_eq!(a.daily, Some(date));
+
+        // And the days differ
+        let seeds: Vec<u64> = (0..30).map(|days| Daily::new(Date { days }).seed).collect();
+        assert!((1..seeds.len()).all(|i| !seeds[..i].contains(&seeds[i])));
+        assert!((0..30).any(|days| Daily::new(Date { days }).mode != GameMode::Classic));
+    }
+
+    fn played(daily: &Daily) -> (Game, Replay) {
+        let mut settings = Settings::default();
+        daily.apply(&mut settings);
+        let mut game = settings.new_game();
+        for _ in 0..300 {
+            game.change_direction(ai::greedy(&game));
+            if !game.update() {
+                break;
+            }
+        }
+        let mut replay = Replay::record(&game, settings.tick_ms);
+        replay.daily = Some(daily.date.to_string());
+        (game, replay)
+    }
+
+    #[test]
+    fn verifying_plays_the_replay_back_on_its_day() {
+        let daily = Daily::new("2026-10-19".parse().unwrap());
+        let (game, replay) = played(&daily);
+        assert_eq!(Daily::verify(&replay), Ok(daily.share(&game)));
+
+        let mut tampered = replay.clone();
+        tampered.seed += 1;
+        assert!(Daily::verify(&tampered).unwrap_err().contains("seed"));
+
+        let mut tampered = replay.clone();
+        tampered.mode = GameMode::Zen;
+        assert!(Daily::verify(&tampered).unwrap_err().contains("mode"));
+
+        // Someone else's day
+        let mut tampered = replay.clone();
+        tampered.daily = Some("2026-10-20".into());
+        assert!(Daily::verify(&tampered).is_err());
+
+        let mut tampered = replay;
+        tampered.daily = None;
+        assert!(Daily::verify(&tampered).is_err());
+    }
+
+    #[test]
+    fn shares_the_result_and_a_tiny_board() {
+        let daily = Daily::new("2026-10-19".parse().unwrap());
+        let (game, _) = played(&daily);
+        let share = daily.share(&game);
+        let lines: Vec<&str> = share.lines().collect();
+        assert_eq!(
+            lines[0],
+            format!(
+                "SNAKE DAILY 2026-10-19  {}  {}",
+                daily.mode.name(),
+                daily.level.name
+            )
+        );
+        assert_eq!(
+            lines[1],
+            format!(
+                "SCORE {}  LENGTH {}  TICKS {}",
+                game.score(),
+                game.snake_len(),
+                game.moves()
+            )
+        );
+
+        // 30x20 tiles in blocks of 3
+        let board = &lines[2..];
+        assert_eq!(board.len(), 7);
+        assert!(board.iter().all(|row| row.chars().count() == SHARE_WIDTH));
+        assert!(share.contains('🟩'));
+    }
+}
@@ -0,0 +1,160 @@
+// Things on the board that move by themselves: blocks patrolling back and
+// forth, balls bouncing around and hunters going after the nearest snake.
+// They sit on top of the tiles instead of being tiles, so food and power-ups
+// can end up underneath one for a while.
+//
+// On every tick that something moves, the snakes go first and then each
+// entity in the order the level placed them, if its period says it's its
+// turn. A snake running into an entity dies, what an entity does when it
+// runs into a snake depends on its kind.
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::game::{Direction, Game, Pos, SnakeId, Tile};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+    /// Goes back and forth in a line, turning around at whatever is in its
+    /// way, snakes included
+    Block,
+    /// Flies straight until something is in the way, then off in a random
+    /// direction. Flying into a snake's head kills it.
+    Ball,
+    /// Closes in on the nearest snake's head, slowly, and kills it
+    Hunter,
+}
+
+impl EntityKind {
+    /// Ticks from one move to the next
+    pub fn period(self) -> u64 {
+        match self {
+            EntityKind::Block => 2,
+            EntityKind::Ball => 1,
+            EntityKind::Hunter => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Entity {
+    pub kind: EntityKind,
+    pub pos: Pos,
+    // Stationary until it first moves, for the kinds that pick their own way
+    pub direction: Direction,
+}
+
+// What's on a tile, to an entity about to move onto it
+enum Ahead {
+    Open,
+    Head(SnakeId),
+    Blocked,
+}
+
+fn ahead(game: &Game, entities: &[Entity], pos: Option<Pos>) -> Ahead {
+    let Some(pos) = pos else {
+        return Ahead::Blocked;
+    };
+    if entities.iter().any(|entity| entity.pos == pos) {
+        return Ahead::Blocked;
+    }
+    match game.tile(pos) {
+        Tile::Empty | Tile::Food | Tile::PowerUp => Ahead::Open,
+        Tile::SnakeHead => match game.snake_at(pos) {
+            Some(id) if game.death_cause(id).is_none() => Ahead::Head(id),
+            _ => Ahead::Blocked,
+        },
+        _ => Ahead::Blocked,
+    }
+}
+
+// The way back, half way round the board's directions
+fn reverse(game: &Game, direction: Direction) -> Direction {
+    let directions = game.topology().directions();
+    match directions.iter().position(|d| *d == direction) {
+        Some(i) => directions[(i + directions.len() / 2) % directions.len()],
+        None => direction,
+    }
+}
+
+impl Entity {
+    /// Makes one move on `game`, where `entities` are all of them (this one
+    /// included) as they are now. Returns the snake it ran into head first,
+    /// if it's the kind that kills.
+    pub fn update(
+        &mut self,
+        game: &Game,
+        entities: &[Entity],
+        rng: &mut impl Rng,
+    ) -> Option<SnakeId> {
+        match self.kind {
+            EntityKind::Block => {
+                let next = game.step(self.pos, self.direction);
+                match ahead(game, entities, next) {
+                    Ahead::Open => self.pos = next.unwrap(),
+                    Ahead::Head(_) | Ahead::Blocked => {
+                        self.direction = reverse(game, self.direction)
+                    }
+                }
+                None
+            }
+            EntityKind::Ball => {
+                let next = game.step(self.pos, self.direction);
+                match ahead(game, entities, next) {
+                    Ahead::Open => self.pos = next.unwrap(),
+                    Ahead::Head(id) => return Some(id),
+                    Ahead::Blocked => {
+                        // Bounce off in a new direction, if there's anywhere to go
+                        let open: Vec<Direction> = game
+                            .topology()
+                            .directions()
+                            .iter()
+                            .copied()
+                            .filter(|&d| {
+                                let next = game.step(self.pos, d);
+                                matches!(ahead(game, entities, next), Ahead::Open)
+                            })
+                            .collect();
+                        if let Some(&direction) = open.choose(rng) {
+                            self.direction = direction;
+                            self.pos = game.step(self.pos, direction).unwrap();
+                        }
+                    }
+                }
+                None
+            }
+            EntityKind::Hunter => {
+                let topology = game.topology();
+                let target = game
+                    .snakes()
+                    .filter(|snake| snake.alive)
+                    .filter_map(|snake| game.snake_head(snake.id))
+                    .min_by_key(|head| topology.distance(self.pos, *head))?;
+
+                // Only ever gets closer, waiting if it can't
+                let (direction, next, distance) = topology
+                    .directions()
+                    .iter()
+                    .filter_map(|&d| {
+                        let next = game.step(self.pos, d)?;
+                        Some((d, next, topology.distance(next, target)))
+                    })
+                    .filter(|(_, next, _)| {
+                        !matches!(ahead(game, entities, Some(*next)), Ahead::Blocked)
+                    })
+                    .min_by_key(|(_, _, distance)| *distance)?;
+                if distance >= topology.distance(self.pos, target) {
+                    return None;
+                }
+                if let Ahead::Head(id) = ahead(game, entities, Some(next)) {
+                    return Some(id);
+                }
+                self.pos = next;
+                self.direction = direction;
+                None
+            }
+        }
+    }
+}
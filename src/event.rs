@@ -0,0 +1,117 @@
+// What happened during a game, for whatever wants to react to it (sound,
+// particles, stats, bots) without comparing boards from one tick to the
+// next. `Game` queues events as they happen and `Game::drain_events` hands
+// them over, oldest first.
+//
+// Nothing has to drain them: the queue only keeps the latest `MAX_EVENTS`,
+// so a headless game that never looks doesn't pile them up.
+
+use std::collections::VecDeque;
+
+use serde::Serialize;
+
+use crate::game::{DeathCause, Direction, Effect, Pos, SnakeId};
+use crate::mode::Ending;
+
+/// Events kept before the oldest start being dropped, far more than a tick
+/// ever makes
+pub const MAX_EVENTS: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// A snake was steered a new way, it goes that way on its next move
+    Turned {
+        snake: SnakeId,
+        direction: Direction,
+    },
+    FoodEaten {
+        snake: SnakeId,
+        pos: Pos,
+        points: u32,
+    },
+    FoodSpawned {
+        pos: Pos,
+    },
+    /// Got longer, the tail staying put on the move after eating
+    Grew {
+        snake: SnakeId,
+        length: usize,
+    },
+    PowerUpSpawned {
+        pos: Pos,
+        effect: Effect,
+    },
+    /// Nobody picked it up in time
+    PowerUpVanished {
+        pos: Pos,
+    },
+    PowerUpStarted {
+        snake: SnakeId,
+        effect: Effect,
+    },
+    PowerUpEnded {
+        snake: SnakeId,
+        effect: Effect,
+    },
+    /// A shield took a hit that would've killed the snake, and is used up.
+    /// Other effects end with `PowerUpEnded`.
+    Shielded {
+        snake: SnakeId,
+    },
+    /// Put up by the rules, see `Game::raise_wall`
+    WallRaised {
+        pos: Pos,
+    },
+    Died {
+        snake: SnakeId,
+        cause: DeathCause,
+    },
+    /// The last event of a game
+    Ended {
+        ending: Ending,
+    },
+}
+
+/// Events not drained yet, see `Game::drain_events`
+#[derive(Debug, Clone, Default)]
+pub struct Events {
+    queue: VecDeque<Event>,
+}
+
+impl Events {
+    pub fn push(&mut self, event: Event) {
+        if self.queue.len() == MAX_EVENTS {
+            self.queue.pop_front();
+        }
+        self.queue.push_back(event);
+    }
+
+    pub fn drain(&mut self) -> impl Iterator<Item = Event> + '_ {
+        self.queue.drain(..)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_latest_events_are_kept() {
+        let mut events = Events::default();
+        for x in 0..MAX_EVENTS + 10 {
+            events.push(Event::FoodSpawned {
+                pos: Pos { x, y: 0 },
+            });
+        }
+        let drained: Vec<Event> = events.drain().collect();
+        assert_eq!(drained.len(), MAX_EVENTS);
+        assert_eq!(
+            drained[0],
+            Event::FoodSpawned {
+                pos: Pos { x: 10, y: 0 }
+            }
+        );
+        assert_eq!(events.drain().count(), 0);
+    }
+}
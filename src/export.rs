@@ -0,0 +1,324 @@
+// Replays to animated GIFs and APNGs. Frames are simulated through
+// `Game::update` and drawn by the CPU rasterizer, so this works on machines
+// without a GPU or a display.
+
+use std::io::Write;
+use std::path::Path;
+
+use crate::cli::ExportArgs;
+use crate::raster::{self, Image};
+use crate::replay::Replay;
+use crate::scene::{self, Scene};
+use crate::theme::Theme;
+
+// How long the last frame stays up before the animation loops
+const LAST_FRAME_MS: u64 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Gif,
+    Apng,
+}
+
+impl Format {
+    /// Format for the extension of `path`
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "gif" => Ok(Format::Gif),
+            "png" | "apng" => Ok(Format::Apng),
+            _ => Err(format!(
+                "can't tell the format of {}, use .gif or .png",
+                path.display()
+            )),
+        }
+    }
+}
+
+/// A frame and how long it's shown for
+struct Frame {
+    image: Image,
+    delay_ms: u64,
+}
+
+// Moves that get a frame: the start, every `skip`th move and the last one
+fn keeps_frame(moves: u64, total: u64, skip: u64) -> bool {
+    moves.is_multiple_of(skip) || moves == total
+}
+
+/// Plays `replay` back, handing every kept frame to `frame`
+fn frames(
+    replay: &Replay,
+    theme: &Theme,
+    args: &ExportArgs,
+    mut frame: impl FnMut(Frame) -> Result<(), String>,
+) -> Result<(), String> {
+    let tile_size = replay.tile_size * args.scale;
+    if tile_size < 1. {
+        return Err(format!(
+            "tiles would be {tile_size} pixels wide, the scale has to be larger"
+        ));
+    }
+
+    let total = total_moves(replay)?;
+    let delay_ms = replay.tick_ms * args.skip;
+    let mut playback = replay.play(tile_size)?;
+    loop {
+        let game = playback.game();
+        let moves = game.moves();
+        if keeps_frame(moves, total, args.skip) {
+            let scene = Scene {
+                game,
+                theme,
+                hud: args.hud.then(|| scene::hud_text(game, replay.tick_ms)),
+                overlay: None,
+                particles: None,
+            };
+            let delay_ms = if moves == total {
+                delay_ms.max(LAST_FRAME_MS)
+            } else {
+                delay_ms
+            };
+            frame(Frame {
+                image: raster::render(&scene),
+                delay_ms,
+            })?;
+        }
+        if !playback.step() {
+            return Ok(());
+        }
+    }
+}
+
+// Moves the replay actually plays for, the last one always gets a frame
+fn total_moves(replay: &Replay) -> Result<u64, String> {
+    let mut playback = replay.play(replay.tile_size)?;
+    while playback.step() {}
+    Ok(playback.game().moves())
+}
+
+fn frame_count(total: u64, skip: u64) -> u32 {
+    (1 + total.div_ceil(skip)) as u32
+}
+
+// Frames only use a handful of colors, so an exact palette is tried first
+// and quantizing is the fallback
+fn gif_frame(image: &Image) -> gif::Frame<'static> {
+    let (width, height) = (image.width as u16, image.height as u16);
+
+    let mut palette: Vec<[u8; 3]> = vec![];
+    let mut indices = Vec::with_capacity(image.pixels.len() / 4);
+    for pixel in image.pixels.chunks_exact(4) {
+        let color = [pixel[0], pixel[1], pixel[2]];
+        let index = match palette.iter().position(|c| *c == color) {
+            Some(index) => index,
+            None if palette.len() < 256 => {
+                palette.push(color);
+                palette.len() - 1
+            }
+            None => {
+                let mut pixels = image.pixels.clone();
+                return gif::Frame::from_rgba_speed(width, height, &mut pixels, 10);
+            }
+        };
+        indices.push(index as u8);
+    }
+
+    let palette: Vec<u8> = palette.into_iter().flatten().collect();
+    gif::Frame::from_palette_pixels(width, height, indices, palette, None)
+}
+
+fn encode_gif(
+    replay: &Replay,
+    theme: &Theme,
+    args: &ExportArgs,
+    out: impl Write,
+) -> Result<(), String> {
+    let mut out = Some(out);
+    let mut encoder = None;
+    frames(replay, theme, args, |frame| {
+        let image = &frame.image;
+        if image.width > u16::MAX as u32 || image.height > u16::MAX as u32 {
+            return Err(format!(
+                "{}x{} frames are too big for a GIF",
+                image.width, image.height
+            ));
+        }
+        if encoder.is_none() {
+            let mut gif = gif::Encoder::new(
+                out.take().unwrap(),
+                image.width as u16,
+                image.height as u16,
+                &[],
+            )
+            .map_err(|e| e.to_string())?;
+            gif.set_repeat(gif::Repeat::Infinite)
+                .map_err(|e| e.to_string())?;
+            encoder = Some(gif);
+        }
+
+        let mut gif_frame = gif_frame(image);
+        // GIF delays are in hundredths of a second
+        gif_frame.delay = (frame.delay_ms / 10).clamp(1, u16::MAX as u64) as u16;
+        encoder
+            .as_mut()
+            .unwrap()
+            .write_frame(&gif_frame)
+            .map_err(|e| e.to_string())
+    })
+}
+
+fn encode_apng(
+    replay: &Replay,
+    theme: &Theme,
+    args: &ExportArgs,
+    out: impl Write,
+) -> Result<(), String> {
+    let error = |e: png::EncodingError| e.to_string();
+
+    let count = frame_count(total_moves(replay)?, args.skip);
+    let mut out = Some(out);
+    let mut writer = None;
+    frames(replay, theme, args, |frame| {
+        let image = &frame.image;
+        if writer.is_none() {
+            let mut encoder = png::Encoder::new(out.take().unwrap(), image.width, image.height);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_animated(count, 0).map_err(error)?;
+            writer = Some(encoder.write_header().map_err(error)?);
+        }
+
+        let writer = writer.as_mut().unwrap();
+        writer
+            .set_frame_delay(frame.delay_ms.min(u16::MAX as u64) as u16, 1000)
+            .map_err(error)?;
+        writer.write_image_data(&image.pixels).map_err(error)
+    })?;
+
+    match writer {
+        Some(writer) => writer.finish().map_err(error),
+        None => Ok(()),
+    }
+}
+
+/// Writes `replay` to `out` as an animation
+pub fn encode(
+    replay: &Replay,
+    theme: &Theme,
+    args: &ExportArgs,
+    format: Format,
+    out: impl Write,
+) -> Result<(), String> {
+    match format {
+        Format::Gif => encode_gif(replay, theme, args, out),
+        Format::Apng => encode_apng(replay, theme, args, out),
+    }
+}
+
+/// Runs `snake export`
+pub fn run(args: &ExportArgs, theme: &Theme) -> Result<(), String> {
+    let format = Format::from_path(&args.out)?;
+    let replay = Replay::load(&args.replay)?;
+
+    let error = |e: &dyn std::fmt::Display| format!("couldn't write {}: {e}", args.out.display());
+    let file = std::fs::File::create(&args.out).map_err(|e| error(&e))?;
+    let mut out = std::io::BufWriter::new(file);
+    encode(&replay, theme, args, format, &mut out)?;
+    out.flush().map_err(|e| error(&e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai;
+    use crate::game::{Game, GameOptions};
+
+    fn replay() -> Replay {
+        let options = GameOptions {
+            seed: Some(5),
+            ..Default::default()
+        };
+        let mut game = Game::with_options(10, 8, 4., options);
+        for _ in 0..25 {
+            game.change_direction(ai::greedy(&game));
+            if !game.update() {
+                break;
+            }
+        }
+        Replay::record(&game, 100)
+    }
+
+    fn args(skip: u64, scale: f32, hud: bool) -> ExportArgs {
+        ExportArgs {
+            replay: "replay.toml".into(),
+            out: "out.gif".into(),
+            skip,
+            scale,
+            hud,
+        }
+    }
+
+    #[test]
+    fn formats_come_from_the_extension() {
+        assert_eq!(Format::from_path(Path::new("a.GIF")), Ok(Format::Gif));
+        assert_eq!(Format::from_path(Path::new("a.png")), Ok(Format::Apng));
+        assert!(Format::from_path(Path::new("a.mp4")).is_err());
+    }
+
+    #[test]
+    fn gifs_have_a_frame_per_kept_move() {
+        let replay = replay();
+        let total = replay.moves;
+        let mut gif = vec![];
+        encode(
+            &replay,
+            &Theme::default(),
+            &args(4, 2., false),
+            Format::Gif,
+            &mut gif,
+        )
+        .unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(gif.as_slice()).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (80, 64));
+
+        let mut delays = vec![];
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            delays.push(frame.delay);
+        }
+        assert_eq!(delays.len() as u32, frame_count(total, 4));
+        assert_eq!(delays[0], 40);
+        assert_eq!(*delays.last().unwrap(), 100);
+    }
+
+    #[test]
+    fn apngs_match_the_rasterized_frames() {
+        let replay = replay();
+        let args = args(1, 1., true);
+        let mut apng = vec![];
+        encode(&replay, &Theme::default(), &args, Format::Apng, &mut apng).unwrap();
+
+        let mut decoder = png::Decoder::new(apng.as_slice()).read_info().unwrap();
+        let animation = decoder.info().animation_control().unwrap();
+        assert_eq!(animation.num_frames as u64, replay.moves + 1);
+
+        // First frame is the game before any moves, with the HUD on top
+        let mut pixels = vec![0; decoder.output_buffer_size()];
+        decoder.next_frame(&mut pixels).unwrap();
+        let game = replay.start(replay.tile_size).unwrap();
+        let scene = Scene {
+            game: &game,
+            theme: &Theme::default(),
+            hud: Some(scene::hud_text(&game, replay.tick_ms)),
+            overlay: None,
+            particles: None,
+        };
+        assert_eq!(pixels, raster::render(&scene).pixels);
+    }
+}
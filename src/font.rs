@@ -0,0 +1,190 @@
+// Tiny bundled 5x7 bitmap font, used for the HUD and overlay messages.
+// Lowercase letters are drawn with the uppercase glyphs, anything we don't
+// have a glyph for falls back to '?'.
+
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+
+// Each glyph gets an 8x8 cell in the atlas so nearest sampling never bleeds
+// into the neighbouring glyph.
+pub const CELL_SIZE: u32 = 8;
+const ATLAS_COLS: u32 = 16;
+
+// Horizontal advance between glyphs, in font pixels
+pub const ADVANCE: u32 = GLYPH_WIDTH + 1;
+
+// Rows top to bottom, the lowest 5 bits of each row are the pixels left to right
+#[rustfmt::skip]
+const GLYPHS: &[(char, [u8; 7])] = &[
+    (' ', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('A', [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('B', [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110]),
+    ('C', [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110]),
+    ('D', [0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110]),
+    ('E', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111]),
+    ('F', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('G', [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111]),
+    ('H', [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('I', [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('J', [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100]),
+    ('K', [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001]),
+    ('L', [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111]),
+    ('M', [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001]),
+    ('N', [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001]),
+    ('O', [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('P', [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('Q', [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101]),
+    ('R', [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001]),
+    ('S', [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110]),
+    ('T', [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100]),
+    ('U', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('V', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100]),
+    ('W', [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010]),
+    ('X', [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001]),
+    ('Y', [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100]),
+    ('Z', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111]),
+    ('0', [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110]),
+    ('1', [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('2', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111]),
+    ('3', [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110]),
+    ('4', [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010]),
+    ('5', [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110]),
+    ('6', [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110]),
+    ('7', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000]),
+    ('8', [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110]),
+    ('9', [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100]),
+    ('.', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100]),
+    (',', [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000]),
+    (':', [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000]),
+    ('-', [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000]),
+    ('+', [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000]),
+    ('=', [0b00000, 0b00000, 0b11111, 0b00000, 0b11111, 0b00000, 0b00000]),
+    ('_', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111]),
+    ('!', [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100]),
+    ('?', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100]),
+    ('/', [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000]),
+    ('>', [0b01000, 0b00100, 0b00010, 0b00001, 0b00010, 0b00100, 0b01000]),
+    ('<', [0b00010, 0b00100, 0b01000, 0b10000, 0b01000, 0b00100, 0b00010]),
+    ('(', [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010]),
+    (')', [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000]),
+    ('[', [0b01110, 0b01000, 0b01000, 0b01000, 0b01000, 0b01000, 0b01110]),
+    (']', [0b01110, 0b00010, 0b00010, 0b00010, 0b00010, 0b00010, 0b01110]),
+    ('\'', [0b01100, 0b00100, 0b01000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('%', [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011]),
+    ('#', [0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010]),
+    ('*', [0b00000, 0b00100, 0b10101, 0b01110, 0b10101, 0b00100, 0b00000]),
+];
+
+// Fully lit cell, lets the text pipeline also draw plain rectangles
+// (HUD background, overlay dimming) without a second texture.
+pub const SOLID: usize = GLYPHS.len();
+
+fn glyph_index(c: char) -> usize {
+    let c = c.to_ascii_uppercase();
+    GLYPHS
+        .iter()
+        .position(|(g, _)| *g == c)
+        .unwrap_or_else(|| GLYPHS.iter().position(|(g, _)| *g == '?').unwrap())
+}
+
+/// Whether pixel (`x`, `y`) of the glyph is lit, `y` counted from the top
+pub fn glyph_pixel(rows: &[u8; 7], x: u32, y: u32) -> bool {
+    x < GLYPH_WIDTH && y < GLYPH_HEIGHT && rows[y as usize] & (1 << (GLYPH_WIDTH - 1 - x)) != 0
+}
+
+/// Size in pixels of `text` rendered at `scale`
+pub fn text_size(text: &str, scale: f32) -> (f32, f32) {
+    let len = text.chars().count() as u32;
+    let width = if len == 0 { 0 } else { len * ADVANCE - 1 };
+    (width as f32 * scale, GLYPH_HEIGHT as f32 * scale)
+}
+
+pub struct Atlas {
+    pub width: u32,
+    pub height: u32,
+    // One byte per pixel, 0 or 255
+    pub pixels: Vec<u8>,
+}
+
+impl Atlas {
+    pub fn new() -> Self {
+        let cells = GLYPHS.len() as u32 + 1; // + SOLID
+        let width = ATLAS_COLS * CELL_SIZE;
+        let height = cells.div_ceil(ATLAS_COLS) * CELL_SIZE;
+        let mut pixels = vec![0; (width * height) as usize];
+
+        const SOLID_ROWS: [u8; 7] = [0b11111; 7];
+        let cell_rows = GLYPHS.iter().map(|(_, rows)| rows).chain([&SOLID_ROWS]);
+        for (cell, rows) in cell_rows.enumerate() {
+            let (cx, cy) = Self::cell_origin(cell);
+            for y in 0..GLYPH_HEIGHT {
+                for x in 0..GLYPH_WIDTH {
+                    let lit = glyph_pixel(rows, x, y);
+                    if lit {
+                        pixels[((cy + y) * width + cx + x) as usize] = 255;
+                    }
+                }
+            }
+        }
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    fn cell_origin(cell: usize) -> (u32, u32) {
+        let cell = cell as u32;
+        (
+            (cell % ATLAS_COLS) * CELL_SIZE,
+            (cell / ATLAS_COLS) * CELL_SIZE,
+        )
+    }
+
+    /// Normalized texture coordinates of a glyph: (left, top, right, bottom)
+    pub fn uv_rect(&self, cell: usize) -> [f32; 4] {
+        let (x, y) = Self::cell_origin(cell);
+        let (w, h) = (self.width as f32, self.height as f32);
+        [
+            x as f32 / w,
+            y as f32 / h,
+            (x + GLYPH_WIDTH) as f32 / w,
+            (y + GLYPH_HEIGHT) as f32 / h,
+        ]
+    }
+
+    pub fn uv_for_char(&self, c: char) -> [f32; 4] {
+        self.uv_rect(glyph_index(c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lowercase_and_unknown_chars_map_to_glyphs() {
+        assert_eq!(glyph_index('a'), glyph_index('A'));
+        assert_eq!(glyph_index('~'), glyph_index('?'));
+        assert_eq!(GLYPHS[glyph_index(' ')].1, [0; 7]);
+    }
+
+    #[test]
+    fn atlas_contains_glyph_pixels() {
+        let atlas = Atlas::new();
+        // Top row of 'T' is fully lit
+        let [l, t, _, _] = atlas.uv_for_char('T');
+        let x = (l * atlas.width as f32) as u32;
+        let y = (t * atlas.height as f32) as u32;
+        for i in 0..GLYPH_WIDTH {
+            assert_eq!(atlas.pixels[(y * atlas.width + x + i) as usize], 255);
+        }
+    }
+
+    #[test]
+    fn text_size_accounts_for_spacing() {
+        assert_eq!(text_size("", 1.), (0., 7.));
+        assert_eq!(text_size("AB", 2.), (22., 14.));
+    }
+}
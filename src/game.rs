@@ -1,404 +1,2126 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crate::entity::Entity;
+use crate::event::{Event, Events};
+use crate::level::Level;
+use crate::mode::{self, Ending, GameMode, Rules};
 use crate::shader;
+use crate::topology::{Grid, Topology};
 
 use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
 
 #[repr(C)]
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Direction {
     Stationary = 0,
     Right,
     Left,
     Up,
     Down,
+    // Only on hex boards
+    UpRight,
+    UpLeft,
+    DownRight,
+    DownLeft,
+}
+
+/// Steering relative to the way the snake is heading, for one handed and
+/// Nokia style controls. Also a three action space for bots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Turn {
+    Left,
+    Straight,
+    Right,
 }
 
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Tile {
     Empty = 0,
     Food,
     SnakeBody,
     SnakeHead,
+    Wall,
+    // Not part of the board, on boards that aren't rectangles
+    Void,
+    // Snakes going in come out of its partner, see `Game::portals`
+    Portal,
+    // A pickup, see `POWER_UPS`
+    PowerUp,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub struct Pos {
-    x: usize,
-    y: usize,
+    pub x: usize,
+    pub y: usize,
+}
+
+/// What a snake ran into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeathCause {
+    /// Left the board, without wrap
+    Edge,
+    Wall,
+    Itself,
+    /// Another snake's head or body
+    Snake,
+    /// Ran into an entity, or one of them into it
+    Hazard,
+}
+
+/// Whether a head may move into the tile its own tail leaves on the same move
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum TailRule {
+    /// It may, the tail is out of the way by then. A snake can chase its
+    /// own tail forever.
+    #[default]
+    Follow,
+    /// It may not, the tail counts as body until the move is over
+    Strict,
 }
 
+/// Index of a snake in a game. Single player games only have snake 0.
+pub type SnakeId = usize;
+
+#[derive(Clone)]
 struct Snake {
-    tiles: Vec<Pos>,
+    // Head first, so a move is a push at the front and a pop at the back
+    tiles: VecDeque<Pos>,
     direction: Direction,
+
+    // Moves left that the tail stays put on, one for every food eaten
+    growth: usize,
+
+    // One point per food eaten, more while an effect multiplies them
+    score: u32,
+    // Dead snakes stay on the board, frozen, until they're removed
+    death: Option<DeathCause>,
+
+    // Power-ups picked up that haven't run out yet, oldest first
+    effects: Vec<ActiveEffect>,
+}
+
+impl Snake {
+    fn new(head: Pos) -> Self {
+        Self {
+            tiles: VecDeque::from([head]),
+            direction: Direction::Stationary,
+            growth: 0,
+            score: 0,
+            death: None,
+            effects: vec![],
+        }
+    }
+
+    // Uses up a shield if the snake has one, true if it did
+    fn take_hit(&mut self) -> bool {
+        let shield = self
+            .effects
+            .iter()
+            .position(|active| active.effect.power_up().shield);
+        shield.map(|i| self.effects.remove(i)).is_some()
+    }
+
+    // Counts down every effect by a move, dropping the ones that ran out
+    fn tick_effects(&mut self, mut ended: impl FnMut(Effect)) {
+        self.effects.retain_mut(|active| {
+            active.ticks_left -= 1;
+            if active.ticks_left == 0 {
+                ended(active.effect);
+            }
+            active.ticks_left > 0
+        });
+    }
+}
+
+/// What other players get to know about a snake
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnakeInfo {
+    pub id: SnakeId,
+    pub alive: bool,
+    pub score: u32,
+    pub length: usize,
 }
 
+/// Kinds of power-up, see `POWER_UPS` for what each one does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Effect {
+    Ghost,
+    Slow,
+    DoubleScore,
+    Magnet,
+    Shield,
+    Speed,
+}
+
+/// A row of `POWER_UPS`: how long an effect lasts and what it changes while
+/// it does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerUp {
+    pub effect: Effect,
+    // Shown on the HUD
+    pub name: &'static str,
+    // Moves it lasts once picked up
+    pub ticks: u32,
+    // How often it spawns compared to the others
+    pub weight: u32,
+    // Food is worth this many points
+    pub score_multiplier: u32,
+    // Time between moves is this many times as long, in single player
+    pub slowdown: u64,
+    // And divided by this
+    pub speedup: u64,
+    // Moving into its own body doesn't kill the snake
+    pub ghost: bool,
+    // Food drifts a tile towards the head every move
+    pub magnet: bool,
+    // A collision uses the effect up instead of killing the snake, which
+    // sits that move out
+    pub shield: bool,
+}
+
+// Changes nothing, the base every row starts from
+const NO_EFFECT: PowerUp = PowerUp {
+    effect: Effect::Ghost,
+    name: "",
+    ticks: 0,
+    weight: 0,
+    score_multiplier: 1,
+    slowdown: 1,
+    speedup: 1,
+    ghost: false,
+    magnet: false,
+    shield: false,
+};
+
+/// Every power-up there is. A snake can have any number of effects at once,
+/// the same one more than once too, and they all count: multipliers
+/// multiply and flags are on if any effect sets them.
+pub const POWER_UPS: [PowerUp; 6] = [
+    PowerUp {
+        effect: Effect::Ghost,
+        name: "GHOST",
+        ticks: 30,
+        weight: 2,
+        ghost: true,
+        ..NO_EFFECT
+    },
+    PowerUp {
+        effect: Effect::Slow,
+        name: "SLOW",
+        ticks: 40,
+        weight: 2,
+        slowdown: 2,
+        ..NO_EFFECT
+    },
+    PowerUp {
+        effect: Effect::DoubleScore,
+        name: "2X",
+        ticks: 60,
+        weight: 3,
+        score_multiplier: 2,
+        ..NO_EFFECT
+    },
+    PowerUp {
+        effect: Effect::Magnet,
+        name: "MAGNET",
+        ticks: 40,
+        weight: 2,
+        magnet: true,
+        ..NO_EFFECT
+    },
+    PowerUp {
+        effect: Effect::Shield,
+        name: "SHIELD",
+        ticks: 100,
+        weight: 1,
+        shield: true,
+        ..NO_EFFECT
+    },
+    PowerUp {
+        effect: Effect::Speed,
+        name: "FAST",
+        ticks: 40,
+        weight: 2,
+        speedup: 2,
+        ..NO_EFFECT
+    },
+];
+
+impl Effect {
+    /// Its row of `POWER_UPS`
+    pub fn power_up(self) -> &'static PowerUp {
+        POWER_UPS
+            .iter()
+            .find(|power_up| power_up.effect == self)
+            .expect("every effect has a row")
+    }
+}
+
+/// An effect a snake has, and the moves it has left
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ActiveEffect {
+    pub effect: Effect,
+    pub ticks_left: u32,
+}
+
+// What a snake's effects add up to
+struct Boosts {
+    score_multiplier: u32,
+    slowdown: u64,
+    speedup: u64,
+    ghost: bool,
+    magnet: bool,
+}
+
+impl Boosts {
+    fn of(effects: &[ActiveEffect]) -> Self {
+        let none = Boosts {
+            score_multiplier: 1,
+            slowdown: 1,
+            speedup: 1,
+            ghost: false,
+            magnet: false,
+        };
+        effects
+            .iter()
+            .map(|active| active.effect.power_up())
+            .fold(none, |boosts, power_up| Boosts {
+                score_multiplier: boosts.score_multiplier * power_up.score_multiplier,
+                slowdown: boosts.slowdown * power_up.slowdown,
+                speedup: boosts.speedup * power_up.speedup,
+                ghost: boosts.ghost || power_up.ghost,
+                magnet: boosts.magnet || power_up.magnet,
+            })
+    }
+}
+
+// A power-up waiting on the board to be picked up
+#[derive(Debug, Clone, Copy)]
+struct Pickup {
+    index: usize,
+    effect: Effect,
+    ticks_left: u32,
+}
+
+// One in this many moves spawns a power-up, if there isn't one already
+const POWER_UP_CHANCE: u32 = 30;
+// Moves a power-up stays on the board before it disappears
+const POWER_UP_LIFETIME: u32 = 50;
+
+#[derive(Debug, Clone, Default)]
+pub struct GameOptions {
+    // Leaving the board comes back in on the opposite side instead of dying
+    pub wrap: bool,
+    pub level: Level,
+    // Seeds food placement, None picks a random seed for every game
+    pub seed: Option<u64>,
+    pub grid: Grid,
+    // Spawns power-ups now and then, see `POWER_UPS`
+    pub power_ups: bool,
+    pub mode: GameMode,
+    pub tail: TailRule,
+}
+
+/// Portal pairs that get a color of their own, more pairs reuse them
+pub const PORTAL_COLORS: usize = 4;
+// `coloridx` of power-ups, after the portals, and of entities
+const POWER_UP_COLOR: u32 = 4 + PORTAL_COLORS as u32;
+const ENTITY_COLOR: u32 = POWER_UP_COLOR + 1;
+
+// Slot in `Game::free_slots` of a tile that isn't free
+const NOT_FREE: usize = usize::MAX;
+
+/// Parts a tick of `Game::elapsed` is counted in, so sped up moves can take
+/// less than a tick
+pub const TICK_PARTS: u64 = 64;
+
+#[derive(Clone)]
 pub struct Game {
     board: Vec<Tile>,
     cols: usize,
     rows: usize,
+    // Shared between clones, it never changes during a game
+    topology: Arc<dyn Topology>,
 
-    board_width: f32,
-    board_height: f32,
-
-    board_size: usize,
+    // Which snake is on each tile, so running into a snake doesn't mean
+    // searching through all of them, and how many of its tiles are there.
+    // That's only ever more than one where a ghost crossed its own body.
+    owners: Vec<Option<SnakeId>>,
+    layers: Vec<u16>,
+    // Empty tiles in no particular order and where each one is in `free`,
+    // so food finds a spot without scanning the board
+    free: Vec<usize>,
+    free_slots: Vec<usize>,
 
     // each tile is a square
     tile_size: f32,
 
-    snake: Snake,
+    // Empty slots are ids that are free to spawn a snake with
+    snakes: Vec<Option<Snake>>,
 
-    food_location: usize,
-}
+    // Pairs of portal tiles. Snakes never stop on one: stepping into a
+    // portal puts the head on the tile past its partner, so a body that's
+    // half way through is just two stretches of tiles that aren't adjacent.
+    portals: Vec<(usize, usize)>,
 
-fn move_in_direction(tile: Pos, direction: Direction) -> Pos {
-    use Direction::*;
-
-    // println!("move in direction log {:?}", tile);
-    match direction {
-        Stationary => tile,
-        Right => Pos {
-            x: tile.x + 1,
-            y: tile.y,
-        },
-        Left => Pos {
-            x: tile.x - 1,
-            y: tile.y,
-        },
-        Up => Pos {
-            x: tile.x,
-            y: tile.y + 1,
-        },
-        Down => Pos {
-            x: tile.x,
-            y: tile.y - 1,
-        },
-    }
-}
+    food_location: usize,
+    power_up: Option<Pickup>,
+    // Moving hazards on top of the board, see entity.rs
+    entities: Vec<Entity>,
 
-impl Game {
-    pub fn new(board_width: f32, board_height: f32, tile_size: f32) -> Self {
-        let cols = (board_width / tile_size) as usize;
-        let rows = (board_height / tile_size) as usize;
+    options: GameOptions,
+    // Shared between clones like the topology, made for the board at the start
+    rules: Arc<dyn Rules>,
+    // How many of the rules' food spawns have been used
+    spawned: usize,
+    // Set once the game is over, by death or by the rules
+    ending: Option<Ending>,
 
-        let board_size = rows * cols;
+    // Seed the rng was created from, so the game can be reproduced
+    seed: u64,
+    rng: ChaCha8Rng,
 
-        // Make starting tile the center tile
-        let starting_col = cols / 2;
-        let starting_row = rows / 2;
-        let starting_tile = starting_row * cols + starting_col;
+    // Moves made so far, and the move each change of direction happened on.
+    // Together with the seed and options that's enough to replay the game.
+    moves: u64,
+    inputs: Vec<(u64, Direction)>,
+    // Ticks any snake moved on, the clock entities keep to
+    ticks: u64,
+    // Game time so far in `TICK_PARTS`, a move in slow motion taking
+    // several ticks and a sped up one part of one
+    elapsed: u64,
 
-        // println!("{}, {}", starting_col, starting_row);
-        let snake = Snake {
-            tiles: vec![Pos {
-                x: starting_col,
-                y: starting_row,
-            }],
-            direction: Direction::Stationary,
-        };
+    // What happened since the last `drain_events`
+    events: Events,
+}
 
-        let mut board: Vec<Tile> = std::iter::repeat(Tile::Empty).take(board_size).collect();
-        board[starting_tile] = Tile::SnakeHead;
+impl Game {
+    pub fn with_options(cols: usize, rows: usize, tile_size: f32, options: GameOptions) -> Self {
+        let board_size = rows * cols;
 
-        let food_location = Self::generate_new_food(&board);
-        board[food_location] = Tile::Food;
+        let seed = options.seed.unwrap_or_else(|| rand::thread_rng().gen());
+        let rng = ChaCha8Rng::seed_from_u64(seed);
+        let walls = options.level.walls(cols, rows);
+        let mask = options.level.mask(cols, rows);
+        let portals = options.level.portals(cols, rows);
+        let entities = options.level.entities(cols, rows);
+        let topology = options.grid.topology(cols, rows, options.wrap);
 
-        Self {
-            board_size,
-            board_width,
-            board_height,
-            board,
+        let mut game = Self {
+            board: vec![Tile::Empty; board_size],
 
             tile_size,
 
             rows,
             cols,
+            topology,
+
+            owners: vec![None; board_size],
+            layers: vec![0; board_size],
+            free: (0..board_size).collect(),
+            free_slots: (0..board_size).collect(),
+
+            snakes: vec![],
+            portals: vec![],
+            food_location: 0,
+            power_up: None,
+            entities: vec![],
+
+            options,
+            rules: Arc::new(mode::Classic),
+            spawned: 0,
+            ending: None,
 
-            snake,
-            food_location,
+            seed,
+            rng,
+
+            moves: 0,
+            inputs: vec![],
+            ticks: 0,
+            elapsed: 0,
+            events: Events::default(),
+        };
+        for (index, playable) in mask.into_iter().enumerate() {
+            if !playable {
+                game.set(index, Tile::Void);
+            }
+        }
+        for wall in walls {
+            game.set(game.topology.index(wall), Tile::Wall);
         }
+        let portals: Vec<_> = portals
+            .into_iter()
+            .map(|(a, b)| (game.topology.index(a), game.topology.index(b)))
+            .collect();
+        game.set_portals(&portals);
+        for mut entity in entities {
+            if game.tile(entity.pos) != Tile::Empty || game.entity_at(entity.pos).is_some() {
+                continue;
+            }
+            // Up on a hex board is whichever way up is closer to the right
+            if entity.direction != Direction::Stationary {
+                entity.direction = game.topology.closest(entity.direction, Direction::Right);
+            }
+            game.entities.push(entity);
+        }
+
+        // Start on the center tile, or the free one closest to it on boards
+        // with a hole or wall in the middle
+        let center = Pos {
+            x: cols / 2,
+            y: rows / 2,
+        };
+        let start = (0..board_size)
+            .filter(|i| {
+                game.board[*i] == Tile::Empty && game.entity_at(game.topology.pos(*i)).is_none()
+            })
+            .min_by_key(|i| (game.topology.distance(game.topology.pos(*i), center), *i))
+            .expect("the board has a free tile");
+        let start = game.topology.pos(start);
+        game.rules = game.options.mode.rules(&game, start);
+        game.place_snake(0, &[start]);
+
+        game
     }
 
-    pub fn reset(&mut self) {
-        *self = Game::new(self.board_width, self.board_height, self.tile_size);
+    /// A board with no snakes, for showing a game that's played elsewhere
+    /// (e.g. on a server) with `set_tile`
+    pub fn mirror(cols: usize, rows: usize, tile_size: f32, grid: Grid) -> Self {
+        let options = GameOptions {
+            grid,
+            ..Default::default()
+        };
+        let mut game = Self::with_options(cols, rows, tile_size, options);
+        game.snakes.clear();
+        for index in 0..game.board.len() {
+            game.set(index, Tile::Empty);
+        }
+        game.owners.fill(None);
+        game.layers.fill(0);
+        game
     }
 
-    fn generate_new_food(board: &Vec<Tile>) -> usize {
-        board
-            .iter()
-            .enumerate()
-            .filter_map(|(i, e)| {
-                if matches!(e, Tile::Empty) {
-                    Some(i)
-                } else {
-                    None
-                }
-            })
-            .choose(&mut rand::thread_rng())
-            .unwrap()
+    pub fn reset(&mut self) {
+        *self = Game::with_options(self.cols, self.rows, self.tile_size, self.options.clone());
     }
 
-    pub fn change_direction(&mut self, new_direction: Direction) {
-        // If length is 1, we can move in any direction and also the next check
-        // is gonna have an out of bounds array access, so change direction and return
-        if self.snake.tiles.len() == 1 {
-            self.snake.direction = new_direction;
-            return;
+    /// Position one step from `pos` in `direction`, None if that leaves the
+    /// board. Steps into a portal come out of its partner.
+    pub fn step(&self, pos: Pos, direction: Direction) -> Option<Pos> {
+        let mut next = self.topology.step(pos, direction)?;
+        // Portals next to portals chain, as long as they don't loop
+        for _ in 0..=self.portals.len() {
+            let Some(exit) = self.partner(self.topology.index(next)) else {
+                return Some(next);
+            };
+            next = self.topology.step(self.topology.pos(exit), direction)?;
         }
+        None
+    }
 
-        // Can't move into itself
-        match new_direction {
-            Direction::Right => {
-                if self.snake.tiles[0].x + 1 == self.snake.tiles[1].x {
-                    return;
-                }
-            }
-            Direction::Left => {
-                if self.snake.tiles[0].x - 1 == self.snake.tiles[1].x {
-                    return;
-                }
-            }
-            Direction::Up => {
-                if self.snake.tiles[0].y + 1 == self.snake.tiles[1].y {
-                    return;
-                }
+    // The other end of the portal at `index`, if there is one
+    fn partner(&self, index: usize) -> Option<usize> {
+        if self.board[index] != Tile::Portal {
+            return None;
+        }
+        self.portals.iter().find_map(|&(a, b)| {
+            if index == a {
+                Some(b)
+            } else if index == b {
+                Some(a)
+            } else {
+                None
             }
-            Direction::Down => {
-                if self.snake.tiles[0].y - 1 == self.snake.tiles[1].y {
-                    return;
+        })
+    }
+
+    /// Portal pairs, as tile indices
+    pub fn portals(&self) -> &[(usize, usize)] {
+        &self.portals
+    }
+
+    /// Which portal pair the tile at `pos` is part of, for telling pairs
+    /// apart on screen
+    pub fn portal_pair(&self, pos: Pos) -> Option<usize> {
+        let index = self.topology.index(pos);
+        self.portals
+            .iter()
+            .position(|&(a, b)| a == index || b == index)
+    }
+
+    /// Replaces the portals, for levels and `mirror`s
+    pub fn set_portals(&mut self, pairs: &[(usize, usize)]) {
+        for (a, b) in std::mem::take(&mut self.portals) {
+            for index in [a, b] {
+                if self.board[index] == Tile::Portal {
+                    self.set(index, Tile::Empty);
                 }
             }
-            Direction::Stationary => unreachable!(),
         }
-        self.snake.direction = new_direction;
+        for &(a, b) in pairs {
+            self.set(a, Tile::Portal);
+            self.set(b, Tile::Portal);
+        }
+        self.portals = pairs.to_vec();
     }
 
-    pub fn current_direction(&self) -> Direction {
-        self.snake.direction
+    /// Entities in the order they move in
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
     }
 
-    /// Updates game. Must be called manually after every change
-    /// if it returns false then game over
-    pub fn update(&mut self) -> bool {
-        if matches!(self.snake.direction, Direction::Stationary) {
-            return true;
-        }
+    pub fn entity_at(&self, pos: Pos) -> Option<&Entity> {
+        self.entities.iter().find(|entity| entity.pos == pos)
+    }
 
-        // Collision detection with wall
-        match self.snake.direction {
-            Direction::Right => {
-                if self.snake.tiles[0].x == self.cols - 1 {
-                    return false;
-                }
-            }
-            Direction::Left => {
-                if self.snake.tiles[0].x == 0 {
-                    return false;
-                }
-            }
-            Direction::Up => {
-                if self.snake.tiles[0].y == self.rows - 1 {
-                    return false;
-                }
+    /// Replaces the entities of a `mirror`
+    pub fn set_entities(&mut self, entities: Vec<Entity>) {
+        self.entities = entities;
+    }
+
+    /// Shape of the board
+    pub fn topology(&self) -> &dyn Topology {
+        &*self.topology
+    }
+
+    // Every change to the board goes through here, to keep `free` up to date
+    fn set(&mut self, index: usize, tile: Tile) {
+        let was_free = self.board[index] == Tile::Empty;
+        self.board[index] = tile;
+        match (was_free, tile == Tile::Empty) {
+            (false, true) => {
+                self.free_slots[index] = self.free.len();
+                self.free.push(index);
             }
-            Direction::Down => {
-                if self.snake.tiles[0].y == 0 {
-                    return false;
+            (true, false) => {
+                let slot = self.free_slots[index];
+                self.free.swap_remove(slot);
+                if let Some(&moved) = self.free.get(slot) {
+                    self.free_slots[moved] = slot;
                 }
+                self.free_slots[index] = NOT_FREE;
             }
             _ => {}
         }
+    }
 
-        // Collision with self
-        let head_tile = self.snake.tiles[0];
-        for tile in self.snake.tiles[1..].iter() {
-            if head_tile.x == tile.x && head_tile.y == tile.y {
-                return false;
-            }
+    /// Moves the food to the next of the rules' spawns, or once they're
+    /// used up a random empty tile. None if the board is full, the food
+    /// then waits for a tile to free up.
+    pub fn respawn_food(&mut self) -> Option<Pos> {
+        if self.board[self.food_location] == Tile::Food {
+            self.set(self.food_location, Tile::Empty);
         }
-
-        // Move snake in direction
-        if !matches!(self.snake.direction, Direction::Stationary) {
-            if self.snake.tiles[0].x == 0 && matches!(self.snake.direction, Direction::Left) {
-                // gotta do this early to avoid overflow cuz usize lol
-                return false;
+        self.food_location = match self.rules.food_spawns().get(self.spawned) {
+            Some(&pos) => {
+                self.spawned += 1;
+                // The first empty tile from the spawn on, if the snake is on it
+                let spawn = self.topology.index(pos);
+                let size = self.board.len();
+                (0..size)
+                    .map(|i| (spawn + i) % size)
+                    .find(|&i| self.board[i] == Tile::Empty)?
             }
+            None => *self.free.choose(&mut self.rng)?,
+        };
+        self.set(self.food_location, Tile::Food);
+        let pos = self.topology.pos(self.food_location);
+        self.events.push(Event::FoodSpawned { pos });
+        Some(pos)
+    }
 
-            let new_snake_head = move_in_direction(self.snake.tiles[0], self.snake.direction);
-            if new_snake_head.x > self.cols || new_snake_head.y > self.rows {
-                // Game over we hit the edge of the board
-                return false;
-            }
+    /// Turns a random empty tile into a wall, one that isn't right next to
+    /// a snake's head. False if there's no such tile.
+    pub fn raise_wall(&mut self) -> bool {
+        let heads: Vec<Pos> = self
+            .snakes
+            .iter()
+            .flatten()
+            .filter(|snake| snake.death.is_none())
+            .map(|snake| snake.tiles[0])
+            .collect();
+        let candidates: Vec<usize> = self
+            .free
+            .iter()
+            .copied()
+            .filter(|&index| {
+                let pos = self.topology.pos(index);
+                self.entity_at(pos).is_none()
+                    && heads
+                        .iter()
+                        .all(|&head| self.topology.distance(pos, head) > 2)
+            })
+            .collect();
+        let Some(&index) = candidates.choose(&mut self.rng) else {
+            return false;
+        };
+        self.set(index, Tile::Wall);
+        self.events.push(Event::WallRaised {
+            pos: self.topology.pos(index),
+        });
+        true
+    }
 
-            // Move snake and update board
-            let last_snake = self.snake.tiles.last().unwrap();
-            let head = self.snake.tiles[0];
-            self.board[last_snake.y * self.cols + last_snake.x] = Tile::Empty;
-            if *last_snake != head {
-                self.board[head.y * self.cols + head.x] = Tile::SnakeBody;
-            }
+    // The single player snake
+    fn snake(&self) -> &Snake {
+        self.snakes[0]
+            .as_ref()
+            .expect("single player games always have snake 0")
+    }
+
+    #[cfg(test)]
+    fn snake_mut(&mut self) -> &mut Snake {
+        self.snakes[0]
+            .as_mut()
+            .expect("single player games always have snake 0")
+    }
 
-            let mut i = self.snake.tiles.len() - 1;
-            while i > 0 {
-                self.snake.tiles[i] = self.snake.tiles[i - 1];
+    pub fn change_direction(&mut self, new_direction: Direction) {
+        self.steer(0, new_direction);
+    }
+
+    /// Changes the direction snake `id` moves in, if there is such a snake
+    pub fn steer(&mut self, id: SnakeId, new_direction: Direction) {
+        let Some(snake) = self.snakes.get(id).and_then(Option::as_ref) else {
+            return;
+        };
+        // Up on a hex board goes whichever way up is closer to the heading
+        let new_direction = self.topology.closest(new_direction, snake.direction);
 
-                i -= 1;
+        // If length is 1, we can move in any direction and also the next check
+        // is gonna have an out of bounds array access, so change direction and return
+        if snake.tiles.len() > 1 {
+            // Can't move into itself
+            debug_assert_ne!(new_direction, Direction::Stationary);
+            if self.step(snake.tiles[0], new_direction) == Some(snake.tiles[1]) {
+                return;
+            }
+        }
+        if let Some(snake) = &mut self.snakes[id] {
+            if snake.direction != new_direction {
+                snake.direction = new_direction;
+                self.events.push(Event::Turned {
+                    snake: id,
+                    direction: new_direction,
+                });
             }
-            self.snake.tiles[0] = new_snake_head;
-            self.board[new_snake_head.y * self.cols + new_snake_head.x] = Tile::SnakeHead;
         }
+    }
 
-        // If eat food handle that shit
-        if (self.snake.tiles[0].y * self.cols + self.snake.tiles[0].x) == self.food_location {
-            self.board[self.food_location] = Tile::Empty;
-            self.food_location = Self::generate_new_food(&self.board);
-            self.board[self.food_location] = Tile::Food;
-
-            let last_tile = self.snake.tiles.last().unwrap();
-            // TODO(smolck)
-            let new_tile = if last_tile.x == self.cols {
-                Pos {
-                    x: last_tile.x,
-                    y: last_tile.y + 1,
-                }
-            } else {
-                Pos {
-                    x: last_tile.x + 1,
-                    y: last_tile.y,
-                }
-            };
-            self.snake.tiles.push(new_tile);
-            self.board[new_tile.y * self.cols + new_tile.x] = Tile::SnakeBody;
+    /// Turns relative to the direction the snake will move in on the next
+    /// `update`, so two turns between ticks add up instead of both being
+    /// relative to the last move (a U-turn is still refused).
+    pub fn turn(&mut self, turn: Turn) {
+        let current = self.current_direction();
+        if turn == Turn::Straight && current == Direction::Stationary {
+            return;
         }
+        self.change_direction(self.topology.turned(current, turn));
+    }
 
-        true
+    /// Direction the snake moves in on the next `update`
+    pub fn current_direction(&self) -> Direction {
+        self.snake().direction
     }
 
-    pub fn render_data(&self) -> Vec<shader::Vertex> {
-        let mut col = 0;
-        let mut row = 0;
+    pub fn score(&self) -> u32 {
+        self.snake().score
+    }
 
-        let mut x = 0.;
-        let mut y = 0.;
-        let tile_size = self.tile_size;
+    /// Points on top of the ones for food, for the rules to hand out
+    pub fn add_score(&mut self, points: u32) {
+        if let Some(snake) = &mut self.snakes[0] {
+            snake.score += points;
+        }
+    }
 
-        let mut vertices = Vec::with_capacity(self.board.len() * 6); // * 6 cuz 6 vertices per
-                                                                     // square, TODO could decrease with
-                                                                     // instancing?
-        for tile in &self.board {
-            use shader::Vertex;
-            /*if matches!(tile, Tile::SnakeHead) {
-                println!("render_data log x: {}, y: {}, {:?}", x, y, tile);
-            }*/
+    pub fn snake_len(&self) -> usize {
+        self.snake().tiles.len()
+    }
 
-            let ps = shader::square_for_pos(self.board_width, self.board_height, x, y, tile_size);
-            let coloridx = match tile {
-                // See main.rs COLORS decl for explanation
-                Tile::Empty => 1,
-                Tile::SnakeBody | Tile::SnakeHead => 0,
-                Tile::Food => 2,
-            };
-            vertices.push(Vertex {
-                position: [ps[0], ps[1]],
-                coloridx,
-            });
-            vertices.push(Vertex {
-                position: [ps[2], ps[3]],
-                coloridx,
-            });
-            vertices.push(Vertex {
-                position: [ps[4], ps[5]],
-                coloridx,
-            });
-            vertices.push(Vertex {
-                position: [ps[6], ps[7]],
-                coloridx,
-            });
-            vertices.push(Vertex {
-                position: [ps[8], ps[9]],
-                coloridx,
-            });
-            vertices.push(Vertex {
-                position: [ps[10], ps[11]],
-                coloridx,
-            });
+    /// The snake's power-up effects, oldest first
+    pub fn effects(&self) -> &[ActiveEffect] {
+        &self.snake().effects
+    }
+
+    /// Time between moves when it's normally `tick_ms`, for the snake's
+    /// slow motion and speed effects
+    pub fn move_ms(&self, tick_ms: u64) -> u64 {
+        let boosts = Boosts::of(&self.snake().effects);
+        (tick_ms * boosts.slowdown / boosts.speedup).max(1)
+    }
 
-            x += tile_size;
-            col += 1;
+    /// Game time so far, in `TICK_PARTS` of the time between moves without
+    /// any effects
+    pub fn elapsed(&self) -> u64 {
+        self.elapsed
+    }
 
-            if col == self.cols {
-                col = 0;
-                row += 1;
+    #[cfg(test)]
+    pub fn head(&self) -> Pos {
+        self.snake().tiles[0]
+    }
 
-                x = 0.;
-                y += tile_size;
-            }
-        }
+    #[cfg(test)]
+    pub fn set_effects(&mut self, effects: Vec<ActiveEffect>) {
+        self.snake_mut().effects = effects;
+    }
 
-        vertices
+    /// Every snake on the board, by id
+    pub fn snakes(&self) -> impl Iterator<Item = SnakeInfo> + '_ {
+        self.snakes.iter().enumerate().filter_map(|(id, snake)| {
+            snake.as_ref().map(|snake| SnakeInfo {
+                id,
+                alive: snake.death.is_none(),
+                score: snake.score,
+                length: snake.tiles.len(),
+            })
+        })
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Tiles snake `id` covers, head first
+    pub fn snake_tiles(&self, id: SnakeId) -> Option<impl Iterator<Item = Pos> + '_> {
+        let snake = self.snakes.get(id)?.as_ref()?;
+        Some(snake.tiles.iter().copied())
+    }
 
-    #[allow(dead_code)]
-    fn util_debug_board(game: &Game) {
-        for i in 0..game.rows {
-            for j in 0..game.cols {
-                let text = match game.board[i * game.cols + j] {
-                    Tile::Empty => " ",
-                    Tile::Food => "F",
-                    Tile::SnakeBody => "b",
-                    Tile::SnakeHead => "S",
-                };
-                print!("[{text}]");
-            }
-            println!();
-        }
-        println!();
+    /// The snake covering `pos`, if any
+    pub fn snake_at(&self, pos: Pos) -> Option<SnakeId> {
+        self.owners[self.topology.index(pos)]
     }
 
-    fn util_idx_for_pos(pos: Pos, cols: usize) -> usize {
-        pos.y * cols + pos.x
+    pub fn snake_head(&self, id: SnakeId) -> Option<Pos> {
+        let snake = self.snakes.get(id)?.as_ref()?;
+        Some(snake.tiles[0])
     }
 
-    #[test]
-    fn board_initializes_properly() {
-        let game = super::Game::new(100., 100., 10.);
+    /// What killed snake `id`, None while it's alive
+    pub fn death_cause(&self, id: SnakeId) -> Option<DeathCause> {
+        self.snakes.get(id)?.as_ref()?.death
+    }
 
-        assert_eq!(game.board.len(), (100 / 10) * (100 / 10));
-        assert_eq!(game.snake.tiles.len(), 1);
-        assert_eq!(game.snake.tiles[0].x, 5);
-        assert_eq!(game.snake.tiles[0].y, 5);
+    /// Power-up effects snake `id` has, oldest first
+    pub fn snake_effects(&self, id: SnakeId) -> Option<&[ActiveEffect]> {
+        let snake = self.snakes.get(id)?.as_ref()?;
+        Some(&snake.effects)
     }
 
-    #[test]
-    fn snake_eats_food_properly_when_small_len() {
-        let mut game = super::Game::new(100., 100., 10.);
+    /// Direction snake `id` moves in on the next update
+    pub fn snake_direction(&self, id: SnakeId) -> Option<Direction> {
+        let snake = self.snakes.get(id)?.as_ref()?;
+        Some(snake.direction)
+    }
 
-        // Setup snake about to eat food
-        let food_x = game.food_location % game.cols;
-        let food_y = game.food_location / game.cols;
-        game.snake.tiles[0] = Pos {
-            x: food_x - 1,
-            y: food_y,
+    /// Puts a new stationary snake with `id` on a free tile, preferring ones
+    /// with room to move in every direction. Replaces any snake that had the
+    /// id, false if there's no free tile.
+    pub fn spawn_snake(&mut self, id: SnakeId) -> bool {
+        self.remove_snake(id);
+
+        let free: Vec<usize> = (0..self.board.len())
+            .filter(|i| matches!(self.board[*i], Tile::Empty))
+            .collect();
+        let roomy: Vec<usize> = free
+            .iter()
+            .copied()
+            .filter(|i| {
+                let pos = self.topology.pos(*i);
+                self.topology.directions().iter().all(|&direction| {
+                    self.step(pos, direction)
+                        .is_some_and(|next| matches!(self.tile(next), Tile::Empty | Tile::Food))
+                })
+            })
+            .collect();
+        let Some(&tile) = roomy.choose(&mut self.rng).or(free.choose(&mut self.rng)) else {
+            return false;
         };
-        game.change_direction(Direction::Right);
 
-        let food_location = game.food_location;
-        assert!(game.update());
+        self.place_snake(id, &[self.topology.pos(tile)]);
+        true
+    }
 
-        // New food is moved to a new location
-        assert_ne!(food_location, game.food_location);
-        // Snake is increased by one
-        assert_eq!(game.snake.tiles.len(), 2);
-        // Snake head is where food is
-        assert_eq!(
-            util_idx_for_pos(game.snake.tiles[0], game.cols),
-            food_location
-        );
-        // New end of snake is behind snake head
-        assert_eq!(game.snake.tiles[1].x, food_location % game.cols + 1);
+    /// Puts a stationary snake `id` on the board covering `body`, head
+    /// first, replacing any snake that had the id. The tiles must be empty
+    /// or the food, which then moves. For setting up puzzles, tests and
+    /// benchmarks.
+    pub fn place_snake(&mut self, id: SnakeId, body: &[Pos]) {
+        self.remove_snake(id);
+        if self.snakes.len() <= id {
+            self.snakes.resize_with(id + 1, || None);
+        }
+
+        let mut snake = Snake::new(body[0]);
+        snake.tiles.extend(&body[1..]);
+        for (i, pos) in body.iter().enumerate() {
+            let index = self.topology.index(*pos);
+            debug_assert!(matches!(self.board[index], Tile::Empty | Tile::Food));
+            let tile = if i == 0 {
+                Tile::SnakeHead
+            } else {
+                Tile::SnakeBody
+            };
+            self.set(index, tile);
+            self.owners[index] = Some(id);
+            self.layers[index] = 1;
+        }
+        self.snakes[id] = Some(snake);
+
+        if self.board[self.food_location] != Tile::Food {
+            self.respawn_food();
+        }
+    }
+
+    /// Takes snake `id` off the board
+    pub fn remove_snake(&mut self, id: SnakeId) {
+        let Some(snake) = self.snakes.get_mut(id).and_then(Option::take) else {
+            return;
+        };
+        for &tile in &snake.tiles {
+            let index = self.topology.index(tile);
+            self.set(index, Tile::Empty);
+            self.owners[index] = None;
+            self.layers[index] = 0;
+        }
+    }
+
+    /// Sets a tile of a `mirror`
+    pub fn set_tile(&mut self, index: usize, tile: Tile) {
+        self.set(index, tile);
+    }
+
+    /// Every tile, row by row from the bottom
+    pub fn tiles(&self) -> &[Tile] {
+        &self.board
+    }
+
+    pub fn food(&self) -> Pos {
+        self.topology.pos(self.food_location)
+    }
+
+    /// The power-up waiting to be picked up, if there is one
+    pub fn power_up(&self) -> Option<(Pos, Effect)> {
+        let pickup = self.power_up?;
+        Some((self.topology.pos(pickup.index), pickup.effect))
+    }
+
+    pub fn tile(&self, pos: Pos) -> Tile {
+        self.board[self.topology.index(pos)]
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn wraps(&self) -> bool {
+        self.options.wrap
+    }
+
+    pub fn grid(&self) -> Grid {
+        self.options.grid
+    }
+
+    pub fn power_ups(&self) -> bool {
+        self.options.power_ups
+    }
+
+    pub fn mode(&self) -> GameMode {
+        self.options.mode
+    }
+
+    pub fn tail_rule(&self) -> TailRule {
+        self.options.tail
+    }
+
+    pub fn rules(&self) -> &dyn Rules {
+        &*self.rules
+    }
+
+    /// How the game ended, None while it's still on
+    pub fn ending(&self) -> Option<Ending> {
+        self.ending
+    }
+
+    /// Pixel (width, height) of the board
+    pub fn board_size(&self) -> (f32, f32) {
+        self.topology.size(self.tile_size)
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn tile_size(&self) -> f32 {
+        self.tile_size
+    }
+
+    pub fn level_name(&self) -> &str {
+        &self.options.level.name
+    }
+
+    pub fn level(&self) -> &Level {
+        &self.options.level
+    }
+
+    /// Number of times the snake has moved
+    pub fn moves(&self) -> u64 {
+        self.moves
+    }
+
+    /// (move, direction) for every move the snake changed direction on
+    pub fn inputs(&self) -> &[(u64, Direction)] {
+        &self.inputs
+    }
+
+    /// What happened since the last call, oldest first. Only the latest
+    /// `event::MAX_EVENTS` are kept.
+    pub fn drain_events(&mut self) -> impl Iterator<Item = Event> + '_ {
+        self.events.drain()
+    }
+
+    /// Updates game. Must be called manually after every change
+    /// if it returns false then game over
+    pub fn update(&mut self) -> bool {
+        if self.ending.is_some() {
+            return false;
+        }
+        let direction = self.snake().direction;
+        let moving = !matches!(direction, Direction::Stationary);
+        if moving {
+            if self.inputs.last().map(|(_, d)| *d) != Some(direction) {
+                self.inputs.push((self.moves, direction));
+            }
+            self.moves += 1;
+            // The time up to this move, which the last one's effects set
+            self.elapsed += self.move_ms(TICK_PARTS);
+        }
+
+        self.update_snakes();
+        if self.snake().death.is_some() {
+            self.ending = Some(Ending::Died);
+        } else if moving {
+            let rules = Arc::clone(&self.rules);
+            self.ending = rules.after_move(self);
+        }
+        if self.ending.is_none() && self.board[self.food_location] != Tile::Food {
+            self.ending = Some(Ending::Filled);
+        }
+        if let Some(ending) = self.ending {
+            self.events.push(Event::Ended { ending });
+        }
+        self.ending.is_none()
+    }
+
+    /// Moves every living snake one step, lower ids first, so of two heads
+    /// going for the same tile the lower id gets it. Returns the snakes that
+    /// died.
+    pub fn update_snakes(&mut self) -> Vec<SnakeId> {
+        let mut died = vec![];
+        let mut moved = false;
+        for id in 0..self.snakes.len() {
+            let Some(mut snake) = self.snakes[id].take() else {
+                continue;
+            };
+            if snake.death.is_none() {
+                moved |= snake.direction != Direction::Stationary;
+                if let Err(cause) = self.move_snake(id, &mut snake) {
+                    snake.death = Some(cause);
+                    died.push(id);
+                    self.events.push(Event::Died { snake: id, cause });
+                }
+            }
+            self.snakes[id] = Some(snake);
+        }
+
+        // Entities and power-ups only come and go while the game is on, so
+        // replays that start at the first move see the same ones
+        if moved {
+            self.ticks += 1;
+            self.update_entities(&mut died);
+            if self.options.power_ups {
+                self.tick_power_up();
+            }
+        }
+        // Food that didn't fit on a full board gets another go
+        if self.board[self.food_location] != Tile::Food {
+            self.respawn_food();
+        }
+        died
+    }
+
+    // Moves the entities whose turn it is, in order, adding the snakes they
+    // kill to `died`
+    fn update_entities(&mut self, died: &mut Vec<SnakeId>) {
+        let mut entities = std::mem::take(&mut self.entities);
+        // Lent out while the entities look at the game
+        let mut rng = self.rng.clone();
+        for i in 0..entities.len() {
+            if !self.ticks.is_multiple_of(entities[i].kind.period()) {
+                continue;
+            }
+            let mut entity = entities[i];
+            let hit = entity.update(self, &entities, &mut rng);
+            entities[i] = entity;
+
+            let Some(id) = hit else {
+                continue;
+            };
+            if let Some(snake) = &mut self.snakes[id] {
+                if !self.rules.deadly() {
+                    continue;
+                }
+                if snake.take_hit() {
+                    self.events.push(Event::Shielded { snake: id });
+                } else {
+                    let cause = DeathCause::Hazard;
+                    snake.death = Some(cause);
+                    died.push(id);
+                    self.events.push(Event::Died { snake: id, cause });
+                }
+            }
+        }
+        self.entities = entities;
+        self.rng = rng;
+    }
+
+    // Counts down the power-up on the board, or maybe spawns one
+    fn tick_power_up(&mut self) {
+        if let Some(pickup) = &mut self.power_up {
+            pickup.ticks_left -= 1;
+            if pickup.ticks_left == 0 {
+                let index = pickup.index;
+                self.power_up = None;
+                self.set(index, Tile::Empty);
+                self.events.push(Event::PowerUpVanished {
+                    pos: self.topology.pos(index),
+                });
+            }
+            return;
+        }
+
+        // Leave a tile for the food
+        if self.free.len() < 2 || !self.rng.gen_ratio(1, POWER_UP_CHANCE) {
+            return;
+        }
+        let index = *self.free.choose(&mut self.rng).unwrap();
+        let effect = POWER_UPS
+            .choose_weighted(&mut self.rng, |power_up| power_up.weight)
+            .expect("power-ups have weights")
+            .effect;
+        self.set(index, Tile::PowerUp);
+        self.events.push(Event::PowerUpSpawned {
+            pos: self.topology.pos(index),
+            effect,
+        });
+        self.power_up = Some(Pickup {
+            index,
+            effect,
+            ticks_left: POWER_UP_LIFETIME,
+        });
+    }
+
+    // Moves the food a tile closer to `head`, if there's an empty one
+    fn pull_food(&mut self, head: Pos) {
+        if self.board[self.food_location] != Tile::Food {
+            return;
+        }
+        let food = self.topology.pos(self.food_location);
+        let distance = self.topology.distance(food, head);
+        let closer = self
+            .topology
+            .directions()
+            .iter()
+            .filter_map(|&direction| self.topology.step(food, direction))
+            .find(|&pos| {
+                self.tile(pos) == Tile::Empty && self.topology.distance(pos, head) < distance
+            });
+        if let Some(pos) = closer {
+            self.set(self.food_location, Tile::Empty);
+            self.food_location = self.topology.index(pos);
+            self.set(self.food_location, Tile::Food);
+        }
+    }
+
+    // Counts down the effects of `snake`, snake `id`, queueing the ones
+    // that run out
+    fn tick_effects(&mut self, id: SnakeId, snake: &mut Snake) {
+        snake.tick_effects(|effect| self.events.push(Event::PowerUpEnded { snake: id, effect }));
+    }
+
+    // Moves `snake`, which is taken out of `snakes` meanwhile. Says what it
+    // ran into if it died.
+    fn move_snake(&mut self, id: SnakeId, snake: &mut Snake) -> Result<(), DeathCause> {
+        if matches!(snake.direction, Direction::Stationary) {
+            return Ok(());
+        }
+
+        let boosts = Boosts::of(&snake.effects);
+        let new_snake_head = match self.next_head(id, snake) {
+            Ok(pos) => pos,
+            Err(cause) => {
+                if self.rules.deadly() {
+                    if !snake.take_hit() {
+                        return Err(cause);
+                    }
+                    self.events.push(Event::Shielded { snake: id });
+                }
+                // The shield (or the rules) took the hit, the snake stays
+                // where it is
+                self.tick_effects(id, snake);
+                return Ok(());
+            }
+        };
+        self.tick_effects(id, snake);
+        let new_head_index = self.topology.index(new_snake_head);
+
+        // Nothing's in the way, so the tail goes first (unless the snake is
+        // growing) and then the head
+        let head = snake.tiles[0];
+        if snake.growth > 0 {
+            snake.growth -= 1;
+            self.events.push(Event::Grew {
+                snake: id,
+                length: snake.tiles.len() + 1,
+            });
+        } else {
+            let tail = snake.tiles.pop_back().unwrap();
+            let index = self.topology.index(tail);
+            self.layers[index] -= 1;
+            if self.layers[index] == 0 {
+                self.set(index, Tile::Empty);
+                self.owners[index] = None;
+            }
+        }
+        // Unless the head was the tail that just left
+        if !snake.tiles.is_empty() {
+            self.set(self.topology.index(head), Tile::SnakeBody);
+        }
+
+        snake.tiles.push_front(new_snake_head);
+        self.set(new_head_index, Tile::SnakeHead);
+        self.owners[new_head_index] = Some(id);
+        self.layers[new_head_index] += 1;
+
+        if let Some(pickup) = self.power_up.filter(|p| p.index == new_head_index) {
+            self.power_up = None;
+            snake.effects.push(ActiveEffect {
+                effect: pickup.effect,
+                ticks_left: pickup.effect.power_up().ticks,
+            });
+            self.events.push(Event::PowerUpStarted {
+                snake: id,
+                effect: pickup.effect,
+            });
+        }
+
+        // If eat food handle that shit
+        if new_head_index == self.food_location {
+            self.events.push(Event::FoodEaten {
+                snake: id,
+                pos: new_snake_head,
+                points: boosts.score_multiplier,
+            });
+            self.respawn_food();
+            snake.score += boosts.score_multiplier;
+
+            // The tail stays put on the next move
+            if self
+                .rules
+                .max_length()
+                .is_none_or(|max| snake.tiles.len() + snake.growth < max)
+            {
+                snake.growth += 1;
+            }
+        }
+
+        if boosts.magnet {
+            self.pull_food(new_snake_head);
+        }
+
+        Ok(())
+    }
+
+    // Where the head of `snake` goes next, or what it runs into. Decided
+    // before anything moves: the new head, whether the snake grows, then
+    // whether its tail is out of the way.
+    fn next_head(&self, id: SnakeId, snake: &Snake) -> Result<Pos, DeathCause> {
+        // Collision detection with wall
+        let new_snake_head = match self.step(snake.tiles[0], snake.direction) {
+            Some(pos) => pos,
+            // Game over we hit the edge of the board
+            None => return Err(DeathCause::Edge),
+        };
+        let new_head_index = self.topology.index(new_snake_head);
+        match self.board[new_head_index] {
+            Tile::Wall => return Err(DeathCause::Wall),
+            // Off the board just as much as past the edge
+            Tile::Void => return Err(DeathCause::Edge),
+            // Some other snake is there
+            Tile::SnakeBody | Tile::SnakeHead if self.owners[new_head_index] != Some(id) => {
+                return Err(DeathCause::Snake)
+            }
+            _ => {}
+        }
+        if self.entity_at(new_snake_head).is_some() {
+            return Err(DeathCause::Hazard);
+        }
+
+        // Into its own body, which ghosts go through. The tail's tile is
+        // free too if the tail leaves it on this move, so not if the snake is
+        // growing, or if the rule is strict.
+        if self.owners[new_head_index] == Some(id) && !Boosts::of(&snake.effects).ghost {
+            let tail = snake.tiles.back().map(|&tail| self.topology.index(tail));
+            let tail_leaves = self.options.tail == TailRule::Follow
+                && snake.growth == 0
+                && tail == Some(new_head_index)
+                && self.layers[new_head_index] == 1;
+            if !tail_leaves {
+                return Err(DeathCause::Itself);
+            }
+        }
+        Ok(new_snake_head)
+    }
+
+    pub fn render_data(&self) -> Vec<shader::Vertex> {
+        let tile_size = self.tile_size;
+
+        // 6 vertices per square, 12 per hexagon. TODO could decrease with
+        // instancing?
+        let mut vertices = Vec::with_capacity(self.board.len() * 6);
+        let mut corners = Vec::with_capacity(12);
+        for (index, tile) in self.board.iter().enumerate() {
+            use shader::Vertex;
+
+            let coloridx = match tile {
+                // See main.rs COLORS decl for explanation
+                Tile::Empty => 1,
+                Tile::SnakeBody | Tile::SnakeHead => 0,
+                Tile::Food => 2,
+                Tile::Wall => 3,
+                // Left out, the background shows through
+                Tile::Void => continue,
+                // Pairs in matching colors
+                Tile::Portal => {
+                    let pair = self.portal_pair(self.topology.pos(index)).unwrap_or(0);
+                    4 + (pair % PORTAL_COLORS) as u32
+                }
+                Tile::PowerUp => POWER_UP_COLOR,
+            };
+            corners.clear();
+            self.topology
+                .triangles(self.topology.pos(index), tile_size, &mut corners);
+            vertices.extend(
+                corners
+                    .iter()
+                    .map(|&position| Vertex { position, coloridx }),
+            );
+        }
+
+        // On top of whatever tile they're on
+        for entity in &self.entities {
+            corners.clear();
+            self.topology.triangles(entity.pos, tile_size, &mut corners);
+            vertices.extend(corners.iter().map(|&position| shader::Vertex {
+                position,
+                coloridx: ENTITY_COLOR,
+            }));
+        }
+
+        vertices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::EntityKind;
+
+    #[allow(dead_code)]
+    fn util_debug_board(game: &Game) {
+        for i in 0..game.rows {
+            for j in 0..game.cols {
+                let text = match game.board[i * game.cols + j] {
+                    Tile::Empty => " ",
+                    Tile::Food => "F",
+                    Tile::SnakeBody => "b",
+                    Tile::SnakeHead => "S",
+                    Tile::Wall => "#",
+                    Tile::Void => "-",
+                    Tile::Portal => "O",
+                    Tile::PowerUp => "P",
+                };
+                print!("[{text}]");
+            }
+            println!();
+        }
+        println!();
+    }
+
+    fn util_idx_for_pos(pos: Pos, cols: usize) -> usize {
+        pos.y * cols + pos.x
+    }
+
+    #[test]
+    fn board_initializes_properly() {
+        let game = super::Game::with_options(10, 10, 10., Default::default());
+
+        assert_eq!(game.board.len(), (100 / 10) * (100 / 10));
+        assert_eq!(game.snake().tiles.len(), 1);
+        assert_eq!(game.snake().tiles[0].x, 5);
+        assert_eq!(game.snake().tiles[0].y, 5);
+    }
+
+    #[test]
+    fn snake_eats_food_properly_when_small_len() {
+        // A seed with the food away from the left edge
+        let options = GameOptions {
+            seed: Some(1),
+            ..Default::default()
+        };
+        let mut game = super::Game::with_options(10, 10, 10., options);
+
+        // Setup snake about to eat food
+        let food_x = game.food_location % game.cols;
+        let food_y = game.food_location / game.cols;
+        game.place_snake(
+            0,
+            &[Pos {
+                x: food_x - 1,
+                y: food_y,
+            }],
+        );
+        game.change_direction(Direction::Right);
+
+        let food_location = game.food_location;
+        assert!(game.update());
+
+        // New food is moved to a new location
+        assert_ne!(food_location, game.food_location);
+        // Snake head is where food is, the snake is no longer yet
+        assert_eq!(
+            util_idx_for_pos(game.snake().tiles[0], game.cols),
+            food_location
+        );
+        assert_eq!(game.snake_len(), 1);
+
+        // Snake is increased by one on the next move, its tail staying where
+        // it ate
+        game.change_direction(Direction::Up);
+        assert!(game.update());
+        assert_eq!(game.snake_len(), 2);
+        assert_eq!(
+            util_idx_for_pos(game.snake().tiles[1], game.cols),
+            food_location
+        );
+    }
+
+    #[test]
+    fn wrap_mode_comes_back_on_the_other_side() {
+        let options = GameOptions {
+            wrap: true,
+            ..Default::default()
+        };
+        let mut game = super::Game::with_options(10, 10, 10., options);
+        game.place_snake(0, &[Pos { x: 9, y: 5 }]);
+        game.change_direction(Direction::Right);
+
+        assert!(game.update());
+        assert_eq!(game.snake().tiles[0], Pos { x: 0, y: 5 });
+    }
+
+    #[test]
+    fn hitting_a_wall_is_game_over() {
+        let options = GameOptions {
+            level: Level::builtin()[1].clone(),
+            ..Default::default()
+        };
+        let mut game = super::Game::with_options(10, 10, 10., options);
+        assert!(matches!(game.board[0], Tile::Wall));
+
+        game.change_direction(Direction::Left);
+        for _ in 0..4 {
+            assert!(game.update());
+        }
+        assert!(!game.update());
+    }
+
+    #[test]
+    fn irregular_boards_start_and_eat_inside() {
+        let options = GameOptions {
+            level: Level::by_name("donut").unwrap(),
+            seed: Some(3),
+            ..Default::default()
+        };
+        let mut game = super::Game::with_options(12, 12, 10., options);
+        let mask = game.level().mask(12, 12);
+        // The middle is a hole, the snake starts next to it
+        assert!(!mask[6 * 12 + 6]);
+        assert!(mask[util_idx_for_pos(game.head(), 12)]);
+
+        for _ in 0..100 {
+            assert!(mask[game.food_location]);
+            game.respawn_food();
+        }
+        assert!(!game.render_data().is_empty());
+        assert!(game.render_data().len() < game.tiles().len() * 6);
+
+        // Heading into the hole is like leaving the board
+        game.change_direction(Direction::Up);
+        for _ in 0..12 {
+            if !game.update() {
+                break;
+            }
+        }
+        assert_eq!(game.death_cause(0), Some(DeathCause::Edge));
+    }
+
+    #[test]
+    fn portals_carry_snakes_across_the_board() {
+        let mut game = super::Game::with_options(10, 10, 10., Default::default());
+        let (a, b) = (Pos { x: 2, y: 5 }, Pos { x: 7, y: 2 });
+        game.set_portals(&[(util_idx_for_pos(a, 10), util_idx_for_pos(b, 10))]);
+        game.place_snake(0, &[Pos { x: 4, y: 5 }, Pos { x: 5, y: 5 }]);
+        game.change_direction(Direction::Left);
+
+        // Into the portal at (2, 5), out on the tile left of (7, 2)
+        assert!(game.update());
+        assert!(game.update());
+        assert_eq!(game.head(), Pos { x: 6, y: 2 });
+        assert_eq!(game.snake().tiles[1], Pos { x: 3, y: 5 });
+
+        // The body follows, and the portals are still there behind it
+        assert!(game.update());
+        assert_eq!(game.snake().tiles[1], Pos { x: 6, y: 2 });
+        assert_eq!(game.board[util_idx_for_pos(a, 10)], Tile::Portal);
+        assert_eq!(game.board[util_idx_for_pos(b, 10)], Tile::Portal);
+
+        // Food never lands on one
+        for _ in 0..200 {
+            game.respawn_food();
+            assert_ne!(game.board[game.food_location], Tile::Portal);
+        }
+    }
+
+    #[test]
+    fn portal_levels_place_their_pairs() {
+        let options = GameOptions {
+            level: Level::by_name("portals").unwrap(),
+            ..Default::default()
+        };
+        let game = super::Game::with_options(8, 8, 10., options);
+        assert_eq!(game.portals().len(), 2);
+        let portals = game.tiles().iter().filter(|t| **t == Tile::Portal);
+        assert_eq!(portals.count(), 4);
+        assert_eq!(game.portal_pair(Pos { x: 2, y: 2 }), Some(0));
+        assert_eq!(game.portal_pair(Pos { x: 2, y: 6 }), Some(1));
+    }
+
+    fn util_put_power_up(game: &mut Game, pos: Pos, effect: Effect) {
+        let index = util_idx_for_pos(pos, game.cols);
+        game.set(index, Tile::PowerUp);
+        game.power_up = Some(Pickup {
+            index,
+            effect,
+            ticks_left: POWER_UP_LIFETIME,
+        });
+    }
+
+    fn util_wrapping_game() -> Game {
+        let options = GameOptions {
+            wrap: true,
+            seed: Some(2),
+            ..Default::default()
+        };
+        super::Game::with_options(10, 10, 10., options)
+    }
+
+    #[test]
+    fn power_ups_last_their_ticks() {
+        let mut game = util_wrapping_game();
+        util_put_power_up(&mut game, Pos { x: 6, y: 5 }, Effect::Slow);
+        game.change_direction(Direction::Right);
+
+        assert!(game.update());
+        assert_eq!(game.power_up(), None);
+        let started = Event::PowerUpStarted {
+            snake: 0,
+            effect: Effect::Slow,
+        };
+        assert!(game.drain_events().any(|event| event == started));
+        let ticks = Effect::Slow.power_up().ticks;
+        assert_eq!(
+            game.effects(),
+            [ActiveEffect {
+                effect: Effect::Slow,
+                ticks_left: ticks
+            }]
+        );
+        assert_eq!(game.move_ms(100), 200);
+
+        for _ in 0..ticks - 1 {
+            assert!(game.update());
+        }
+        assert_eq!(game.effects()[0].ticks_left, 1);
+        game.drain_events().for_each(drop);
+        assert!(game.update());
+        assert!(game.effects().is_empty());
+        assert_eq!(game.move_ms(100), 100);
+        let ended = Event::PowerUpEnded {
+            snake: 0,
+            effect: Effect::Slow,
+        };
+        assert!(game.drain_events().any(|event| event == ended));
+    }
+
+    #[test]
+    fn stacked_effects_multiply() {
+        let options = GameOptions {
+            seed: Some(1),
+            ..Default::default()
+        };
+        let mut game = super::Game::with_options(10, 10, 10., options);
+        let food = game.food();
+        game.place_snake(
+            0,
+            &[Pos {
+                x: food.x - 1,
+                y: food.y,
+            }],
+        );
+        let double = ActiveEffect {
+            effect: Effect::DoubleScore,
+            ticks_left: 5,
+        };
+        game.snake_mut().effects = vec![double, double];
+        game.change_direction(Direction::Right);
+
+        assert!(game.update());
+        assert_eq!(game.score(), 4);
+        assert!(game.update());
+        assert_eq!(game.snake_len(), 2);
+    }
+
+    #[test]
+    fn speed_and_slow_motion_cancel_out() {
+        let mut game = util_wrapping_game();
+        let effect = |effect| ActiveEffect {
+            effect,
+            ticks_left: 5,
+        };
+        game.snake_mut().effects = vec![effect(Effect::Speed)];
+        assert_eq!(game.move_ms(100), 50);
+        game.snake_mut().effects.push(effect(Effect::Slow));
+        assert_eq!(game.move_ms(100), 100);
+        game.snake_mut().effects = vec![effect(Effect::Speed), effect(Effect::Speed)];
+        assert_eq!(game.move_ms(3), 1);
+    }
+
+    // A snake of 4 going round a 2x2 square, head first, its tail next to
+    // its head
+    fn util_tail_chaser(tail: TailRule) -> Game {
+        let options = GameOptions {
+            seed: Some(1),
+            tail,
+            ..Default::default()
+        };
+        let mut game = Game::with_options(10, 10, 10., options);
+        let body = [(5, 5), (5, 6), (6, 6), (6, 5)].map(|(x, y)| Pos { x, y });
+        game.place_snake(0, &body);
+        game
+    }
+
+    // Steers the snake into the tile its tail is on
+    fn util_chase_tail(game: &mut Game) {
+        let tiles = &game.snake().tiles;
+        let (head, tail) = (tiles[0], tiles[tiles.len() - 1]);
+        let direction = game
+            .topology()
+            .directions()
+            .iter()
+            .copied()
+            .find(|&d| game.step(head, d) == Some(tail))
+            .unwrap();
+        game.change_direction(direction);
+    }
+
+    #[test]
+    fn snakes_chase_their_own_tail_round_a_square() {
+        let mut game = util_tail_chaser(TailRule::Follow);
+        for _ in 0..20 {
+            util_chase_tail(&mut game);
+            assert!(game.update());
+            assert_eq!(game.snake_len(), 4);
+            let taken = game
+                .tiles()
+                .iter()
+                .filter(|t| matches!(t, Tile::SnakeHead | Tile::SnakeBody))
+                .count();
+            assert_eq!(taken, 4);
+        }
+        assert_eq!(game.moves(), 20);
+    }
+
+    #[test]
+    fn strict_tails_are_in_the_way() {
+        let mut game = util_tail_chaser(TailRule::Strict);
+        let before = game.snake().tiles.clone();
+        util_chase_tail(&mut game);
+        assert!(!game.update());
+        assert_eq!(game.death_cause(0), Some(DeathCause::Itself));
+        // Died where it was, the head never went into the tail
+        assert_eq!(game.snake().tiles, before);
+    }
+
+    #[test]
+    fn growing_tails_stay_in_the_way() {
+        let mut game = util_tail_chaser(TailRule::Follow);
+        // As if it just ate, so the tail doesn't move this time
+        game.snake_mut().growth = 1;
+        game.change_direction(Direction::Right);
+        assert!(!game.update());
+        assert_eq!(game.death_cause(0), Some(DeathCause::Itself));
+    }
+
+    #[test]
+    fn ghosts_go_through_their_own_body() {
+        let mut game = super::Game::with_options(10, 10, 10., Default::default());
+        // Hooked, so heading right goes into the body
+        let body = [(5, 5), (5, 4), (6, 4), (6, 5), (6, 6)].map(|(x, y)| Pos { x, y });
+        game.place_snake(0, &body);
+        game.change_direction(Direction::Right);
+
+        let mut solid = game.clone();
+        assert!(!solid.update());
+        assert_eq!(solid.death_cause(0), Some(DeathCause::Itself));
+
+        game.snake_mut().effects.push(ActiveEffect {
+            effect: Effect::Ghost,
+            ticks_left: 10,
+        });
+        for _ in 0..4 {
+            assert!(game.update());
+            // Where the body crosses itself the tile stays taken until the
+            // last part of the snake on it has left
+            for pos in game.snake().tiles.iter() {
+                assert_ne!(game.tile(*pos), Tile::Empty, "{pos:?}");
+            }
+            let taken = game
+                .tiles()
+                .iter()
+                .filter(|t| matches!(t, Tile::SnakeBody | Tile::SnakeHead))
+                .count();
+            let mut distinct: Vec<_> = game.snake().tiles.iter().map(|p| (p.x, p.y)).collect();
+            distinct.sort();
+            distinct.dedup();
+            assert_eq!(taken, distinct.len());
+        }
+    }
+
+    #[test]
+    fn shields_take_one_hit() {
+        let mut game = super::Game::with_options(10, 10, 10., Default::default());
+        game.place_snake(0, &[Pos { x: 0, y: 5 }]);
+        game.snake_mut().effects.push(ActiveEffect {
+            effect: Effect::Shield,
+            ticks_left: 10,
+        });
+        game.change_direction(Direction::Left);
+
+        // The shield is used up and the snake stays put
+        game.drain_events().for_each(drop);
+        assert!(game.update());
+        assert_eq!(game.head(), Pos { x: 0, y: 5 });
+        assert!(game.effects().is_empty());
+        assert_eq!(
+            game.drain_events().collect::<Vec<_>>(),
+            [Event::Shielded { snake: 0 }]
+        );
+        assert!(!game.update());
+        assert_eq!(game.death_cause(0), Some(DeathCause::Edge));
+        assert_eq!(
+            game.drain_events().collect::<Vec<_>>(),
+            [
+                Event::Died {
+                    snake: 0,
+                    cause: DeathCause::Edge
+                },
+                Event::Ended {
+                    ending: Ending::Died
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn updates_say_what_happened() {
+        let options = GameOptions {
+            seed: Some(1),
+            ..Default::default()
+        };
+        let mut game = super::Game::with_options(10, 10, 10., options);
+        let food = game.food();
+        game.place_snake(
+            0,
+            &[Pos {
+                x: food.x - 1,
+                y: food.y,
+            }],
+        );
+        // Setting up the board spawned food
+        assert!(game
+            .drain_events()
+            .all(|event| matches!(event, Event::FoodSpawned { .. })));
+
+        game.change_direction(Direction::Right);
+        game.change_direction(Direction::Right);
+        assert!(game.update());
+        assert_eq!(
+            game.drain_events().collect::<Vec<_>>(),
+            [
+                Event::Turned {
+                    snake: 0,
+                    direction: Direction::Right
+                },
+                Event::FoodEaten {
+                    snake: 0,
+                    pos: food,
+                    points: 1
+                },
+                Event::FoodSpawned { pos: game.food() },
+            ]
+        );
+        // The tail stays put on the next move
+        assert!(game.update());
+        assert_eq!(
+            game.drain_events().collect::<Vec<_>>(),
+            [Event::Grew {
+                snake: 0,
+                length: 2
+            }]
+        );
+        assert!(game.update());
+        assert_eq!(game.drain_events().count(), 0);
+    }
+
+    #[test]
+    fn magnets_pull_the_food() {
+        let mut game = util_wrapping_game();
+        game.place_snake(0, &[Pos { x: 0, y: 0 }]);
+        game.snake_mut().effects.push(ActiveEffect {
+            effect: Effect::Magnet,
+            ticks_left: 10,
+        });
+        game.set(game.food_location, Tile::Empty);
+        game.food_location = util_idx_for_pos(Pos { x: 5, y: 5 }, 10);
+        game.set(game.food_location, Tile::Food);
+        game.change_direction(Direction::Up);
+
+        // Head goes to (0, 1), 9 tiles from the food, which moves a tile closer
+        assert!(game.update());
+        assert_eq!(game.topology.distance(game.food(), game.head()), 8);
+        assert_eq!(game.tile(game.food()), Tile::Food);
+    }
+
+    #[test]
+    fn filling_the_board_wins() {
+        let options = GameOptions {
+            wrap: true,
+            ..Default::default()
+        };
+        let mut game = super::Game::with_options(3, 1, 10., options);
+        game.place_snake(0, &[Pos { x: 1, y: 0 }, Pos { x: 2, y: 0 }]);
+        assert_eq!(game.food(), Pos { x: 0, y: 0 });
+        game.change_direction(Direction::Left);
+
+        // The food goes where the tail was, and eating it while growing
+        // leaves nowhere for the food to go
+        assert!(game.update());
+        assert_eq!(game.food(), Pos { x: 2, y: 0 });
+        assert!(!game.update());
+        assert_eq!(game.ending(), Some(Ending::Filled));
+        assert!(!game.tiles().contains(&Tile::Food));
+    }
+
+    #[test]
+    fn power_ups_spawn_only_while_moving() {
+        let options = GameOptions {
+            wrap: true,
+            seed: Some(2),
+            power_ups: true,
+            ..Default::default()
+        };
+        let mut game = super::Game::with_options(10, 10, 10., options);
+        for _ in 0..200 {
+            game.update();
+        }
+        assert_eq!(game.power_up(), None);
+
+        // Round and round the bottom row, which power-ups spawn on, come and
+        // go from
+        game.place_snake(0, &[Pos { x: 0, y: 0 }]);
+        game.change_direction(Direction::Right);
+        let mut spawned = 0;
+        let mut on_board = 0;
+        for _ in 0..500 {
+            let had = game.power_up();
+            assert!(game.update());
+            match (had, game.power_up()) {
+                (None, Some((pos, _))) => {
+                    assert_eq!(game.tile(pos), Tile::PowerUp);
+                    spawned += 1;
+                    on_board = 1;
+                }
+                (Some(_), Some(_)) => {
+                    on_board += 1;
+                    assert!(on_board <= POWER_UP_LIFETIME);
+                }
+                (Some((pos, _)), None) => {
+                    assert_ne!(game.tile(pos), Tile::PowerUp);
+                }
+                (None, None) => {}
+            }
+        }
+        assert!(spawned > 1);
+
+        let mut plain = util_wrapping_game();
+        plain.change_direction(Direction::Right);
+        for _ in 0..500 {
+            plain.update();
+            assert_eq!(plain.power_up(), None);
+        }
+    }
+
+    fn util_put_entity(
+        game: &mut Game,
+        kind: EntityKind,
+        x: usize,
+        y: usize,
+        direction: Direction,
+    ) {
+        let mut entities = game.entities().to_vec();
+        entities.push(Entity {
+            kind,
+            pos: Pos { x, y },
+            direction,
+        });
+        game.set_entities(entities);
+    }
+
+    #[test]
+    fn blocks_patrol_back_and_forth() {
+        let mut game = super::Game::with_options(10, 10, 10., Default::default());
+        game.place_snake(0, &[Pos { x: 0, y: 0 }]);
+        util_put_entity(&mut game, EntityKind::Block, 7, 8, Direction::Right);
+        game.change_direction(Direction::Up);
+
+        // A step every other tick, turning around at the edge
+        let mut xs = vec![];
+        for _ in 0..8 {
+            assert!(game.update());
+            xs.push(game.entities()[0].pos.x);
+        }
+        assert_eq!(xs, [7, 8, 8, 9, 9, 9, 9, 8]);
+        assert_eq!(game.entities()[0].direction, Direction::Left);
+    }
+
+    #[test]
+    fn running_into_an_entity_is_game_over() {
+        let mut game = super::Game::with_options(10, 10, 10., Default::default());
+        game.place_snake(0, &[Pos { x: 4, y: 5 }]);
+        util_put_entity(&mut game, EntityKind::Block, 6, 5, Direction::Stationary);
+        game.change_direction(Direction::Right);
+
+        assert!(game.update());
+        assert!(!game.update());
+        assert_eq!(game.death_cause(0), Some(DeathCause::Hazard));
+        assert_eq!(game.head(), Pos { x: 5, y: 5 });
+    }
+
+    #[test]
+    fn hunters_close_in_and_bite() {
+        let mut game = super::Game::with_options(10, 10, 10., Default::default());
+        game.place_snake(0, &[Pos { x: 0, y: 5 }]);
+        util_put_entity(&mut game, EntityKind::Hunter, 3, 7, Direction::Stationary);
+        game.change_direction(Direction::Right);
+
+        // Its first move, on the third tick, takes it next to the snake's path
+        assert!(game.update());
+        assert!(game.update());
+        assert_eq!(game.entities()[0].pos, Pos { x: 3, y: 7 });
+        assert!(game.update());
+        assert_eq!(game.entities()[0].pos, Pos { x: 3, y: 6 });
+
+        // And by the next one the snake has got away
+        for _ in 0..3 {
+            assert!(game.update());
+        }
+        assert_eq!(game.entities()[0].pos, Pos { x: 4, y: 6 });
+
+        // One waiting right above where the head will be bites
+        let mut game = super::Game::with_options(10, 10, 10., Default::default());
+        game.place_snake(0, &[Pos { x: 0, y: 5 }]);
+        util_put_entity(&mut game, EntityKind::Hunter, 3, 6, Direction::Stationary);
+        game.change_direction(Direction::Right);
+        assert!(game.update());
+        assert!(game.update());
+        assert!(!game.update());
+        assert_eq!(game.death_cause(0), Some(DeathCause::Hazard));
+    }
+
+    #[test]
+    fn shields_take_a_hit_from_an_entity() {
+        let mut game = super::Game::with_options(10, 10, 10., Default::default());
+        game.place_snake(0, &[Pos { x: 0, y: 5 }]);
+        game.snake_mut().effects.push(ActiveEffect {
+            effect: Effect::Shield,
+            ticks_left: 10,
+        });
+        util_put_entity(&mut game, EntityKind::Hunter, 3, 6, Direction::Stationary);
+        game.change_direction(Direction::Right);
+
+        for _ in 0..3 {
+            assert!(game.update());
+        }
+        assert!(game.effects().is_empty());
+        assert_eq!(game.entities()[0].pos, Pos { x: 3, y: 6 });
+    }
+
+    #[test]
+    fn balls_bounce_the_same_way_with_the_same_seed() {
+        let options = GameOptions {
+            seed: Some(3),
+            ..Default::default()
+        };
+        let paths: Vec<Vec<Pos>> = (0..2)
+            .map(|_| {
+                let mut game = super::Game::with_options(10, 10, 10., options.clone());
+                game.place_snake(0, &[Pos { x: 0, y: 0 }]);
+                util_put_entity(&mut game, EntityKind::Ball, 7, 5, Direction::Right);
+                game.change_direction(Direction::Up);
+                (0..9)
+                    .map(|_| {
+                        assert!(game.update());
+                        game.entities()[0].pos
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(paths[0], paths[1]);
+
+        // Straight to the edge, then off somewhere else, a step every tick
+        assert_eq!(paths[0][..2], [Pos { x: 8, y: 5 }, Pos { x: 9, y: 5 }]);
+        let topology = Grid::Square.topology(10, 10, false);
+        for pair in paths[0].windows(2) {
+            assert_eq!(topology.distance(pair[0], pair[1]), 1);
+        }
+    }
+
+    #[test]
+    fn zen_snakes_stop_instead_of_dying_and_stop_growing() {
+        let options = GameOptions {
+            mode: GameMode::Zen,
+            ..Default::default()
+        };
+        let mut game = super::Game::with_options(10, 10, 10., options);
+        game.place_snake(0, &[Pos { x: 0, y: 5 }]);
+        game.change_direction(Direction::Left);
+        assert!(game.update());
+        assert_eq!(game.head(), Pos { x: 0, y: 5 });
+        assert_eq!(game.ending(), None);
+
+        // Back and forth along the bottom rows, the head at the left of the
+        // fourth one
+        let mut body: Vec<Pos> = (0..mode::ZEN_MAX_LENGTH)
+            .map(|i| {
+                let (x, y) = (i % 10, i / 10);
+                let x = if y % 2 == 0 { x } else { 9 - x };
+                Pos { x, y }
+            })
+            .collect();
+        body.reverse();
+        game.place_snake(0, &body);
+        game.set(game.food_location, Tile::Empty);
+        game.food_location = util_idx_for_pos(Pos { x: 0, y: 4 }, 10);
+        game.set(game.food_location, Tile::Food);
+        game.change_direction(Direction::Up);
+
+        assert!(game.update());
+        assert!(game.update());
+        assert_eq!(game.score(), 1);
+        assert_eq!(game.snake_len(), mode::ZEN_MAX_LENGTH);
+    }
+
+    #[test]
+    fn same_seed_places_the_same_food() {
+        let options = GameOptions {
+            seed: Some(42),
+            ..Default::default()
+        };
+        let a = super::Game::with_options(10, 10, 10., options.clone());
+        let b = super::Game::with_options(10, 10, 10., options);
+        assert_eq!(a.food_location, b.food_location);
+        assert_eq!(a.seed(), 42);
+    }
+
+    #[test]
+    fn relative_turns_follow_the_heading() {
+        let mut game = super::Game::with_options(10, 10, 10., Default::default());
+        game.place_snake(0, &[Pos { x: 5, y: 5 }, Pos { x: 5, y: 4 }]);
+
+        // Stationary turns as if heading up
+        game.turn(Turn::Straight);
+        assert_eq!(game.current_direction(), Direction::Stationary);
+        game.turn(Turn::Right);
+        assert_eq!(game.current_direction(), Direction::Right);
+        game.turn(Turn::Left);
+        assert_eq!(game.current_direction(), Direction::Up);
+        game.turn(Turn::Left);
+        assert_eq!(game.current_direction(), Direction::Left);
+    }
+
+    #[test]
+    fn hex_snakes_turn_a_sixth_at_a_time() {
+        let options = GameOptions {
+            grid: Grid::Hex,
+            ..Default::default()
+        };
+        let mut game = super::Game::with_options(10, 10, 10., options);
+        game.place_snake(0, &[Pos { x: 5, y: 5 }]);
+
+        game.change_direction(Direction::Right);
+        assert!(game.update());
+        // Up while heading right is up and to the right, odd rows sit further right
+        game.change_direction(Direction::Up);
+        assert_eq!(game.current_direction(), Direction::UpRight);
+        assert!(game.update());
+        assert_eq!(game.head(), Pos { x: 7, y: 6 });
+
+        game.turn(Turn::Left);
+        assert_eq!(game.current_direction(), Direction::UpLeft);
+        assert!(game.update());
+        assert_eq!(game.head(), Pos { x: 6, y: 7 });
+    }
+
+    #[test]
+    fn relative_turns_between_ticks_cant_reverse() {
+        let mut game = super::Game::with_options(10, 10, 10., Default::default());
+        game.place_snake(0, &[Pos { x: 5, y: 5 }, Pos { x: 5, y: 4 }]);
+        game.snake_mut().direction = Direction::Up;
+
+        game.turn(Turn::Left);
+        // Relative to Left now, turning left again would be Down into the body
+        game.turn(Turn::Left);
+        assert_eq!(game.current_direction(), Direction::Left);
     }
 }
@@ -0,0 +1,163 @@
+// Golden images in tests/golden, shared by the GPU and CPU renderer tests.
+// The CPU rasterizer is the reference: `SNAKE_BLESS=1 cargo test` rewrites
+// the images from it, the GPU output only gets compared.
+
+use std::path::{Path, PathBuf};
+
+use crate::app::Overlay;
+use crate::event::Event;
+use crate::game::{DeathCause, Direction, Game, GameOptions};
+use crate::level::Level;
+use crate::particles::Particles;
+use crate::raster::Image;
+use crate::scene::Scene;
+use crate::theme::Theme;
+use crate::topology::Grid;
+
+// Per channel, GPUs are allowed to round a little differently
+const TOLERANCE: u8 = 2;
+
+pub const CASES: [&str; 6] = [
+    "board",
+    "walls_ocean",
+    "hud_overlay",
+    "hex",
+    "portals",
+    "particles",
+];
+
+fn game(level: &str, grid: Grid) -> Game {
+    let options = GameOptions {
+        level: Level::by_name(level).unwrap(),
+        seed: Some(4),
+        grid,
+        ..Default::default()
+    };
+    let mut game = Game::with_options(12, 10, 8., options);
+    game.change_direction(Direction::Right);
+    for _ in 0..3 {
+        game.update();
+    }
+    game
+}
+
+/// Calls `f` with the scene for the golden image `name`
+pub fn with_scene<R>(name: &str, f: impl FnOnce(&Scene) -> R) -> R {
+    let (level, theme, grid) = match name {
+        "board" => ("classic", "classic", Grid::Square),
+        "walls_ocean" => ("pillars", "ocean", Grid::Square),
+        "hud_overlay" => ("box", "classic", Grid::Square),
+        "hex" => ("box", "classic", Grid::Hex),
+        "portals" => ("portals", "ocean", Grid::Square),
+        "particles" => ("classic", "classic", Grid::Square),
+        _ => panic!("no golden image called {name}"),
+    };
+    let game = game(level, grid);
+    let theme = Theme::by_name(theme).unwrap();
+
+    // Shortly after a bite and a death, mid shake
+    let mut particles = Particles::default();
+    if name == "particles" {
+        let events = [
+            Event::FoodEaten {
+                snake: 0,
+                pos: game.food(),
+                points: 1,
+            },
+            Event::Died {
+                snake: 0,
+                cause: DeathCause::Edge,
+            },
+        ];
+        particles.on_tick(&events, &game);
+        particles.advance(0.15);
+    }
+
+    let mut scene = Scene {
+        game: &game,
+        theme: &theme,
+        hud: None,
+        overlay: None,
+        particles: (name == "particles").then_some(&particles),
+    };
+    if name == "hud_overlay" {
+        scene.hud = Some("SCORE 0".into());
+        scene.overlay = Some(Overlay {
+            lines: vec!["PAUSED".into(), "ESC".into()],
+        });
+    }
+    f(&scene)
+}
+
+fn path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{name}.png"))
+}
+
+fn load_png(path: &Path) -> Result<Image, String> {
+    let error = |e: &dyn std::fmt::Display| format!("couldn't load {}: {e}", path.display());
+
+    let file = std::fs::File::open(path).map_err(|e| error(&e))?;
+    let mut decoder = png::Decoder::new(std::io::BufReader::new(file));
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::ALPHA);
+    let mut reader = decoder.read_info().map_err(|e| error(&e))?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).map_err(|e| error(&e))?;
+    if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+        return Err(error(&"only 8 bit RGBA images are supported"));
+    }
+    pixels.truncate(info.buffer_size());
+
+    Ok(Image {
+        width: info.width,
+        height: info.height,
+        pixels,
+    })
+}
+
+/// Rewrites the golden image `name` when SNAKE_BLESS is set
+pub fn bless(name: &str, image: &Image) -> bool {
+    if std::env::var_os("SNAKE_BLESS").is_none() {
+        return false;
+    }
+    let path = path(name);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    image.save_png(&path).unwrap();
+    true
+}
+
+/// Panics if `image` isn't (close to) the golden image `name`, leaving the
+/// actual image next to it to look at
+pub fn compare(name: &str, image: &Image) {
+    let path = path(name);
+    let golden =
+        load_png(&path).unwrap_or_else(|e| panic!("{e}, run with SNAKE_BLESS=1 to create it"));
+    assert_eq!(
+        (image.width, image.height),
+        (golden.width, golden.height),
+        "{name} changed size"
+    );
+
+    for (i, (got, want)) in image
+        .pixels
+        .chunks_exact(4)
+        .zip(golden.pixels.chunks_exact(4))
+        .enumerate()
+    {
+        if got
+            .iter()
+            .zip(want)
+            .any(|(a, b)| a.abs_diff(*b) > TOLERANCE)
+        {
+            let actual = path.with_extension("actual.png");
+            image.save_png(&actual).unwrap();
+            let (x, y) = (i as u32 % image.width, i as u32 / image.width);
+            panic!(
+                "{name} differs at ({x}, {y}): got {got:?}, want {want:?}, see {}",
+                actual.display()
+            );
+        }
+    }
+}
@@ -0,0 +1,244 @@
+// Maps physical keys to game actions. Screens only ever see actions, so
+// everything here can be tested (and rebound) without a window.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use winit::keyboard::KeyCode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Confirm,
+    Back,
+    Pause,
+    Restart,
+    Quit,
+    ToggleTheme,
+    Screenshot,
+}
+
+impl Action {
+    pub const ALL: [Action; 11] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Confirm,
+        Action::Back,
+        Action::Pause,
+        Action::Restart,
+        Action::Quit,
+        Action::ToggleTheme,
+        Action::Screenshot,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::MoveUp => "UP",
+            Action::MoveDown => "DOWN",
+            Action::MoveLeft => "LEFT",
+            Action::MoveRight => "RIGHT",
+            Action::Confirm => "CONFIRM",
+            Action::Back => "BACK",
+            Action::Pause => "PAUSE",
+            Action::Restart => "RESTART",
+            Action::Quit => "QUIT",
+            Action::ToggleTheme => "THEME",
+            Action::Screenshot => "SCREENSHOT",
+        }
+    }
+}
+
+/// How movement actions steer the snake
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlScheme {
+    /// Up/down/left/right move in that direction on screen
+    #[default]
+    Absolute,
+    /// Left/right turn relative to the snake's heading, up/down do nothing
+    Relative,
+}
+
+/// Short name of a key for display, e.g. "W" for `KeyCode::KeyW`
+pub fn key_label(key: KeyCode) -> String {
+    let name = format!("{key:?}");
+    let name = ["Key", "Digit", "Arrow"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(&name);
+    name.to_uppercase()
+}
+
+/// Keys bound to every action, any number of keys per action and the same
+/// key may trigger several actions (e.g. Space both confirms and pauses).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bindings {
+    #[serde(flatten)]
+    keys: BTreeMap<Action, Vec<KeyCode>>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use KeyCode::*;
+
+        let keys = [
+            (Action::MoveUp, vec![ArrowUp, KeyW, KeyK]),
+            (Action::MoveDown, vec![ArrowDown, KeyS, KeyJ]),
+            (Action::MoveLeft, vec![ArrowLeft, KeyA, KeyH]),
+            (Action::MoveRight, vec![ArrowRight, KeyD, KeyL]),
+            (Action::Confirm, vec![Enter, Space]),
+            (Action::Back, vec![Escape]),
+            (Action::Pause, vec![Space, Escape, KeyP]),
+            (Action::Restart, vec![KeyR]),
+            (Action::Quit, vec![KeyQ]),
+            (Action::ToggleTheme, vec![KeyT]),
+            (Action::Screenshot, vec![PrintScreen, F2]),
+        ];
+        Self {
+            keys: keys.into_iter().collect(),
+        }
+    }
+}
+
+impl Bindings {
+    /// Layers a bindings table, e.g. `pause = ["KeyP", "Space"]`, over these
+    /// bindings. Actions missing from it keep their keys.
+    pub fn merge_toml(mut self, text: &str) -> Result<Self, toml::de::Error> {
+        self.merge(toml::from_str(text)?);
+        Ok(self)
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("bindings are always serializable")
+    }
+
+    /// Actions bound in `other` take its keys, the rest keep theirs
+    pub fn merge(&mut self, other: Bindings) {
+        self.keys.extend(other.keys);
+    }
+
+    /// Layers the bindings in `path` over these ones. A missing or broken
+    /// file leaves them as they are (and logs why).
+    pub fn load_over(self, path: &Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(text) => self.clone().merge_toml(&text).unwrap_or_else(|e| {
+                log::error!("invalid key bindings in {}: {e}", path.display());
+                self
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => self,
+            Err(e) => {
+                log::error!("couldn't read {}: {e}", path.display());
+                self
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_toml())
+    }
+
+    /// Actions triggered by `key`, in `Action::ALL` order
+    pub fn actions_for(&self, key: KeyCode) -> Vec<Action> {
+        self.keys
+            .iter()
+            .filter(|(_, keys)| keys.contains(&key))
+            .map(|(action, _)| *action)
+            .collect()
+    }
+
+    pub fn keys_for(&self, action: Action) -> &[KeyCode] {
+        self.keys.get(&action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn bind(&mut self, action: Action, key: KeyCode) {
+        let keys = self.keys.entry(action).or_default();
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+
+    pub fn clear(&mut self, action: Action) {
+        self.keys.insert(action, vec![]);
+    }
+}
+
+/// Default location of the bindings file
+pub fn bindings_path() -> Option<std::path::PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("snake").join("bindings.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_include_arrows_wasd_and_vim_keys() {
+        let bindings = Bindings::default();
+        for key in [KeyCode::ArrowUp, KeyCode::KeyW, KeyCode::KeyK] {
+            assert_eq!(bindings.actions_for(key), vec![Action::MoveUp]);
+        }
+        assert_eq!(
+            bindings.actions_for(KeyCode::Space),
+            vec![Action::Confirm, Action::Pause]
+        );
+        assert!(bindings.actions_for(KeyCode::F12).is_empty());
+    }
+
+    #[test]
+    fn config_overrides_only_listed_actions() {
+        let bindings = Bindings::default()
+            .merge_toml(
+                r#"
+            pause = ["KeyP"]
+            toggle_theme = ["F2", "KeyY"]
+            "#,
+            )
+            .unwrap();
+
+        assert_eq!(bindings.keys_for(Action::Pause), &[KeyCode::KeyP]);
+        assert_eq!(
+            bindings.actions_for(KeyCode::KeyY),
+            vec![Action::ToggleTheme]
+        );
+        assert_eq!(bindings.actions_for(KeyCode::KeyW), vec![Action::MoveUp]);
+        assert!(!bindings
+            .actions_for(KeyCode::Space)
+            .contains(&Action::Pause));
+    }
+
+    #[test]
+    fn invalid_config_is_an_error() {
+        assert!(Bindings::default()
+            .merge_toml("pause = [\"NotAKey\"]")
+            .is_err());
+        assert!(Bindings::default().merge_toml("fly = [\"KeyF\"]").is_err());
+    }
+
+    #[test]
+    fn round_trips_through_toml() {
+        let mut bindings = Bindings::default();
+        bindings.clear(Action::Quit);
+        bindings.bind(Action::Quit, KeyCode::F10);
+        assert_eq!(
+            Bindings::default().merge_toml(&bindings.to_toml()).unwrap(),
+            bindings
+        );
+    }
+
+    #[test]
+    fn key_labels_are_short() {
+        assert_eq!(key_label(KeyCode::KeyW), "W");
+        assert_eq!(key_label(KeyCode::ArrowLeft), "LEFT");
+        assert_eq!(key_label(KeyCode::Space), "SPACE");
+    }
+}
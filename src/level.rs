@@ -0,0 +1,476 @@
+use std::path::Path;
+
+use crate::entity::{Entity, EntityKind};
+use crate::game::{Direction, Pos};
+
+// Level file characters for entities, blocks starting out the way they point
+const ENTITY_CHARS: [(char, EntityKind, Direction); 6] = [
+    ('<', EntityKind::Block, Direction::Left),
+    ('>', EntityKind::Block, Direction::Right),
+    ('^', EntityKind::Block, Direction::Up),
+    ('v', EntityKind::Block, Direction::Down),
+    ('o', EntityKind::Ball, Direction::Stationary),
+    ('H', EntityKind::Hunter, Direction::Stationary),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Layout {
+    Empty,
+    // Walls all around the edge of the board
+    Border,
+    // 2x2 blocks, one in each quarter of the board
+    Pillars,
+    // Boards that aren't rectangles, shaped by cutting cells off
+    Cross,
+    Circle,
+    Donut,
+    // Two portal pairs, crossing the board diagonally
+    Portals,
+    // A patrolling block either side, a ball and a hunter
+    Hazards,
+    // Loaded from a level file, wall and cut off cells as (x, row counted
+    // from the top), portal pairs in the same way by their digit and
+    // entities by their character
+    Custom {
+        width: usize,
+        height: usize,
+        walls: Vec<(usize, usize)>,
+        holes: Vec<(usize, usize)>,
+        portals: Vec<(u32, [(usize, usize); 2])>,
+        entities: Vec<(char, (usize, usize))>,
+    },
+}
+
+/// Wall layout the board starts with. Built-in layouts scale with the
+/// board size, custom ones are anchored to the top left corner.
+///
+/// Level files are plain text, one line per row of the board from the top:
+/// `#` is a wall, `.` or a space is empty and `-` isn't part of the board at
+/// all, for boards that aren't rectangles. A digit is a portal, leading to
+/// the other cell with the same digit. `<`, `>`, `^` and `v` are blocks
+/// patrolling the way they point, `o` a bouncing ball and `H` a hunter.
+/// Lines starting with `;` are comments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Level {
+    pub name: String,
+    layout: Layout,
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Self {
+            name: "CLASSIC".into(),
+            layout: Layout::Empty,
+        }
+    }
+}
+
+impl Level {
+    pub fn builtin() -> Vec<Level> {
+        vec![
+            Level::default(),
+            Level {
+                name: "BOX".into(),
+                layout: Layout::Border,
+            },
+            Level {
+                name: "PILLARS".into(),
+                layout: Layout::Pillars,
+            },
+            Level {
+                name: "CROSS".into(),
+                layout: Layout::Cross,
+            },
+            Level {
+                name: "CIRCLE".into(),
+                layout: Layout::Circle,
+            },
+            Level {
+                name: "DONUT".into(),
+                layout: Layout::Donut,
+            },
+            Level {
+                name: "PORTALS".into(),
+                layout: Layout::Portals,
+            },
+            Level {
+                name: "HAZARDS".into(),
+                layout: Layout::Hazards,
+            },
+        ]
+    }
+
+    /// Built-in level by (case insensitive) name
+    pub fn by_name(name: &str) -> Option<Level> {
+        Self::builtin()
+            .into_iter()
+            .find(|l| l.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Built-in level called `spec`, or else the level file at that path
+    pub fn find(spec: &str) -> Result<Level, String> {
+        match Self::by_name(spec) {
+            Some(level) => Ok(level),
+            None if Path::new(spec).exists() => Self::load(Path::new(spec)),
+            None => Err(format!("no built-in level or level file called '{spec}'")),
+        }
+    }
+
+    pub fn parse(name: &str, text: &str) -> Result<Level, String> {
+        let mut walls = vec![];
+        let mut holes = vec![];
+        let mut portals: [Vec<(usize, usize)>; 10] = Default::default();
+        let mut entities = vec![];
+        let mut width = 0;
+        let mut height = 0;
+
+        for line in text.lines().filter(|l| !l.starts_with(';')) {
+            for (x, c) in line.chars().enumerate() {
+                match c {
+                    '#' => walls.push((x, height)),
+                    '-' => holes.push((x, height)),
+                    '0'..='9' => portals[c as usize - '0' as usize].push((x, height)),
+                    '.' | ' ' => {}
+                    c if ENTITY_CHARS.iter().any(|(entity, ..)| *entity == c) => {
+                        entities.push((c, (x, height)))
+                    }
+                    c => {
+                        return Err(format!(
+                            "unexpected '{c}' on row {}, expected '#', '.', '-', a digit \
+                             or one of '<>^voH'",
+                            height + 1
+                        ))
+                    }
+                }
+            }
+            width = width.max(line.trim_end().chars().count());
+            height += 1;
+        }
+
+        if height == 0 {
+            return Err("level is empty".into());
+        }
+        let portals = portals
+            .into_iter()
+            .enumerate()
+            .filter(|(_, cells)| !cells.is_empty())
+            .map(|(digit, cells)| {
+                let pair = <[_; 2]>::try_from(cells).map_err(|cells: Vec<_>| {
+                    format!(
+                        "portal {digit} is on {} cells, portals come in pairs",
+                        cells.len()
+                    )
+                })?;
+                Ok((digit as u32, pair))
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Level {
+            name: name.to_uppercase(),
+            layout: Layout::Custom {
+                width,
+                height,
+                walls,
+                holes,
+                portals,
+                entities,
+            },
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Level, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("couldn't read level {}: {e}", path.display()))?;
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "CUSTOM".into());
+        Self::parse(&name, &text).map_err(|e| format!("invalid level {}: {e}", path.display()))
+    }
+
+    /// Level file text for a loaded level, None for built-in ones
+    pub fn to_text(&self) -> Option<String> {
+        let Layout::Custom {
+            width,
+            height,
+            ref walls,
+            ref holes,
+            ref portals,
+            ref entities,
+        } = self.layout
+        else {
+            return None;
+        };
+
+        let mut rows = vec![vec!['.'; width]; height];
+        for (x, row) in walls {
+            rows[*row][*x] = '#';
+        }
+        for (x, row) in holes {
+            rows[*row][*x] = '-';
+        }
+        for (digit, pair) in portals {
+            for (x, row) in pair {
+                rows[*row][*x] = char::from_digit(*digit, 10).unwrap();
+            }
+        }
+        for (c, (x, row)) in entities {
+            rows[*row][*x] = *c;
+        }
+        Some(
+            rows.into_iter()
+                .map(|row| row.into_iter().collect::<String>() + "\n")
+                .collect(),
+        )
+    }
+
+    /// (cols, rows) the level was drawn for, None if it fits any board
+    pub fn size(&self) -> Option<(usize, usize)> {
+        match self.layout {
+            Layout::Custom { width, height, .. } => Some((width, height)),
+            _ => None,
+        }
+    }
+
+    /// Wall tiles for a `cols` x `rows` board
+    pub fn walls(&self, cols: usize, rows: usize) -> Vec<Pos> {
+        match self.layout {
+            Layout::Empty => vec![],
+            Layout::Border => {
+                let mut walls = vec![];
+                for x in 0..cols {
+                    walls.push(Pos { x, y: 0 });
+                    walls.push(Pos { x, y: rows - 1 });
+                }
+                for y in 1..rows.saturating_sub(1) {
+                    walls.push(Pos { x: 0, y });
+                    walls.push(Pos { x: cols - 1, y });
+                }
+                walls
+            }
+            Layout::Pillars => {
+                let mut walls = vec![];
+                for (cx, cy) in [(1, 1), (3, 1), (1, 3), (3, 3)] {
+                    let (x, y) = (cols * cx / 4, rows * cy / 4);
+                    for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        if x + dx < cols && y + dy < rows {
+                            walls.push(Pos {
+                                x: x + dx,
+                                y: y + dy,
+                            });
+                        }
+                    }
+                }
+                walls
+            }
+            Layout::Custom { ref walls, .. } => walls
+                .iter()
+                .filter(|(x, row)| *x < cols && *row < rows)
+                .map(|(x, row)| Pos {
+                    x: *x,
+                    y: rows - 1 - row,
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Which cells of a `cols` x `rows` board are part of it, row by row from
+    /// the bottom. The rest are off the board, as good as walls.
+    pub fn mask(&self, cols: usize, rows: usize) -> Vec<bool> {
+        let shape: fn(f32, f32) -> bool = match self.layout {
+            Layout::Cross => |u, v| u.abs() <= 1. / 3. || v.abs() <= 1. / 3.,
+            Layout::Circle => |u, v| u * u + v * v <= 1.,
+            Layout::Donut => |u, v| (0.25..=1.).contains(&(u * u + v * v)),
+            Layout::Custom { ref holes, .. } => {
+                let mut mask = vec![true; cols * rows];
+                for &(x, row) in holes {
+                    if x < cols && row < rows {
+                        mask[(rows - 1 - row) * cols + x] = false;
+                    }
+                }
+                return mask;
+            }
+            _ => |_, _| true,
+        };
+        (0..cols * rows)
+            .map(|i| {
+                // Cell center scaled to -1..1 across the board
+                let u = ((i % cols) as f32 + 0.5) / cols as f32 * 2. - 1.;
+                let v = ((i / cols) as f32 + 0.5) / rows as f32 * 2. - 1.;
+                shape(u, v)
+            })
+            .collect()
+    }
+
+    /// Portal pairs on a `cols` x `rows` board, a snake going into either
+    /// end comes out of the other
+    pub fn portals(&self, cols: usize, rows: usize) -> Vec<(Pos, Pos)> {
+        match self.layout {
+            Layout::Portals => {
+                let at = |cx, cy| Pos {
+                    x: cols * cx / 4,
+                    y: rows * cy / 4,
+                };
+                [(at(1, 1), at(3, 3)), (at(1, 3), at(3, 1))]
+                    .into_iter()
+                    .filter(|(a, b)| a != b)
+                    .collect()
+            }
+            Layout::Custom { ref portals, .. } => portals
+                .iter()
+                .filter(|(_, pair)| pair.iter().all(|(x, row)| *x < cols && *row < rows))
+                .map(|(_, [a, b])| {
+                    let pos = |(x, row): (usize, usize)| Pos {
+                        x,
+                        y: rows - 1 - row,
+                    };
+                    (pos(*a), pos(*b))
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Entities on a `cols` x `rows` board, in the order they move in
+    pub fn entities(&self, cols: usize, rows: usize) -> Vec<Entity> {
+        let entity = |kind, x, y, direction| Entity {
+            kind,
+            pos: Pos { x, y },
+            direction,
+        };
+        match self.layout {
+            Layout::Hazards => vec![
+                entity(EntityKind::Block, 1, rows / 4, Direction::Right),
+                entity(
+                    EntityKind::Block,
+                    cols.saturating_sub(2),
+                    rows * 3 / 4,
+                    Direction::Left,
+                ),
+                entity(
+                    EntityKind::Ball,
+                    cols / 4,
+                    rows * 3 / 4,
+                    Direction::Stationary,
+                ),
+                entity(
+                    EntityKind::Hunter,
+                    cols.saturating_sub(2),
+                    1,
+                    Direction::Stationary,
+                ),
+            ]
+            .into_iter()
+            // Too small a board for them all
+            .filter(|entity| entity.pos.x < cols)
+            .collect(),
+            Layout::Custom { ref entities, .. } => entities
+                .iter()
+                .filter(|(_, (x, row))| *x < cols && *row < rows)
+                .map(|(c, (x, row))| {
+                    let (_, kind, direction) = ENTITY_CHARS
+                        .into_iter()
+                        .find(|(entity, ..)| entity == c)
+                        .expect("parsed from ENTITY_CHARS");
+                    entity(kind, *x, rows - 1 - row, direction)
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_level_files() {
+        let level = Level::parse("test", "; a comment\n###\n#..\n").unwrap();
+        assert_eq!(level.name, "TEST");
+        assert_eq!(level.size(), Some((3, 2)));
+
+        // Top row of the file is the top row of the board
+        let walls = level.walls(3, 2);
+        assert!(walls.contains(&Pos { x: 0, y: 1 }));
+        assert!(walls.contains(&Pos { x: 2, y: 1 }));
+        assert!(walls.contains(&Pos { x: 0, y: 0 }));
+        assert_eq!(walls.len(), 4);
+    }
+
+    #[test]
+    fn holes_cut_cells_off_the_board() {
+        let level = Level::parse("test", "-.-\n...\n-#-\n").unwrap();
+        assert_eq!(
+            level.mask(3, 3),
+            [false, true, false, true, true, true, false, true, false]
+        );
+        assert_eq!(level.walls(3, 3), [Pos { x: 1, y: 0 }]);
+
+        // Built-in shapes cut off the corners, the donut its middle too
+        for name in ["cross", "circle", "donut"] {
+            let mask = Level::by_name(name).unwrap().mask(9, 9);
+            assert!(!mask[0] && !mask[80], "{name}");
+            assert_eq!(mask[40], name != "donut");
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_characters() {
+        assert!(Level::parse("bad", "#x#").is_err());
+        assert!(Level::parse("empty", "").is_err());
+    }
+
+    #[test]
+    fn digits_pair_up_into_portals() {
+        let level = Level::parse("test", "1..\n..2\n2.1\n").unwrap();
+        assert_eq!(
+            level.portals(3, 3),
+            [
+                (Pos { x: 0, y: 2 }, Pos { x: 2, y: 0 }),
+                (Pos { x: 2, y: 1 }, Pos { x: 0, y: 0 })
+            ]
+        );
+
+        let error = Level::parse("test", "1.1\n..1\n").unwrap_err();
+        assert_eq!(error, "portal 1 is on 3 cells, portals come in pairs");
+        assert!(Level::parse("test", "..0\n").is_err());
+
+        let level = Level::by_name("portals").unwrap();
+        assert_eq!(level.portals(8, 8).len(), 2);
+        assert!(level.walls(8, 8).is_empty());
+    }
+
+    #[test]
+    fn arrows_balls_and_hunters_are_entities() {
+        let level = Level::parse("test", ">.o\n#.v\nH..\n").unwrap();
+        let entity = |kind, x, y, direction| Entity {
+            kind,
+            pos: Pos { x, y },
+            direction,
+        };
+        assert_eq!(
+            level.entities(3, 3),
+            [
+                entity(EntityKind::Block, 0, 2, Direction::Right),
+                entity(EntityKind::Ball, 2, 2, Direction::Stationary),
+                entity(EntityKind::Block, 2, 1, Direction::Down),
+                entity(EntityKind::Hunter, 0, 0, Direction::Stationary),
+            ]
+        );
+        assert_eq!(level.walls(3, 3), [Pos { x: 0, y: 1 }]);
+
+        let level = Level::by_name("hazards").unwrap();
+        assert_eq!(level.entities(20, 20).len(), 4);
+    }
+
+    #[test]
+    fn loaded_levels_round_trip_through_text() {
+        let level = Level::parse("test", "###\n#-3\n3<H\n").unwrap();
+        let text = level.to_text().unwrap();
+        assert_eq!(text, "###\n#-3\n3<H\n");
+        assert_eq!(Level::parse("test", &text).unwrap(), level);
+        assert_eq!(Level::default().to_text(), None);
+    }
+}
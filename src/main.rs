@@ -1,123 +1,73 @@
+mod ai;
+mod app;
+mod audio;
+mod bot;
+mod cli;
+mod client;
+mod config;
+mod daily;
+mod entity;
+mod event;
+mod export;
+mod font;
 mod game;
+#[cfg(test)]
+mod golden;
+mod input;
+mod level;
+mod mode;
+mod offscreen;
+mod particles;
+mod protocol;
+mod raster;
+mod renderer;
+mod replay;
+mod scene;
+mod server;
+mod settings;
 mod shader;
+mod stream;
+mod text;
+mod theme;
+mod topology;
+mod tournament;
+mod transport;
+mod tui;
+
+use std::time::{Duration, Instant};
+
+use clap::{CommandFactory, Parser};
 
 // TODO(smolck): checkkered pattern background option
 use winit::{
     event::*,
     event_loop::{ControlFlow, EventLoop},
-    keyboard::{KeyCode, PhysicalKey},
-    window::Window,
+    keyboard::PhysicalKey,
+    window::{Window, WindowBuilder},
 };
 
-use wgpu::util::DeviceExt;
-
-struct Uniforms {
-    window_resolution: [f32; 2],
-    snake_color: [f32; 3],
-    bg_color: [f32; 3],
-    food_color: [f32; 3],
-
-    colors_buffer: wgpu::Buffer,
-    resolution_buffer: wgpu::Buffer,
-    bind_group_layout: wgpu::BindGroupLayout,
-    bind_group: wgpu::BindGroup,
-}
-
-impl Uniforms {
-    pub fn new(
-        device: &wgpu::Device,
-        win_width: f32,
-        win_height: f32,
-        snake_color: [f32; 3],
-        bg_color: [f32; 3],
-        food_color: [f32; 3],
-    ) -> Self {
-        let resolution_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("resolution uniforms buffer"),
-            contents: bytemuck::cast_slice(&[win_width, win_height]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let colors_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("uniforms buffer"),
-            contents: bytemuck::cast_slice(&[
-                snake_color[0],
-                snake_color[1],
-                snake_color[2],
-                0.,
-                bg_color[0],
-                bg_color[1],
-                bg_color[2],
-                0.,
-                food_color[0],
-                food_color[1],
-                food_color[2],
-                0.,
-            ]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("uniforms_bind_group_layout"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                },
-            ],
-        });
-
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("uniforms bind group"),
-            layout: &bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: colors_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: resolution_buffer.as_entire_binding(),
-                },
-            ],
-        });
-
-        Self {
-            window_resolution: [win_width, win_height],
-            snake_color: [1., 1., 1.],
-            bg_color: [0., 0., 0.],
-            food_color: [1., 0., 0.],
-
-            resolution_buffer,
-            colors_buffer,
-            bind_group,
-            bind_group_layout,
-        }
+// Longest wait between frames while particles are moving
+const FRAME: Duration = Duration::from_millis(16);
+
+fn present_mode(mode: settings::PresentMode, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+    let wanted = match mode {
+        settings::PresentMode::Auto => return wgpu::PresentMode::AutoVsync,
+        settings::PresentMode::Fifo => return wgpu::PresentMode::Fifo,
+        settings::PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+        settings::PresentMode::Immediate => wgpu::PresentMode::Immediate,
+    };
+    if supported.contains(&wanted) {
+        wanted
+    } else {
+        log::warn!("present mode {wanted:?} isn't supported here, using Fifo");
+        wgpu::PresentMode::Fifo
     }
 }
 
-struct State {
-    game_state: game::Game,
+struct State<V> {
+    view: V,
 
     surface: wgpu::Surface,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
 
@@ -126,16 +76,16 @@ struct State {
     // unsafe references to the window's resources.
     window: Window,
 
-    render_pipeline: wgpu::RenderPipeline,
-
-    vertex_buffer: wgpu::Buffer,
-    num_vertices: u32,
-
-    uniforms: Uniforms,
+    renderer: renderer::Renderer,
+    // Simulated here, between frames, rather than in the game
+    particles: particles::Particles,
+    // When the game ticks next, and when the last frame was drawn
+    next_tick: Instant,
+    last_frame: Instant,
 }
 
-impl State {
-    pub async fn new(window: Window) -> Self {
+impl<V: scene::View> State<V> {
+    pub async fn new(window: Window, view: V, present: settings::PresentMode) -> Self {
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
             backends: wgpu::Backends::DX12
                 | wgpu::Backends::DX11
@@ -173,108 +123,42 @@ impl State {
         let surface_format = surface_caps
             .formats
             .iter()
-            .filter(|f| f.is_srgb())
-            .next()
+            .find(|f| f.is_srgb())
             .unwrap_or(&surface_caps.formats[0]);
 
         let win_size = window.inner_size();
-        println!("win size: {:?}", win_size);
+        log::info!("win size: {:?}", win_size);
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: *surface_format,
             width: win_size.width,
             height: win_size.height,
-            present_mode: surface_caps.present_modes[0], // TODO(smolck)
+            present_mode: present_mode(present, &surface_caps.present_modes),
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
         };
 
         surface.configure(&device, &config);
 
-        let snake_color = [1., 1., 1.];
-        let bg_color = [0., 0., 0.];
-        let food_color = [1., 0., 0.];
-        let uniforms = Uniforms::new(
-            &device,
-            win_size.width as f32,
-            win_size.height as f32,
-            snake_color,
-            bg_color,
-            food_color,
+        let renderer = renderer::Renderer::new(
+            device,
+            queue,
+            config.format,
+            win_size.width,
+            win_size.height,
         );
 
-        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("vertex buffer"),
-            mapped_at_creation: false,
-            size: 100_000_000, // TODO(smolck): yeah probably no lol
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
-
-        let render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&uniforms.bind_group_layout],
-                push_constant_ranges: &[],
-            });
-
-        // TODO(smolck)
-        let num_vertices = 6;
-
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("render pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[shader::Vertex::desc()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,                         // no multisampling
-                mask: !0,                         // no multisampling also tf does the bang do
-                alpha_to_coverage_enabled: false, // apparently antialiasing something something
-            },
-            multiview: None, // can render to array textures whatever that means but NO
-                             // also man is wgpu explicit lol
-        });
-
-        let game = game::Game::new(win_size.width as f32, win_size.height as f32, 20.0);
-
         Self {
             surface,
             config,
-            device,
-            queue,
             size: win_size,
             window,
 
-            game_state: game,
-
-            render_pipeline,
-            vertex_buffer,
-            num_vertices,
-
-            uniforms,
+            view,
+            renderer,
+            particles: particles::Particles::default(),
+            next_tick: Instant::now(),
+            last_frame: Instant::now(),
         }
     }
 
@@ -284,7 +168,8 @@ impl State {
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
+            self.surface.configure(self.renderer.device(), &self.config);
+            self.renderer.resize(new_size.width, new_size.height);
         }
     }
 
@@ -294,58 +179,42 @@ impl State {
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
-        let render_data = self.game_state.render_data();
-        self.num_vertices = render_data.len() as u32;
-        self.queue
-            .write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&render_data));
-
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
-
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0,
-                            g: 0.0,
-                            b: 0.0,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
-
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_bind_group(0, &self.uniforms.bind_group, &[]);
-            render_pass.draw(0..self.num_vertices, 0..1);
-        }
-
-        self.queue.submit(std::iter::once(encoder.finish()));
+        let mut scene = self.view.scene();
+        scene.particles = Some(&self.particles);
+        self.renderer.draw(&view, &scene);
         output.present();
 
+        if self.view.take_screenshot_request() {
+            let mut scene = self.view.scene();
+            scene.particles = Some(&self.particles);
+            offscreen::save_screenshot(self.renderer.capture(&scene));
+        }
+
         Ok(())
     }
 }
 
-async fn run() {
-    env_logger::init();
-
+// Shows `view` in a window, `poll` gets a go at it before every tick
+async fn run<V: scene::View + 'static>(
+    settings: &settings::Settings,
+    view: V,
+    mut poll: impl FnMut(&mut V) + 'static,
+) {
     let event_loop = EventLoop::new().unwrap();
-    let window = Window::new(&event_loop).unwrap();
-
-    let mut state = State::new(window).await;
+    let (width, height) = view.scene().size();
+    let window = WindowBuilder::new()
+        .with_title("snake")
+        .with_fullscreen(
+            settings
+                .fullscreen
+                .then_some(winit::window::Fullscreen::Borderless(None)),
+        )
+        .with_inner_size(winit::dpi::PhysicalSize::new(width, height))
+        .build(&event_loop)
+        .unwrap();
+
+    let mut state = State::new(window, view, settings.present_mode).await;
+    let mut audio = audio::Audio::new(settings.mute);
     event_loop.set_control_flow(ControlFlow::Poll);
 
     // event_loop.run_app(
@@ -359,30 +228,35 @@ async fn run() {
                 WindowEvent::CloseRequested => elwt.exit(),
                 WindowEvent::Resized(new_size) => state.resize(*new_size),
                 WindowEvent::KeyboardInput {
-                    event: KeyEvent { physical_key, .. },
+                    event:
+                        KeyEvent {
+                            physical_key: PhysicalKey::Code(key_code),
+                            state: ElementState::Pressed,
+                            ..
+                        },
                     ..
                 } => {
-                    if let PhysicalKey::Code(key_code) = physical_key {
-                        state.game_state.change_direction(match key_code {
-                            KeyCode::ArrowUp => game::Direction::Up,
-                            KeyCode::ArrowDown => game::Direction::Down,
-                            KeyCode::ArrowLeft => game::Direction::Left,
-                            KeyCode::ArrowRight => game::Direction::Right,
-                            // TODO(smolck)
-                            _ => state.game_state.current_direction(),
-                        });
+                    state.view.on_key(*key_code);
+                    if state.view.should_quit() {
+                        elwt.exit();
                     }
                     state.window.request_redraw();
                 }
                 WindowEvent::RedrawRequested => {
-                    // state.game_state.change_direction(game::Direction::Left);
-
-                    if !state.game_state.update() {
-                        // TODO(smolck): Display a lose message
-                        state.game_state.reset();
+                    let now = Instant::now();
+                    if now >= state.next_tick {
+                        poll(&mut state.view);
+                        state.view.tick();
+                        let events = state.view.take_events();
+                        audio.play_events(&events);
+                        state.particles.on_tick(&events, state.view.scene().game);
+                        state.next_tick = now + Duration::from_millis(state.view.tick_ms());
                     }
+                    state
+                        .particles
+                        .advance((now - state.last_frame).as_secs_f32());
+                    state.last_frame = now;
 
-                    std::thread::sleep(std::time::Duration::from_millis(100));
                     match state.render() {
                         Ok(_) => {}
                         Err(wgpu::SurfaceError::Lost) => state.resize(state.size),
@@ -390,6 +264,13 @@ async fn run() {
                         Err(e) => eprintln!("{:?}", e),
                     }
 
+                    // Nothing to draw until the next tick unless particles are moving
+                    let wake = if state.particles.is_idle() {
+                        state.next_tick
+                    } else {
+                        state.next_tick.min(now + FRAME)
+                    };
+                    std::thread::sleep(wake.saturating_duration_since(Instant::now()));
                     state.window.request_redraw();
                 }
                 _ => {}
@@ -399,6 +280,284 @@ async fn run() {
         .expect("failure?");
 }
 
+// No frontend, the built-in bot plays and the result goes to stdout. With
+// `--stream` it plays in real time for viewers.
+fn run_headless(settings: &settings::Settings) {
+    let mut game = settings.new_game();
+    let mut broadcaster = settings.stream.as_ref().map(|addr| {
+        let bind = stream::Broadcaster::bind(
+            addr,
+            settings.cols,
+            settings.rows,
+            settings.grid,
+            settings.tick_ms,
+        );
+        let mut broadcaster = bind.unwrap_or_else(|e| {
+            eprintln!("couldn't stream on {addr}: {e}");
+            std::process::exit(1);
+        });
+        broadcaster.set_name(0, "bot");
+        broadcaster.set_portals(game.portals());
+        if let Ok(addr) = broadcaster.local_addr() {
+            log::info!("streaming on {addr}");
+        }
+        broadcaster
+    });
+    let broadcast = |broadcaster: &mut Option<stream::Broadcaster>, game: &game::Game| {
+        if let Some(broadcaster) = broadcaster {
+            if let Err(e) = broadcaster.send(game) {
+                log::warn!("streaming failed: {e}");
+            }
+            std::thread::sleep(Duration::from_millis(settings.tick_ms));
+        }
+    };
+
+    let mut ticks = 0;
+    broadcast(&mut broadcaster, &game);
+    while ticks < settings.max_ticks {
+        game.change_direction(ai::greedy(&game));
+        ticks += 1;
+        let alive = game.update();
+        broadcast(&mut broadcaster, &game);
+        if !alive {
+            break;
+        }
+    }
+
+    println!(
+        "score {} length {} ticks {} seed {}",
+        game.score(),
+        game.snake_len(),
+        ticks,
+        game.seed()
+    );
+
+    let mut replay = replay::Replay::record(&game, settings.tick_ms);
+    if let Some(date) = settings.daily {
+        replay.daily = Some(date.to_string());
+        print!("{}", daily::Daily::new(date).share(&game));
+    }
+    if let Some(path) = &settings.record {
+        if let Err(e) = replay.save(path) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(path) = &settings.snapshot {
+        let scene = scene::Scene {
+            game: &game,
+            theme: &settings.theme,
+            hud: None,
+            overlay: None,
+            particles: None,
+        };
+        let mut image = raster::render(&scene);
+        if let Some(size) = settings.snapshot_size {
+            image = raster::thumbnail(&image, size);
+        }
+        if let Err(e) = image.save_png(path) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_command(
+    command: &cli::Command,
+    settings: &settings::Settings,
+    bindings: &input::Bindings,
+) -> Result<(), String> {
+    match command {
+        cli::Command::Export(args) => export::run(args, &settings.theme),
+        cli::Command::Server(args) => {
+            let options = server::ServerOptions {
+                cols: settings.cols,
+                rows: settings.rows,
+                tick_ms: settings.tick_ms,
+                game: settings.game_options(),
+                max_players: args.max_players as usize,
+                input_delay: args.input_delay,
+            };
+            server::Server::bind(&args.bind, options)
+                .and_then(|mut server| server.run())
+                .map_err(|e| format!("server on {} failed: {e}", args.bind))
+        }
+        cli::Command::Connect(args) => {
+            let role = if args.spectate {
+                protocol::Role::Spectator
+            } else {
+                protocol::Role::Player
+            };
+            run_session(&args.addr, role, &args.name, settings, bindings)
+        }
+        cli::Command::Watch(args) => run_session(
+            &args.addr,
+            protocol::Role::Spectator,
+            &args.name,
+            settings,
+            bindings,
+        ),
+        cli::Command::Bots(args) => run_bots(args, settings),
+        cli::Command::Tournament(args) => run_tournament(args, settings),
+        cli::Command::Sounds(args) => audio::run(args),
+        cli::Command::Verify(args) => {
+            let replay = replay::Replay::load(&args.replay)?;
+            print!("{}", daily::Daily::verify(&replay)?);
+            Ok(())
+        }
+    }
+}
+
+// Starts the bots, plays them against each other and prints how each did
+fn run_bots(args: &cli::BotsArgs, settings: &settings::Settings) -> Result<(), String> {
+    let seed = settings.seed.unwrap_or(0);
+    let mut bots = args
+        .bots
+        .iter()
+        .map(|spec| bot::start(spec, seed))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut game = settings.new_game();
+    let mut broadcaster = match &args.stream {
+        Some(addr) => {
+            let mut broadcaster = stream::Broadcaster::bind(
+                addr,
+                settings.cols,
+                settings.rows,
+                settings.grid,
+                settings.tick_ms,
+            )
+            .map_err(|e| format!("couldn't stream on {addr}: {e}"))?;
+            for (id, bot) in bots.iter().enumerate() {
+                broadcaster.set_name(id, bot.name());
+            }
+            broadcaster.set_portals(game.portals());
+            Some(broadcaster)
+        }
+        None => None,
+    };
+
+    let results = bot::play(
+        &mut game,
+        &mut bots,
+        Duration::from_millis(args.move_timeout),
+        settings.max_ticks,
+        |game| {
+            if let Some(broadcaster) = &mut broadcaster {
+                if let Err(e) = broadcaster.send(game) {
+                    log::warn!("streaming failed: {e}");
+                }
+                std::thread::sleep(Duration::from_millis(settings.tick_ms));
+            }
+        },
+    );
+
+    println!("seed {}", game.seed());
+    for result in results {
+        println!("{result}");
+    }
+    Ok(())
+}
+
+// Plays the tournament, prints the standings and saves them where asked
+fn run_tournament(args: &cli::TournamentArgs, settings: &settings::Settings) -> Result<(), String> {
+    let first_seed = settings.seed.unwrap_or(0);
+    let jobs = args.jobs.map_or_else(
+        || std::thread::available_parallelism().map_or(1, |n| n.get()),
+        |jobs| jobs as usize,
+    );
+    let options = tournament::TournamentOptions {
+        format: args.format,
+        rounds: args.rounds.map(|rounds| rounds as usize),
+        seeds: (first_seed..first_seed + args.games).collect(),
+        jobs,
+        move_timeout: Duration::from_millis(args.move_timeout),
+        max_ticks: args.max_ticks.unwrap_or(settings.max_ticks),
+        cols: settings.cols,
+        rows: settings.rows,
+        game: settings.game_options(),
+    };
+    let report = tournament::run(&args.entrants, &options)?;
+    println!("{}", report.table());
+
+    let save = |path: &std::path::Path, text: String| {
+        std::fs::write(path, text)
+            .map_err(|e| format!("couldn't save results to {}: {e}", path.display()))
+    };
+    if let Some(path) = &args.csv {
+        save(path, report.csv())?;
+    }
+    if let Some(path) = &args.json {
+        save(path, report.json())?;
+    }
+    Ok(())
+}
+
+// Joins a server or stream and shows it on the configured frontend
+fn run_session(
+    addr: &str,
+    role: protocol::Role,
+    name: &str,
+    settings: &settings::Settings,
+    bindings: &input::Bindings,
+) -> Result<(), String> {
+    let client = client::Client::connect(addr, role, name, settings.tile_size)
+        .map_err(|e| format!("couldn't join {addr}: {e}"))?;
+    let mut session = client::Session::new(client, settings.theme.clone(), bindings.clone());
+    match settings.frontend {
+        settings::Frontend::Window => {
+            pollster::block_on(run(settings, session, |_| {}));
+            Ok(())
+        }
+        settings::Frontend::Terminal => tui::run_session(&mut session).map_err(|e| e.to_string()),
+    }
+}
+
 fn main() {
-    pollster::block_on(run());
+    env_logger::init();
+
+    let cli = cli::Cli::parse();
+    let bindings_path = input::bindings_path();
+    let (settings, bindings) = match config::load(&cli, bindings_path.as_deref()) {
+        Ok(loaded) => loaded,
+        Err(e) => cli::Cli::command()
+            .error(clap::error::ErrorKind::ValueValidation, e)
+            .exit(),
+    };
+
+    if let Some(command) = &cli.command {
+        if let Err(e) = run_command(command, &settings, &bindings) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if settings.headless {
+        run_headless(&settings);
+        return;
+    }
+
+    let mut watcher = config::Watcher::new(&cli, &settings, &bindings, bindings_path.clone());
+    match settings.frontend {
+        settings::Frontend::Window => {
+            let app = app::App::new(&settings, bindings, bindings_path);
+            let poll = move |app: &mut app::App| {
+                if let Some(watcher) = &mut watcher {
+                    watcher.poll(app);
+                }
+                if let Some(share) = app.take_share() {
+                    print!("{share}");
+                }
+            };
+            pollster::block_on(run(&settings, app, poll))
+        }
+        settings::Frontend::Terminal => {
+            if let Err(e) = tui::run(&settings, bindings, bindings_path, watcher) {
+                eprintln!("terminal error: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
 }
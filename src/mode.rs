@@ -0,0 +1,449 @@
+// Rulesets. Every game is played by the rules of its `GameMode`: what ends it,
+// what a collision does, how far the snake grows and where food turns up.
+// The board itself is the same in every mode.
+//
+// Limits are counted in moves and ticks rather than time, so modes stay
+// deterministic and replays play out the same. A tick is the time between
+// moves without slow motion or speed, so at the default 100 ms time attack's
+// 600 ticks are a minute.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::game::{Game, Pos, Tile, TICK_PARTS};
+
+/// Ticks of game time a time attack lasts
+pub const TIME_ATTACK_TICKS: u64 = 600;
+/// Moves between the walls that go up in survival
+pub const SURVIVAL_WALL_EVERY: u64 = 25;
+/// Longest a snake gets in zen
+pub const ZEN_MAX_LENGTH: usize = 40;
+/// Food to eat to solve a puzzle
+pub const PUZZLE_FOOD: usize = 7;
+
+// Puzzles place their food from this seed rather than the game's, so the
+// same board is always the same puzzle
+const PUZZLE_SEED: u64 = 0x5eed;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum GameMode {
+    /// Play until the snake dies
+    #[default]
+    Classic,
+    /// Most food in 600 ticks, a minute at the default speed
+    TimeAttack,
+    /// Walls go up as time passes, a point for every one outlived
+    Survival,
+    /// Nothing kills, running into something just stops the snake. It
+    /// only grows so far.
+    Zen,
+    /// Reach a length in a limited number of moves, with the food always in
+    /// the same places. Moves to spare are bonus points.
+    Puzzle,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 5] = [
+        GameMode::Classic,
+        GameMode::TimeAttack,
+        GameMode::Survival,
+        GameMode::Zen,
+        GameMode::Puzzle,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Classic => "CLASSIC",
+            GameMode::TimeAttack => "TIME ATTACK",
+            GameMode::Survival => "SURVIVAL",
+            GameMode::Zen => "ZEN",
+            GameMode::Puzzle => "PUZZLE",
+        }
+    }
+
+    /// Rules for a game on `game`'s board, with the snake starting at `start`
+    pub fn rules(self, game: &Game, start: Pos) -> Arc<dyn Rules> {
+        match self {
+            GameMode::Classic => Arc::new(Classic),
+            GameMode::TimeAttack => Arc::new(TimeAttack),
+            GameMode::Survival => Arc::new(Survival),
+            GameMode::Zen => Arc::new(Zen),
+            GameMode::Puzzle => Arc::new(Puzzle::new(game, start)),
+        }
+    }
+}
+
+/// How a game that isn't over any more ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Ending {
+    Died,
+    /// The clock ran out, how every time attack that isn't lost ends
+    TimeUp,
+    /// Reached the length a puzzle asked for
+    Solved,
+    /// Ran out of moves before solving a puzzle
+    OutOfMoves,
+    /// Left no room on the board for food
+    Filled,
+}
+
+impl Ending {
+    /// Heading of the game over screen
+    pub fn title(self) -> &'static str {
+        match self {
+            Ending::Died => "GAME OVER",
+            Ending::TimeUp => "TIME UP",
+            Ending::Solved => "SOLVED",
+            Ending::OutOfMoves => "OUT OF MOVES",
+            Ending::Filled => "BOARD FILLED",
+        }
+    }
+}
+
+/// What a mode changes about a game. The defaults are the classic rules.
+pub trait Rules: Send + Sync {
+    /// Whether running into something kills, otherwise the snake stays put
+    /// until it's steered somewhere free
+    fn deadly(&self) -> bool {
+        true
+    }
+
+    /// Longest the snake grows, food past that only scores
+    fn max_length(&self) -> Option<usize> {
+        None
+    }
+
+    /// Tiles food appears on, in order, before it goes back to random ones
+    fn food_spawns(&self) -> &[Pos] {
+        &[]
+    }
+
+    /// Called after every move of a living snake, may end the game
+    fn after_move(&self, _game: &mut Game) -> Option<Ending> {
+        None
+    }
+
+    /// What's left to go, for the HUD, at `tick_ms` per move
+    fn status(&self, _game: &Game, _tick_ms: u64) -> Option<String> {
+        None
+    }
+}
+
+// Moves left before `limit`
+fn moves_left(game: &Game, limit: u64) -> u64 {
+    limit.saturating_sub(game.moves())
+}
+
+/// No changes, the rules of `GameMode::Classic`
+pub struct Classic;
+
+impl Rules for Classic {}
+
+struct TimeAttack;
+
+// Game time left in a time attack, in `TICK_PARTS`
+fn time_left(game: &Game) -> u64 {
+    (TIME_ATTACK_TICKS * TICK_PARTS).saturating_sub(game.elapsed())
+}
+
+impl Rules for TimeAttack {
+    fn after_move(&self, game: &mut Game) -> Option<Ending> {
+        (time_left(game) == 0).then_some(Ending::TimeUp)
+    }
+
+    fn status(&self, game: &Game, tick_ms: u64) -> Option<String> {
+        let ms = time_left(game) * tick_ms / TICK_PARTS;
+        Some(format!("TIME {}", ms.div_ceil(1000)))
+    }
+}
+
+struct Survival;
+
+impl Rules for Survival {
+    fn after_move(&self, game: &mut Game) -> Option<Ending> {
+        if game.moves().is_multiple_of(SURVIVAL_WALL_EVERY) && game.raise_wall() {
+            game.add_score(1);
+        }
+        None
+    }
+
+    fn status(&self, game: &Game, _tick_ms: u64) -> Option<String> {
+        let next = SURVIVAL_WALL_EVERY - game.moves() % SURVIVAL_WALL_EVERY;
+        Some(format!("WALL IN {next}"))
+    }
+}
+
+struct Zen;
+
+impl Rules for Zen {
+    fn deadly(&self) -> bool {
+        false
+    }
+
+    fn max_length(&self) -> Option<usize> {
+        Some(ZEN_MAX_LENGTH)
+    }
+}
+
+// Moves from `from` to every tile, going around walls and through portals.
+// None for the tiles it can't get to.
+fn distances(game: &Game, from: Pos) -> Vec<Option<usize>> {
+    let topology = game.topology();
+    let mut distances = vec![None; game.tiles().len()];
+    distances[topology.index(from)] = Some(0);
+    let mut queue = VecDeque::from([(from, 0)]);
+    while let Some((pos, distance)) = queue.pop_front() {
+        for &direction in topology.directions() {
+            let Some(next) = game.step(pos, direction) else {
+                continue;
+            };
+            let i = topology.index(next);
+            if distances[i].is_none() && !matches!(game.tile(next), Tile::Wall | Tile::Void) {
+                distances[i] = Some(distance + 1);
+                queue.push_back((next, distance + 1));
+            }
+        }
+    }
+    distances
+}
+
+struct Puzzle {
+    spawns: Vec<Pos>,
+    // The shortest way past every food, ignoring the snake, and half again
+    moves: u64,
+}
+
+impl Puzzle {
+    fn new(game: &Game, start: Pos) -> Self {
+        let topology = game.topology();
+        let reachable = distances(game, start);
+        let mut free: Vec<Pos> = (0..game.tiles().len())
+            .filter(|&index| reachable[index].is_some())
+            .map(|index| topology.pos(index))
+            .filter(|&pos| {
+                pos != start && game.tile(pos) == Tile::Empty && game.entity_at(pos).is_none()
+            })
+            .collect();
+        let mut rng = ChaCha8Rng::seed_from_u64(PUZZLE_SEED);
+        free.shuffle(&mut rng);
+        free.truncate(PUZZLE_FOOD);
+
+        // Every food is reachable from the start, so from each other too
+        let path: usize = std::iter::once(start)
+            .chain(free.iter().copied())
+            .collect::<Vec<_>>()
+            .windows(2)
+            .filter_map(|pair| distances(game, pair[0])[topology.index(pair[1])])
+            .sum();
+        Self {
+            spawns: free,
+            moves: (path as u64 * 3).div_ceil(2),
+        }
+    }
+
+    fn target(&self) -> usize {
+        1 + self.spawns.len()
+    }
+}
+
+impl Rules for Puzzle {
+    fn food_spawns(&self) -> &[Pos] {
+        &self.spawns
+    }
+
+    fn after_move(&self, game: &mut Game) -> Option<Ending> {
+        if game.snake_len() >= self.target() {
+            game.add_score(moves_left(game, self.moves) as u32);
+            Some(Ending::Solved)
+        } else if moves_left(game, self.moves) == 0 {
+            Some(Ending::OutOfMoves)
+        } else {
+            None
+        }
+    }
+
+    fn status(&self, game: &Game, _tick_ms: u64) -> Option<String> {
+        Some(format!(
+            "GOAL {}  MOVES {}",
+            self.target(),
+            moves_left(game, self.moves)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{ActiveEffect, Direction, Effect, GameOptions};
+    use crate::level::Level;
+
+    // Next to the snake's head, the free tile closest to the food
+    fn towards_food(game: &Game) -> Direction {
+        let topology = game.topology();
+        let head = game.snake_tiles(0).unwrap().next().unwrap();
+        let free = |pos: &Pos| matches!(game.tile(*pos), Tile::Empty | Tile::Food);
+        topology
+            .directions()
+            .iter()
+            .copied()
+            .filter_map(|d| Some((d, game.step(head, d).filter(free)?)))
+            .min_by_key(|(_, next)| topology.distance(*next, game.food()))
+            .map_or(Direction::Up, |(d, _)| d)
+    }
+
+    fn game(mode: GameMode, wrap: bool, seed: u64) -> Game {
+        let options = GameOptions {
+            wrap,
+            seed: Some(seed),
+            mode,
+            ..Default::default()
+        };
+        Game::with_options(10, 10, 10., options)
+    }
+
+    #[test]
+    fn time_attacks_end_when_the_clock_runs_out() {
+        let mut game = game(GameMode::TimeAttack, true, 1);
+        assert_eq!(game.rules().status(&game, 100).as_deref(), Some("TIME 60"));
+        game.change_direction(Direction::Right);
+        for _ in 1..TIME_ATTACK_TICKS {
+            assert!(game.update());
+        }
+        assert_eq!(game.rules().status(&game, 100).as_deref(), Some("TIME 1"));
+        assert!(!game.update());
+        assert_eq!(game.ending(), Some(Ending::TimeUp));
+
+        // Nothing moves once it's over
+        assert!(!game.update());
+        assert_eq!(game.moves(), TIME_ATTACK_TICKS);
+    }
+
+    #[test]
+    fn time_attacks_count_slowed_down_and_sped_up_moves() {
+        // Slowed down moves take two ticks, sped up ones half a tick
+        for (effect, time, moves) in [
+            (Effect::Slow, "TIME 40", TIME_ATTACK_TICKS - 100),
+            (Effect::Speed, "TIME 55", TIME_ATTACK_TICKS + 50),
+        ] {
+            let mut game = game(GameMode::TimeAttack, true, 1);
+            game.set_effects(vec![ActiveEffect {
+                effect,
+                ticks_left: 100,
+            }]);
+            game.change_direction(Direction::Right);
+            for _ in 0..100 {
+                assert!(game.update());
+            }
+            assert_eq!(game.rules().status(&game, 100).as_deref(), Some(time));
+            while game.update() {}
+            assert_eq!(game.ending(), Some(Ending::TimeUp));
+            assert_eq!(game.moves(), moves);
+        }
+    }
+
+    #[test]
+    fn survival_walls_go_up_away_from_the_snake() {
+        let mut game = game(GameMode::Survival, true, 4);
+        let walls = |game: &Game| -> Vec<usize> {
+            (0..game.tiles().len())
+                .filter(|i| game.tiles()[*i] == Tile::Wall)
+                .collect()
+        };
+        for _ in 0..SURVIVAL_WALL_EVERY * 2 {
+            let before = walls(&game);
+            game.change_direction(towards_food(&game));
+            assert!(game.update());
+
+            let after = walls(&game);
+            if !game.moves().is_multiple_of(SURVIVAL_WALL_EVERY) {
+                assert_eq!(after, before);
+                continue;
+            }
+            let new: Vec<_> = after.iter().filter(|w| !before.contains(w)).collect();
+            assert_eq!(new.len(), 1);
+            let head = game.snake_tiles(0).unwrap().next().unwrap();
+            let wall = game.topology().pos(*new[0]);
+            assert!(game.topology().distance(wall, head) > 2);
+        }
+        // A point for every food and every wall
+        assert_eq!(game.score() as usize, game.snake_len() - 1 + 2);
+    }
+
+    #[test]
+    fn puzzles_place_the_same_food_whatever_the_seed() {
+        let a = game(GameMode::Puzzle, true, 1);
+        let b = game(GameMode::Puzzle, true, 2);
+        assert_eq!(a.food(), b.food());
+        assert_eq!(a.rules().food_spawns(), b.rules().food_spawns());
+        assert_eq!(a.rules().food_spawns().len(), PUZZLE_FOOD);
+
+        let mut game = a;
+        let mut eaten = vec![game.food()];
+        loop {
+            game.change_direction(towards_food(&game));
+            if !game.update() {
+                break;
+            }
+            if *eaten.last().unwrap() != game.food() {
+                eaten.push(game.food());
+            }
+        }
+        assert_eq!(game.ending(), Some(Ending::Solved));
+        assert_eq!(eaten[..PUZZLE_FOOD], *game.rules().food_spawns());
+        // Moves to spare are points
+        assert!(game.score() as usize > PUZZLE_FOOD);
+    }
+
+    #[test]
+    fn puzzle_moves_go_around_walls() {
+        let options = GameOptions {
+            level: Level::parse("test", "...\n##.\n...\n").unwrap(),
+            mode: GameMode::Puzzle,
+            ..Default::default()
+        };
+        let game = Game::with_options(3, 3, 10., options);
+        let (a, b) = (Pos { x: 0, y: 0 }, Pos { x: 0, y: 2 });
+        assert_eq!(game.topology().distance(a, b), 2);
+        let index = game.topology().index(b);
+        assert_eq!(distances(&game, a)[index], Some(6));
+
+        // Walled in, there's no way there at all
+        let options = GameOptions {
+            level: Level::parse("test", ".#.\n..#\n...\n").unwrap(),
+            ..Default::default()
+        };
+        let game = Game::with_options(3, 3, 10., options);
+        let index = game.topology().index(Pos { x: 2, y: 2 });
+        assert_eq!(distances(&game, a)[index], None);
+    }
+
+    #[test]
+    fn puzzles_run_out_of_moves() {
+        let mut game = game(GameMode::Puzzle, true, 1);
+        // Round and round in a tight square, never getting anywhere
+        let circle = [
+            Direction::Up,
+            Direction::Right,
+            Direction::Down,
+            Direction::Left,
+        ];
+        let mut moves = 0;
+        while game.update() {
+            game.change_direction(circle[moves % 4]);
+            moves += 1;
+        }
+        assert_eq!(game.ending(), Some(Ending::OutOfMoves));
+        assert!(game
+            .rules()
+            .status(&game, 100)
+            .unwrap()
+            .ends_with("MOVES 0"));
+    }
+}
@@ -0,0 +1,163 @@
+// Rendering without a window: a device that doesn't need a surface, reading
+// textures back and saving them as PNGs. Used for screenshots and the golden
+// image tests.
+
+use std::path::PathBuf;
+use std::sync::mpsc;
+
+use crate::raster::Image;
+use crate::renderer::Renderer;
+use crate::scene::Scene;
+
+// Format offscreen frames are rendered in, sRGB like the window surface
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// A device with no surface to present to. Falls back to a software adapter
+/// (WARP, llvmpipe, SwiftShader...) when there's no GPU, None if there's no
+/// adapter at all.
+pub fn headless_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+
+    let adapter = [false, true]
+        .into_iter()
+        .find_map(|force_fallback_adapter| {
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+                force_fallback_adapter,
+            }))
+        })?;
+    log::info!("rendering offscreen with {:?}", adapter.get_info());
+
+    pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            features: wgpu::Features::empty(),
+            limits: wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+            label: Some("offscreen device"),
+        },
+        None,
+    ))
+    .map_err(|e| log::error!("couldn't get an offscreen device: {e}"))
+    .ok()
+}
+
+/// Renders `scene` without a window, None if there's no adapter to do it
+pub fn render(scene: &Scene) -> Option<Result<Image, String>> {
+    let (device, queue) = headless_device()?;
+    let (width, height) = scene.size();
+    let mut renderer = Renderer::new(device, queue, FORMAT, width, height);
+    Some(renderer.capture(scene))
+}
+
+/// Where the screenshot hotkey saves to: the pictures dir, or else the
+/// current one
+fn screenshot_path() -> PathBuf {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    dirs::picture_dir()
+        .unwrap_or_default()
+        .join(format!("snake-{secs}.png"))
+}
+
+pub fn save_screenshot(image: Result<Image, String>) {
+    let path = screenshot_path();
+    match image.and_then(|image| image.save_png(&path)) {
+        Ok(()) => log::info!("saved screenshot to {}", path.display()),
+        Err(e) => log::error!("screenshot failed: {e}"),
+    }
+}
+
+/// Copies `texture` (which needs COPY_SRC usage) into an image
+pub fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Result<Image, String> {
+    use wgpu::TextureFormat::*;
+
+    let format = texture.format();
+    let swap_red_blue = match format {
+        Rgba8Unorm | Rgba8UnormSrgb => false,
+        Bgra8Unorm | Bgra8UnormSrgb => true,
+        _ => return Err(format!("can't read back {format:?} textures")),
+    };
+
+    let (width, height) = (texture.width(), texture.height());
+    let row_bytes = width * 4;
+    // Rows in the buffer have to be padded to a multiple of 256 bytes
+    let padded_row_bytes =
+        row_bytes.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("readback buffer"),
+        size: (padded_row_bytes * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("readback encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_bytes),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver
+        .recv()
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("couldn't read the frame back: {e}"))?;
+
+    let mut pixels = Vec::with_capacity((row_bytes * height) as usize);
+    for row in slice.get_mapped_range().chunks(padded_row_bytes as usize) {
+        pixels.extend_from_slice(&row[..row_bytes as usize]);
+    }
+    buffer.unmap();
+
+    if swap_red_blue {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+
+    // Machines without a GPU still have a software adapter, so not finding one
+    // fails the test rather than quietly skipping it
+    #[test]
+    fn gpu_frames_match_golden_images() {
+        for name in golden::CASES {
+            golden::with_scene(name, |scene| {
+                let image = render(scene).expect("no wgpu adapter to render with");
+                golden::compare(name, &image.unwrap());
+            });
+        }
+    }
+}
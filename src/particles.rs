@@ -0,0 +1,400 @@
+// Particles and screen shake set off by game events: a burst where food gets
+// eaten, debris when a snake dies, a trail behind sped up snakes and a shake
+// of the board on death. Simulated on the render side in fixed steps of
+// `STEP` however long frames take, and never looked at by the game, so games
+// play out the same with them or without.
+//
+// Each frame they're handed to the renderer as instances, one quad each,
+// colored from the board colors like the tiles (see `Theme::board_colors`).
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::event::Event;
+use crate::game::{Effect, Game, Pos};
+
+/// Seconds simulated per step
+pub const STEP: f32 = 1. / 120.;
+/// Particles alive at once, new ones past that are left out
+pub const MAX_PARTICLES: usize = 4096;
+// Frame time caught up on at most, so a stall (the window being dragged, the
+// machine sleeping) doesn't freeze the game while particles catch up
+const MAX_CATCH_UP: f32 = 0.25;
+// Fraction of their speed particles keep after a second
+const DRAG: f32 = 0.1;
+
+// How long a death shakes the board for, and how far at first in tiles
+const SHAKE_SECS: f32 = 0.35;
+const SHAKE_TILES: f32 = 0.4;
+
+// `coloridx` of the tiles particles come off
+const SNAKE_COLOR: u32 = 0;
+const FOOD_COLOR: u32 = 2;
+
+// How a kind of particle starts out, sizes and speeds in tiles
+struct Emitter {
+    count: usize,
+    speed: [f32; 2],
+    // Seconds
+    life: [f32; 2],
+    size: f32,
+    // Tiles per second squared, up
+    gravity: f32,
+    coloridx: u32,
+}
+
+const BURST: Emitter = Emitter {
+    count: 16,
+    speed: [2., 6.],
+    life: [0.25, 0.5],
+    size: 0.25,
+    gravity: 0.,
+    coloridx: FOOD_COLOR,
+};
+
+// For every tile of the snake
+const DEBRIS: Emitter = Emitter {
+    count: 4,
+    speed: [3., 10.],
+    life: [0.6, 1.2],
+    size: 0.35,
+    gravity: -20.,
+    coloridx: SNAKE_COLOR,
+};
+
+// Every move, off the end of the tail
+const TRAIL: Emitter = Emitter {
+    count: 3,
+    speed: [0., 1.],
+    life: [0.2, 0.45],
+    size: 0.3,
+    gravity: 0.,
+    coloridx: SNAKE_COLOR,
+};
+
+#[derive(Debug, Clone, Copy)]
+struct Particle {
+    // Pixels, y up from the bottom left of the board
+    pos: [f32; 2],
+    vel: [f32; 2],
+    gravity: f32,
+    size: f32,
+    coloridx: u32,
+    age: f32,
+    life: f32,
+}
+
+/// A particle as the renderer gets it, drawn as a square
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ParticleInstance {
+    pub center: [f32; 2],
+    pub size: f32,
+    pub coloridx: u32, // index into color uniforms
+    pub alpha: f32,
+}
+
+impl ParticleInstance {
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRS: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+            0 => Float32x2, 1 => Float32, 2 => Uint32, 3 => Float32
+        ];
+
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &ATTRS,
+        }
+    }
+
+    /// Two triangles covering it, in the order particles.wgsl makes them
+    pub fn corners(&self) -> [[f32; 2]; 6] {
+        let [x, y] = self.center;
+        let h = self.size / 2.;
+        let (l, r, b, t) = (x - h, x + h, y - h, y + h);
+        [[l, b], [r, b], [l, t], [r, b], [r, t], [l, t]]
+    }
+}
+
+pub struct Particles {
+    particles: Vec<Particle>,
+    // Seconds of shake left, and how far it goes at the start in pixels
+    shake_left: f32,
+    shake_size: f32,
+    // Seconds simulated so far, and frame time not simulated yet
+    time: f32,
+    pending: f32,
+    // Its own, the game's stays untouched
+    rng: ChaCha8Rng,
+}
+
+impl Default for Particles {
+    fn default() -> Self {
+        Self {
+            particles: Vec::new(),
+            shake_left: 0.,
+            shake_size: 0.,
+            time: 0.,
+            pending: 0.,
+            rng: ChaCha8Rng::seed_from_u64(0),
+        }
+    }
+}
+
+impl Particles {
+    /// Sets off effects for what happened on a tick of `game`, right after it
+    pub fn on_tick(&mut self, events: &[Event], game: &Game) {
+        for event in events {
+            match *event {
+                Event::FoodEaten { pos, .. } => self.emit(&BURST, game, pos),
+                Event::Died { snake, .. } => {
+                    let tiles: Vec<Pos> = game.snake_tiles(snake).into_iter().flatten().collect();
+                    for pos in tiles {
+                        self.emit(&DEBRIS, game, pos);
+                    }
+                    self.shake_left = SHAKE_SECS;
+                    self.shake_size = SHAKE_TILES * game.tile_size();
+                }
+                _ => {}
+            }
+        }
+
+        for snake in game.snakes().filter(|snake| snake.alive) {
+            let fast = game
+                .snake_effects(snake.id)
+                .is_some_and(|effects| effects.iter().any(|a| a.effect == Effect::Speed));
+            let tail = game.snake_tiles(snake.id).and_then(|tiles| tiles.last());
+            if let (true, Some(tail)) = (fast, tail) {
+                self.emit(&TRAIL, game, tail);
+            }
+        }
+    }
+
+    fn emit(&mut self, emitter: &Emitter, game: &Game, pos: Pos) {
+        let tile_size = game.tile_size();
+        let center = game.topology().center(pos, tile_size);
+        for _ in 0..emitter.count {
+            if self.particles.len() == MAX_PARTICLES {
+                return;
+            }
+            let angle = self.rng.gen_range(0. ..std::f32::consts::TAU);
+            let [slow, fast] = emitter.speed;
+            let speed = self.rng.gen_range(slow..=fast) * tile_size;
+            let [short, long] = emitter.life;
+            self.particles.push(Particle {
+                pos: center,
+                vel: [angle.cos() * speed, angle.sin() * speed],
+                gravity: emitter.gravity * tile_size,
+                size: emitter.size * tile_size,
+                coloridx: emitter.coloridx,
+                age: 0.,
+                life: self.rng.gen_range(short..=long),
+            });
+        }
+    }
+
+    /// Moves everything on by `secs` of frame time, in as many whole steps as
+    /// fit. The rest waits for the next frame.
+    pub fn advance(&mut self, secs: f32) {
+        self.pending = (self.pending + secs).min(MAX_CATCH_UP);
+        while self.pending >= STEP {
+            self.pending -= STEP;
+            self.step();
+        }
+    }
+
+    fn step(&mut self) {
+        let drag = DRAG.powf(STEP);
+        for p in &mut self.particles {
+            p.vel[1] += p.gravity * STEP;
+            p.vel = p.vel.map(|v| v * drag);
+            p.pos[0] += p.vel[0] * STEP;
+            p.pos[1] += p.vel[1] * STEP;
+            p.age += STEP;
+        }
+        self.particles.retain(|p| p.age < p.life);
+        self.shake_left = (self.shake_left - STEP).max(0.);
+        self.time += STEP;
+    }
+
+    /// True when there's nothing moving, so frames can wait for the game
+    pub fn is_idle(&self) -> bool {
+        self.particles.is_empty() && self.shake_left == 0.
+    }
+
+    /// Particles to draw this frame, fading and shrinking as they get old
+    pub fn instances(&self) -> Vec<ParticleInstance> {
+        self.particles
+            .iter()
+            .map(|p| {
+                let left = 1. - p.age / p.life;
+                ParticleInstance {
+                    center: p.pos,
+                    size: p.size * (0.5 + left / 2.),
+                    coloridx: p.coloridx,
+                    alpha: left,
+                }
+            })
+            .collect()
+    }
+
+    /// How far the board is moved this frame in pixels, dying down over the
+    /// shake
+    pub fn shake(&self) -> [f32; 2] {
+        let left = self.shake_left / SHAKE_SECS;
+        let size = self.shake_size * left * left;
+        // Two frequencies that don't line up, so it doesn't just go diagonally
+        let t = self.time * std::f32::consts::TAU;
+        [size * (t * 23.).sin(), size * (t * 17.).cos()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{ActiveEffect, DeathCause, Direction, GameOptions};
+
+    fn game() -> Game {
+        let options = GameOptions {
+            seed: Some(1),
+            ..Default::default()
+        };
+        let mut game = Game::with_options(10, 10, 10., options);
+        game.change_direction(Direction::Right);
+        game
+    }
+
+    // A bit over `secs` of 60 FPS frames
+    fn play(particles: &mut Particles, secs: f32) {
+        for _ in 0..=(secs * 60.) as usize {
+            particles.advance(1. / 60.);
+        }
+    }
+
+    #[test]
+    fn food_bursts_then_fades_away() {
+        let game = game();
+        let mut particles = Particles::default();
+        let pos = Pos { x: 3, y: 4 };
+        particles.on_tick(
+            &[Event::FoodEaten {
+                snake: 0,
+                pos,
+                points: 1,
+            }],
+            &game,
+        );
+        let center = game.topology().center(pos, game.tile_size());
+        let instances = particles.instances();
+        assert_eq!(instances.len(), BURST.count);
+        assert!(instances
+            .iter()
+            .all(|i| i.center == center && i.alpha == 1. && i.coloridx == FOOD_COLOR));
+        assert_eq!(particles.shake(), [0., 0.]);
+
+        particles.advance(0.1);
+        let moved = particles.instances();
+        assert!(moved.iter().all(|i| i.center != center && i.alpha < 1.));
+
+        play(&mut particles, BURST.life[1]);
+        assert!(particles.is_idle());
+        assert!(particles.instances().is_empty());
+    }
+
+    #[test]
+    fn deaths_scatter_the_snake_and_shake_the_board() {
+        let mut game = game();
+        game.update();
+        let length = game.snake_len();
+        let mut particles = Particles::default();
+        particles.on_tick(
+            &[Event::Died {
+                snake: 0,
+                cause: DeathCause::Edge,
+            }],
+            &game,
+        );
+        assert_eq!(particles.instances().len(), length * DEBRIS.count);
+
+        particles.advance(STEP);
+        let [x, y] = particles.shake();
+        assert!(x != 0. || y != 0.);
+        assert!(x.abs().max(y.abs()) <= SHAKE_TILES * game.tile_size());
+
+        play(&mut particles, SHAKE_SECS);
+        assert_eq!(particles.shake(), [0., 0.]);
+        assert!(!particles.is_idle());
+    }
+
+    #[test]
+    fn sped_up_snakes_leave_a_trail() {
+        let mut game = game();
+        let mut particles = Particles::default();
+        particles.on_tick(&[], &game);
+        assert!(particles.is_idle());
+
+        game.set_effects(vec![ActiveEffect {
+            effect: Effect::Speed,
+            ticks_left: 5,
+        }]);
+        particles.on_tick(&[], &game);
+        let tail = game.snake_tiles(0).unwrap().last().unwrap();
+        let center = game.topology().center(tail, game.tile_size());
+        let instances = particles.instances();
+        assert_eq!(instances.len(), TRAIL.count);
+        assert!(instances.iter().all(|i| i.center == center));
+    }
+
+    #[test]
+    fn stalls_are_not_caught_up_on() {
+        let game = game();
+        let mut particles = Particles::default();
+        let eaten = Event::FoodEaten {
+            snake: 0,
+            pos: Pos { x: 3, y: 4 },
+            points: 1,
+        };
+        particles.on_tick(&[eaten], &game);
+        particles.advance(10.);
+        assert!(particles.time <= MAX_CATCH_UP);
+        assert_eq!(particles.instances().len(), BURST.count);
+    }
+
+    #[test]
+    fn frames_are_simulated_in_whole_steps() {
+        let game = game();
+        let burst = |frames: &[f32]| {
+            let mut particles = Particles::default();
+            let pos = Pos { x: 3, y: 4 };
+            let eaten = Event::FoodEaten {
+                snake: 0,
+                pos,
+                points: 1,
+            };
+            particles.on_tick(&[eaten], &game);
+            for secs in frames {
+                particles.advance(*secs);
+            }
+            particles.instances()
+        };
+        // Same steps however the time is split into frames
+        assert_eq!(
+            burst(&[STEP * 6.2]),
+            burst(&[STEP * 2.5, STEP * 2.5, STEP * 1.2])
+        );
+        assert_eq!(burst(&[STEP / 2.]), burst(&[]));
+    }
+
+    #[test]
+    fn there_are_only_so_many_particles() {
+        let game = game();
+        let mut particles = Particles::default();
+        let eaten = Event::FoodEaten {
+            snake: 0,
+            pos: Pos { x: 3, y: 4 },
+            points: 1,
+        };
+        particles.on_tick(&[eaten; MAX_PARTICLES], &game);
+        assert_eq!(particles.instances().len(), MAX_PARTICLES);
+    }
+}
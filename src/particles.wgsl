@@ -0,0 +1,64 @@
+// Same color uniforms and view as the tile shader, see shader.wgsl
+struct ColorUniforms {
+    colors: array<vec4<f32>, 10>,
+}
+
+struct View {
+    size: vec2<f32>,
+    offset: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> color_uniforms: ColorUniforms;
+
+@group(0) @binding(1)
+var<uniform> view: View;
+
+// One per particle
+struct InstanceInput {
+    @location(0) center: vec2<f32>,
+    @location(1) size: f32,
+    @location(2) coloridx: u32,
+    @location(3) alpha: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+// Two triangles making up a square, in the order of
+// `ParticleInstance::corners`
+var<private> CORNERS: array<vec2<f32>, 6> = array<vec2<f32>, 6>(
+    vec2<f32>(-0.5, -0.5),
+    vec2<f32>(0.5, -0.5),
+    vec2<f32>(-0.5, 0.5),
+    vec2<f32>(0.5, -0.5),
+    vec2<f32>(0.5, 0.5),
+    vec2<f32>(-0.5, 0.5),
+);
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) index: u32,
+    particle: InstanceInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let rgb = color_uniforms.colors[particle.coloridx].rgb;
+    out.color = vec4<f32>(rgb, particle.alpha);
+
+    let pos = particle.center + CORNERS[index] * particle.size + view.offset;
+    let pos_float = pos / view.size;
+    // Same pixel -> clip conversion as the tile shader
+    let x = (pos_float.x - 0.5) * 2.;
+    let y = (pos_float.y - 0.5) * 2.;
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
@@ -0,0 +1,662 @@
+// Wire format between `snake server` and its clients, also spoken by
+// `--stream` to viewers (which only ever get to spectate). Everything is little
+// endian and every message is framed as
+//
+//     u32 length of what follows | u8 kind | payload
+//
+// Client to server:
+//
+//     1  Hello     u16 version, u8 role (0 player, 1 spectator), string name
+//     2  Input     u64 tick, u8 direction
+//     3  Leave
+//
+// Server to client:
+//
+//     16 Welcome   u16 version, u8 snake id (255 for spectators), u16 cols,
+//                  u16 rows, u8 grid (0 square, 1 hex), u32 tick_ms, u16 input
+//                  delay in ticks, u32 count, count * (u32, u32) portal pairs
+//     17 Reject    string reason, the connection is closed after it
+//     18 Keyframe  u64 tick, cols * rows u8 tiles, snakes, entities
+//     19 Delta     u64 tick, u32 count, count * (u32 tile index, u8 tile), snakes,
+//                  entities
+//     20 Joined    u8 snake id, string name
+//     21 Left      u8 snake id
+//
+// where a string is a u8 length and that many bytes of UTF-8, snakes are a
+// u32 count and count * (u8 id, u8 alive, u32 score, u32 length), entities a
+// u32 count and count * (u8 kind, u32 tile index), tiles are numbered row by
+// row from the bottom left and use `Tile`'s discriminants, directions
+// `Direction`'s (except 0, a snake can't be steered to a stop) and entity
+// kinds `EntityKind`'s.
+//
+// A client says Hello first and the server answers Welcome or Reject. After
+// that the server sends a Keyframe, then a Delta every tick with the tiles
+// that changed, and Keyframes now and then so a dropped Delta doesn't stick.
+// An Input asks for a direction change on the tick it names, so every
+// player's inputs are delayed by the same number of ticks. Bump VERSION on
+// any change to the format.
+
+use std::io::{self, Read, Write};
+
+use crate::entity::EntityKind;
+use crate::game::{Direction, Game, SnakeId, SnakeInfo, Tile};
+use crate::topology::Grid;
+
+pub const VERSION: u16 = 7;
+
+// Snake id sent to spectators, who don't have a snake
+const NO_SNAKE: u8 = 255;
+
+// Larger messages are refused, a 255x255 keyframe is well below this
+const MAX_MESSAGE: u32 = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Player,
+    Spectator,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientMessage {
+    Hello {
+        version: u16,
+        role: Role,
+        name: String,
+    },
+    Input {
+        tick: u64,
+        direction: Direction,
+    },
+    Leave,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerMessage {
+    Welcome {
+        version: u16,
+        snake: Option<SnakeId>,
+        cols: usize,
+        rows: usize,
+        grid: Grid,
+        tick_ms: u64,
+        input_delay: u64,
+        // Tile indices, so viewers can tell the pairs apart
+        portals: Vec<(usize, usize)>,
+    },
+    Reject {
+        reason: String,
+    },
+    Keyframe {
+        tick: u64,
+        tiles: Vec<Tile>,
+        snakes: Vec<SnakeInfo>,
+        entities: Vec<(usize, EntityKind)>,
+    },
+    Delta {
+        tick: u64,
+        changes: Vec<(usize, Tile)>,
+        snakes: Vec<SnakeInfo>,
+        entities: Vec<(usize, EntityKind)>,
+    },
+    Joined {
+        snake: SnakeId,
+        name: String,
+    },
+    Left {
+        snake: SnakeId,
+    },
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn tile_from_u8(byte: u8) -> io::Result<Tile> {
+    Ok(match byte {
+        0 => Tile::Empty,
+        1 => Tile::Food,
+        2 => Tile::SnakeBody,
+        3 => Tile::SnakeHead,
+        4 => Tile::Wall,
+        5 => Tile::Void,
+        6 => Tile::Portal,
+        7 => Tile::PowerUp,
+        _ => return Err(invalid(format!("unknown tile {byte}"))),
+    })
+}
+
+fn entity_kind_from_u8(byte: u8) -> io::Result<EntityKind> {
+    Ok(match byte {
+        0 => EntityKind::Block,
+        1 => EntityKind::Ball,
+        2 => EntityKind::Hunter,
+        _ => return Err(invalid(format!("unknown entity {byte}"))),
+    })
+}
+
+// Only directions a snake can be steered in
+fn direction_from_u8(byte: u8) -> io::Result<Direction> {
+    Ok(match byte {
+        1 => Direction::Right,
+        2 => Direction::Left,
+        3 => Direction::Up,
+        4 => Direction::Down,
+        5 => Direction::UpRight,
+        6 => Direction::UpLeft,
+        7 => Direction::DownRight,
+        8 => Direction::DownLeft,
+        _ => return Err(invalid(format!("unknown direction {byte}"))),
+    })
+}
+
+/// Builds a message payload
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn new(kind: u8) -> Self {
+        Self { bytes: vec![kind] }
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    // Cut to the 255 bytes that fit, on a char boundary
+    fn string(&mut self, value: &str) {
+        let mut end = value.len().min(u8::MAX as usize);
+        while !value.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.u8(end as u8);
+        self.bytes.extend_from_slice(&value.as_bytes()[..end]);
+    }
+
+    fn snakes(&mut self, snakes: &[SnakeInfo]) {
+        self.u32(snakes.len() as u32);
+        for snake in snakes {
+            self.u8(snake.id as u8);
+            self.u8(snake.alive as u8);
+            self.u32(snake.score);
+            self.u32(snake.length as u32);
+        }
+    }
+
+    fn entities(&mut self, entities: &[(usize, EntityKind)]) {
+        self.u32(entities.len() as u32);
+        for &(index, kind) in entities {
+            self.u8(kind as u8);
+            self.u32(index as u32);
+        }
+    }
+
+    fn send(self, out: &mut impl Write) -> io::Result<()> {
+        let mut framed = Vec::with_capacity(self.bytes.len() + 4);
+        framed.extend_from_slice(&(self.bytes.len() as u32).to_le_bytes());
+        framed.extend_from_slice(&self.bytes);
+        out.write_all(&framed)?;
+        out.flush()
+    }
+}
+
+/// Reads a message payload, failing on anything truncated
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> io::Result<&[u8]> {
+        if self.bytes.len() < n {
+            return Err(invalid("message is too short"));
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.u8()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|e| invalid(e.to_string()))
+    }
+
+    fn snakes(&mut self) -> io::Result<Vec<SnakeInfo>> {
+        (0..self.u32()?)
+            .map(|_| {
+                Ok(SnakeInfo {
+                    id: self.u8()? as SnakeId,
+                    alive: self.u8()? != 0,
+                    score: self.u32()?,
+                    length: self.u32()? as usize,
+                })
+            })
+            .collect()
+    }
+
+    fn entities(&mut self) -> io::Result<Vec<(usize, EntityKind)>> {
+        (0..self.u32()?)
+            .map(|_| {
+                let kind = entity_kind_from_u8(self.u8()?)?;
+                Ok((self.u32()? as usize, kind))
+            })
+            .collect()
+    }
+
+    fn finish(&self) -> io::Result<()> {
+        match self.bytes.len() {
+            0 => Ok(()),
+            n => Err(invalid(format!(
+                "{n} bytes left over at the end of a message"
+            ))),
+        }
+    }
+}
+
+// Reads one framed message, (kind, payload)
+fn read_frame(input: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut len = [0; 4];
+    input.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len);
+    if len == 0 || len > MAX_MESSAGE {
+        return Err(invalid(format!("message length {len} is out of range")));
+    }
+
+    let mut frame = vec![0; len as usize];
+    input.read_exact(&mut frame)?;
+    let kind = frame.remove(0);
+    Ok((kind, frame))
+}
+
+impl ClientMessage {
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        let message = match self {
+            ClientMessage::Hello {
+                version,
+                role,
+                name,
+            } => {
+                let mut w = Writer::new(1);
+                w.u16(*version);
+                w.u8(match role {
+                    Role::Player => 0,
+                    Role::Spectator => 1,
+                });
+                w.string(name);
+                w
+            }
+            ClientMessage::Input { tick, direction } => {
+                let mut w = Writer::new(2);
+                w.u64(*tick);
+                w.u8(*direction as u8);
+                w
+            }
+            ClientMessage::Leave => Writer::new(3),
+        };
+        message.send(out)
+    }
+
+    pub fn read(input: &mut impl Read) -> io::Result<Self> {
+        let (kind, payload) = read_frame(input)?;
+        let mut r = Reader { bytes: &payload };
+        let message = match kind {
+            1 => ClientMessage::Hello {
+                version: r.u16()?,
+                role: match r.u8()? {
+                    0 => Role::Player,
+                    1 => Role::Spectator,
+                    role => return Err(invalid(format!("unknown role {role}"))),
+                },
+                name: r.string()?,
+            },
+            2 => ClientMessage::Input {
+                tick: r.u64()?,
+                direction: direction_from_u8(r.u8()?)?,
+            },
+            3 => ClientMessage::Leave,
+            _ => return Err(invalid(format!("unknown client message {kind}"))),
+        };
+        r.finish()?;
+        Ok(message)
+    }
+}
+
+impl ServerMessage {
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        let message = match self {
+            ServerMessage::Welcome {
+                version,
+                snake,
+                cols,
+                rows,
+                grid,
+                tick_ms,
+                input_delay,
+                portals,
+            } => {
+                let mut w = Writer::new(16);
+                w.u16(*version);
+                w.u8(snake.map_or(NO_SNAKE, |id| id as u8));
+                w.u16(*cols as u16);
+                w.u16(*rows as u16);
+                w.u8(match grid {
+                    Grid::Square => 0,
+                    Grid::Hex => 1,
+                });
+                w.u32(*tick_ms as u32);
+                w.u16(*input_delay as u16);
+                w.u32(portals.len() as u32);
+                for &(a, b) in portals {
+                    w.u32(a as u32);
+                    w.u32(b as u32);
+                }
+                w
+            }
+            ServerMessage::Reject { reason } => {
+                let mut w = Writer::new(17);
+                w.string(reason);
+                w
+            }
+            ServerMessage::Keyframe {
+                tick,
+                tiles,
+                snakes,
+                entities,
+            } => {
+                let mut w = Writer::new(18);
+                w.u64(*tick);
+                w.bytes.extend(tiles.iter().map(|tile| *tile as u8));
+                w.snakes(snakes);
+                w.entities(entities);
+                w
+            }
+            ServerMessage::Delta {
+                tick,
+                changes,
+                snakes,
+                entities,
+            } => {
+                let mut w = Writer::new(19);
+                w.u64(*tick);
+                w.u32(changes.len() as u32);
+                for (index, tile) in changes {
+                    w.u32(*index as u32);
+                    w.u8(*tile as u8);
+                }
+                w.snakes(snakes);
+                w.entities(entities);
+                w
+            }
+            ServerMessage::Joined { snake, name } => {
+                let mut w = Writer::new(20);
+                w.u8(*snake as u8);
+                w.string(name);
+                w
+            }
+            ServerMessage::Left { snake } => {
+                let mut w = Writer::new(21);
+                w.u8(*snake as u8);
+                w
+            }
+        };
+        message.send(out)
+    }
+
+    /// Reads the next message. Keyframes don't say how many tiles they have,
+    /// so `tiles` is the board size from the Welcome (0 before it).
+    pub fn read(input: &mut impl Read, tiles: usize) -> io::Result<Self> {
+        let (kind, payload) = read_frame(input)?;
+        let mut r = Reader { bytes: &payload };
+        let message = match kind {
+            16 => ServerMessage::Welcome {
+                version: r.u16()?,
+                snake: match r.u8()? {
+                    NO_SNAKE => None,
+                    id => Some(id as SnakeId),
+                },
+                cols: r.u16()? as usize,
+                rows: r.u16()? as usize,
+                grid: match r.u8()? {
+                    0 => Grid::Square,
+                    1 => Grid::Hex,
+                    grid => return Err(invalid(format!("unknown grid {grid}"))),
+                },
+                tick_ms: r.u32()? as u64,
+                input_delay: r.u16()? as u64,
+                portals: (0..r.u32()?)
+                    .map(|_| Ok((r.u32()? as usize, r.u32()? as usize)))
+                    .collect::<io::Result<_>>()?,
+            },
+            17 => ServerMessage::Reject {
+                reason: r.string()?,
+            },
+            18 => ServerMessage::Keyframe {
+                tick: r.u64()?,
+                tiles: r
+                    .take(tiles)?
+                    .iter()
+                    .map(|byte| tile_from_u8(*byte))
+                    .collect::<io::Result<_>>()?,
+                snakes: r.snakes()?,
+                entities: r.entities()?,
+            },
+            19 => {
+                let tick = r.u64()?;
+                let changes = (0..r.u32()?)
+                    .map(|_| Ok((r.u32()? as usize, tile_from_u8(r.u8()?)?)))
+                    .collect::<io::Result<_>>()?;
+                ServerMessage::Delta {
+                    tick,
+                    changes,
+                    snakes: r.snakes()?,
+                    entities: r.entities()?,
+                }
+            }
+            20 => ServerMessage::Joined {
+                snake: r.u8()? as SnakeId,
+                name: r.string()?,
+            },
+            21 => ServerMessage::Left {
+                snake: r.u8()? as SnakeId,
+            },
+            _ => return Err(invalid(format!("unknown server message {kind}"))),
+        };
+        r.finish()?;
+        Ok(message)
+    }
+}
+
+/// Encoded Keyframe and Delta for `tick` of `game`, the Delta against
+/// `sent` which then becomes the current board
+pub fn encode_tick(tick: u64, game: &Game, sent: &mut Vec<Tile>) -> (Vec<u8>, Vec<u8>) {
+    let tiles = game.tiles();
+    let snakes: Vec<_> = game.snakes().collect();
+    let entities: Vec<_> = game
+        .entities()
+        .iter()
+        .map(|entity| (game.topology().index(entity.pos), entity.kind))
+        .collect();
+    let changes = tiles
+        .iter()
+        .zip(sent.iter())
+        .enumerate()
+        .filter(|(_, (new, old))| new != old)
+        .map(|(i, (new, _))| (i, *new))
+        .collect();
+
+    let mut keyframe = vec![];
+    ServerMessage::Keyframe {
+        tick,
+        tiles: tiles.to_vec(),
+        snakes: snakes.clone(),
+        entities: entities.clone(),
+    }
+    .write(&mut keyframe)
+    .unwrap();
+    let mut delta = vec![];
+    ServerMessage::Delta {
+        tick,
+        changes,
+        snakes,
+        entities,
+    }
+    .write(&mut delta)
+    .unwrap();
+
+    sent.clear();
+    sent.extend_from_slice(tiles);
+    (keyframe, delta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_round_trip() {
+        let snakes = vec![SnakeInfo {
+            id: 3,
+            alive: true,
+            score: 7,
+            length: 8,
+        }];
+        let server = [
+            ServerMessage::Welcome {
+                version: VERSION,
+                snake: Some(3),
+                cols: 4,
+                rows: 2,
+                grid: Grid::Square,
+                tick_ms: 90,
+                input_delay: 2,
+                portals: vec![],
+            },
+            ServerMessage::Welcome {
+                version: VERSION,
+                snake: None,
+                cols: 4,
+                rows: 2,
+                grid: Grid::Hex,
+                tick_ms: 90,
+                input_delay: 2,
+                portals: vec![(0, 7), (3, 4)],
+            },
+            ServerMessage::Reject {
+                reason: "full".into(),
+            },
+            ServerMessage::Keyframe {
+                tick: 12,
+                tiles: vec![
+                    Tile::Empty,
+                    Tile::Food,
+                    Tile::SnakeBody,
+                    Tile::SnakeHead,
+                    Tile::Wall,
+                    Tile::Void,
+                    Tile::Portal,
+                    Tile::PowerUp,
+                ],
+                snakes: snakes.clone(),
+                entities: vec![],
+            },
+            ServerMessage::Delta {
+                tick: 13,
+                changes: vec![(0, Tile::SnakeHead), (7, Tile::Food)],
+                snakes,
+                entities: vec![
+                    (2, EntityKind::Block),
+                    (5, EntityKind::Ball),
+                    (6, EntityKind::Hunter),
+                ],
+            },
+            ServerMessage::Joined {
+                snake: 1,
+                name: "ann".into(),
+            },
+            ServerMessage::Left { snake: 1 },
+        ];
+        let mut bytes = vec![];
+        for message in &server {
+            message.write(&mut bytes).unwrap();
+        }
+        let mut input = bytes.as_slice();
+        for message in &server {
+            assert_eq!(&ServerMessage::read(&mut input, 8).unwrap(), message);
+        }
+        assert!(input.is_empty());
+
+        // More than a u8 count would hold
+        let crowded = ServerMessage::Delta {
+            tick: 14,
+            changes: vec![],
+            snakes: vec![],
+            entities: (0..300).map(|i| (i, EntityKind::Ball)).collect(),
+        };
+        let mut bytes = vec![];
+        crowded.write(&mut bytes).unwrap();
+        assert_eq!(ServerMessage::read(&mut &bytes[..], 8).unwrap(), crowded);
+
+        let client = [
+            ClientMessage::Hello {
+                version: VERSION,
+                role: Role::Spectator,
+                name: "bob".into(),
+            },
+            ClientMessage::Input {
+                tick: 40,
+                direction: Direction::Down,
+            },
+            ClientMessage::Leave,
+        ];
+        let mut bytes = vec![];
+        for message in &client {
+            message.write(&mut bytes).unwrap();
+        }
+        let mut input = bytes.as_slice();
+        for message in &client {
+            assert_eq!(&ClientMessage::read(&mut input).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn malformed_messages_are_errors() {
+        // Unknown kind, bad direction, steering to a stop, trailing bytes and
+        // a runaway length
+        for bytes in [
+            &[1, 0, 0, 0, 99][..],
+            &[10, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 9],
+            &[10, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            &[2, 0, 0, 0, 3, 0],
+            &[255, 255, 255, 255, 1],
+        ] {
+            assert!(ClientMessage::read(&mut &bytes[..]).is_err());
+        }
+    }
+}
@@ -0,0 +1,283 @@
+// CPU rasterizer, draws the same triangles as the GPU (`Game::render_data`
+// for the board, `Particles::instances` over it, a `TextBatch` for the HUD and
+// overlay) into RGBA frames. Needs no graphics stack at all, so it backs the
+// snapshot tests, thumbnails and anything else that shouldn't depend on there
+// being a GPU.
+
+use std::path::Path;
+
+use crate::scene::Scene;
+use crate::text::TextBatch;
+
+/// 8 bit RGBA pixels, top row first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn save_png(&self, path: &Path) -> Result<(), String> {
+        let error = |e: &dyn std::fmt::Display| format!("couldn't save {}: {e}", path.display());
+
+        let file = std::fs::File::create(path).map_err(|e| error(&e))?;
+        let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.pixels))
+            .map_err(|e| error(&e))
+    }
+}
+
+// Linear RGB, turned into sRGB at the end like the GPU's sRGB render targets
+struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 3]>,
+}
+
+// Twice the signed area of (a, b, c), positive when clockwise on screen
+fn orient([ax, ay]: [f32; 2], [bx, by]: [f32; 2], [cx, cy]: [f32; 2]) -> f32 {
+    (bx - ax) * (cy - ay) - (by - ay) * (cx - ax)
+}
+
+// Pixel centers exactly on a top or left edge are inside, ones on other
+// edges aren't, so triangles sharing an edge never both draw a pixel.
+// Same rule as D3D/Vulkan.
+fn is_top_left([ax, ay]: [f32; 2], [bx, by]: [f32; 2]) -> bool {
+    (ay == by && bx > ax) || by < ay
+}
+
+fn to_srgb(linear: f32) -> u8 {
+    let c = linear.clamp(0., 1.);
+    let srgb = if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    };
+    (srgb * 255. + 0.5) as u8
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32, clear: [f32; 3]) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![clear; (width * height) as usize],
+        }
+    }
+
+    /// Blends `shade(weights)` into every pixel whose center is inside the
+    /// triangle. Corners are in window pixels (y up, like the shaders get
+    /// them), `weights` are the barycentric weights of the corners.
+    fn fill_triangle(
+        &mut self,
+        corners: [[f32; 2]; 3],
+        mut shade: impl FnMut([f32; 3]) -> [f32; 4],
+    ) {
+        // To framebuffer coordinates, y down
+        let height = self.height as f32;
+        let mut v = corners.map(|[x, y]| [x, height - y]);
+        let mut order = [0, 1, 2];
+
+        let mut area = orient(v[0], v[1], v[2]);
+        if area == 0. {
+            return;
+        }
+        if area < 0. {
+            v.swap(1, 2);
+            order.swap(1, 2);
+            area = -area;
+        }
+
+        let min_x = v
+            .iter()
+            .map(|p| p[0])
+            .fold(f32::MAX, f32::min)
+            .floor()
+            .max(0.) as u32;
+        let min_y = v
+            .iter()
+            .map(|p| p[1])
+            .fold(f32::MAX, f32::min)
+            .floor()
+            .max(0.) as u32;
+        let max_x = (v.iter().map(|p| p[0]).fold(f32::MIN, f32::max).ceil() as u32).min(self.width);
+        let max_y =
+            (v.iter().map(|p| p[1]).fold(f32::MIN, f32::max).ceil() as u32).min(self.height);
+
+        // Edge opposite each corner
+        let edges = [(v[1], v[2]), (v[2], v[0]), (v[0], v[1])];
+        let top_left = edges.map(|(a, b)| is_top_left(a, b));
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let p = [x as f32 + 0.5, y as f32 + 0.5];
+                let w = edges.map(|(a, b)| orient(a, b, p));
+                let inside = w
+                    .iter()
+                    .zip(top_left)
+                    .all(|(&w, top_left)| w > 0. || (w == 0. && top_left));
+                if !inside {
+                    continue;
+                }
+
+                let mut weights = [0.; 3];
+                for (i, w) in w.iter().enumerate() {
+                    weights[order[i]] = w / area;
+                }
+                let [r, g, b, a] = shade(weights);
+                let dst = &mut self.pixels[(y * self.width + x) as usize];
+                *dst = [
+                    r * a + dst[0] * (1. - a),
+                    g * a + dst[1] * (1. - a),
+                    b * a + dst[2] * (1. - a),
+                ];
+            }
+        }
+    }
+
+    fn into_image(self) -> Image {
+        let pixels = self
+            .pixels
+            .iter()
+            .flat_map(|[r, g, b]| [to_srgb(*r), to_srgb(*g), to_srgb(*b), 255])
+            .collect();
+        Image {
+            width: self.width,
+            height: self.height,
+            pixels,
+        }
+    }
+}
+
+/// Draws `scene` the way the GPU renderer would
+pub fn render(scene: &Scene) -> Image {
+    let (width, height) = scene.size();
+    let mut canvas = Canvas::new(width, height, scene.theme.background);
+
+    // Board, colors picked by `coloridx` like in shader.wgsl, and moved by
+    // the screen shake
+    let colors = scene.theme.board_colors();
+    let color = |coloridx: u32| colors.get(coloridx as usize).copied().unwrap_or_default();
+    let [dx, dy] = scene.particles.map_or([0., 0.], |p| p.shake());
+    let shaken = |[x, y]: [f32; 2]| [x + dx, y + dy];
+    for triangle in scene.game.render_data().chunks_exact(3) {
+        let [r, g, b] = color(triangle[0].coloridx);
+        let corners = [0, 1, 2].map(|i| shaken(triangle[i].position));
+        canvas.fill_triangle(corners, |_| [r, g, b, 1.]);
+    }
+
+    // Particles, like particles.wgsl
+    for particle in scene.particles.map(|p| p.instances()).unwrap_or_default() {
+        let [r, g, b] = color(particle.coloridx);
+        for triangle in particle.corners().chunks_exact(3) {
+            let corners = [0, 1, 2].map(|i| shaken(triangle[i]));
+            canvas.fill_triangle(corners, |_| [r, g, b, particle.alpha]);
+        }
+    }
+
+    // HUD and overlay, sampling the glyph atlas like text.wgsl does
+    let mut batch = TextBatch::new();
+    scene.ui(&mut batch, width as f32, height as f32);
+    let atlas = batch.atlas();
+    for triangle in batch.vertices().chunks_exact(3) {
+        let corners = [0, 1, 2].map(|i| triangle[i].position);
+        canvas.fill_triangle(corners, |weights| {
+            let [mut u, mut v] = [0., 0.];
+            for (vertex, weight) in triangle.iter().zip(weights) {
+                u += vertex.uv[0] * weight;
+                v += vertex.uv[1] * weight;
+            }
+            // Nearest sampling, clamped to the edge
+            let tx = ((u * atlas.width as f32) as u32).min(atlas.width - 1);
+            let ty = ((v * atlas.height as f32) as u32).min(atlas.height - 1);
+            let coverage = atlas.pixels[(ty * atlas.width + tx) as usize] as f32 / 255.;
+
+            let [r, g, b, a] = triangle[0].color;
+            [r, g, b, a * coverage]
+        });
+    }
+
+    canvas.into_image()
+}
+
+/// `image` shrunk to fit in a `max_side` square, averaging the pixels that
+/// end up in the same spot. Images that already fit are left as they are.
+pub fn thumbnail(image: &Image, max_side: u32) -> Image {
+    let factor = image.width.max(image.height).div_ceil(max_side.max(1));
+    if factor <= 1 {
+        return image.clone();
+    }
+
+    let width = image.width.div_ceil(factor);
+    let height = image.height.div_ceil(factor);
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for ty in 0..height {
+        for tx in 0..width {
+            let mut sum = [0u32; 4];
+            let mut count = 0;
+            for y in ty * factor..((ty + 1) * factor).min(image.height) {
+                for x in tx * factor..((tx + 1) * factor).min(image.width) {
+                    let i = ((y * image.width + x) * 4) as usize;
+                    for (s, p) in sum.iter_mut().zip(&image.pixels[i..i + 4]) {
+                        *s += *p as u32;
+                    }
+                    count += 1;
+                }
+            }
+            pixels.extend(sum.map(|s| (s / count) as u8));
+        }
+    }
+
+    Image {
+        width,
+        height,
+        pixels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden;
+
+    #[test]
+    fn cpu_frames_match_golden_images() {
+        for name in golden::CASES {
+            let image = golden::with_scene(name, render);
+            if !golden::bless(name, &image) {
+                golden::compare(name, &image);
+            }
+        }
+    }
+
+    #[test]
+    fn shared_edges_are_drawn_once() {
+        // Two half transparent triangles making up a square, the diagonal
+        // would come out darker if it got blended twice
+        let mut canvas = Canvas::new(4, 4, [0.; 3]);
+        let square = [[0., 0.], [4., 0.], [0., 4.], [4., 0.], [4., 4.], [0., 4.]];
+        for triangle in square.chunks_exact(3) {
+            let corners = [triangle[0], triangle[1], triangle[2]];
+            canvas.fill_triangle(corners, |_| [1., 1., 1., 0.5]);
+        }
+        assert!(canvas.pixels.iter().all(|p| *p == [0.5; 3]));
+    }
+
+    #[test]
+    fn thumbnails_average_and_keep_the_aspect_ratio() {
+        let image = Image {
+            width: 4,
+            height: 2,
+            pixels: [[0, 0, 0, 255], [255, 255, 255, 255]].repeat(4).concat(),
+        };
+        let thumb = thumbnail(&image, 2);
+        assert_eq!((thumb.width, thumb.height), (2, 1));
+        assert_eq!(&thumb.pixels[..4], &[127, 127, 127, 255]);
+        assert_eq!(thumbnail(&image, 10), image);
+    }
+}
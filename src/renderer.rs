@@ -0,0 +1,378 @@
+// Draws a frame (board, particles, HUD and overlay) into any texture view, so
+// the window, screenshots and golden tests all go through the same pipelines.
+
+use wgpu::util::DeviceExt;
+
+use crate::offscreen;
+use crate::particles::{self, ParticleInstance};
+use crate::raster::Image;
+use crate::scene::Scene;
+use crate::shader;
+use crate::text;
+use crate::theme::Theme;
+
+struct Uniforms {
+    // Window size and board offset, the `View` in shader.wgsl
+    view_buffer: wgpu::Buffer,
+    colors_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+// Board colors, each padded to a vec4 to match the uniform layout in shader.wgsl
+fn color_uniform_data(theme: &Theme) -> Vec<f32> {
+    theme
+        .board_colors()
+        .iter()
+        .flat_map(|[r, g, b]| [*r, *g, *b, 0.])
+        .collect()
+}
+
+impl Uniforms {
+    pub fn new(device: &wgpu::Device, win_width: f32, win_height: f32, theme: &Theme) -> Self {
+        let view_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("view uniforms buffer"),
+            contents: bytemuck::cast_slice(&[win_width, win_height, 0., 0.]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let colors_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("uniforms buffer"),
+            contents: bytemuck::cast_slice(&color_uniform_data(theme)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("uniforms_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("uniforms bind group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: colors_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: view_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        Self {
+            view_buffer,
+            colors_buffer,
+            bind_group,
+            bind_group_layout,
+        }
+    }
+
+    pub fn set_theme(&self, queue: &wgpu::Queue, theme: &Theme) {
+        queue.write_buffer(
+            &self.colors_buffer,
+            0,
+            bytemuck::cast_slice(&color_uniform_data(theme)),
+        );
+    }
+
+    pub fn set_view(&self, queue: &wgpu::Queue, win_width: f32, win_height: f32, offset: [f32; 2]) {
+        let [x, y] = offset;
+        queue.write_buffer(
+            &self.view_buffer,
+            0,
+            bytemuck::cast_slice(&[win_width, win_height, x, y]),
+        );
+    }
+}
+
+pub struct Renderer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+
+    render_pipeline: wgpu::RenderPipeline,
+    text_renderer: text::TextRenderer,
+    // HUD and overlay quads, kept around to reuse the allocation
+    ui: text::TextBatch,
+
+    vertex_buffer: wgpu::Buffer,
+    num_vertices: u32,
+
+    // Instanced quads on top of the board, sharing its uniforms
+    particle_pipeline: wgpu::RenderPipeline,
+    particle_buffer: wgpu::Buffer,
+    num_particles: u32,
+
+    uniforms: Uniforms,
+    // Theme the color uniforms currently hold
+    theme: Theme,
+}
+
+impl Renderer {
+    /// Renders `width` x `height` frames into textures of `format`
+    pub fn new(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let theme = Theme::default();
+        let uniforms = Uniforms::new(&device, width as f32, height as f32, &theme);
+
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("vertex buffer"),
+            mapped_at_creation: false,
+            size: 100_000_000, // TODO(smolck): yeah probably no lol
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Render Pipeline Layout"),
+                bind_group_layouts: &[&uniforms.bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        // TODO(smolck)
+        let num_vertices = 6;
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("render pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[shader::Vertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,                         // no multisampling
+                mask: !0,                         // no multisampling also tf does the bang do
+                alpha_to_coverage_enabled: false, // apparently antialiasing something something
+            },
+            multiview: None, // can render to array textures whatever that means but NO
+                             // also man is wgpu explicit lol
+        });
+
+        let particle_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("particle buffer"),
+            mapped_at_creation: false,
+            size: (particles::MAX_PARTICLES * std::mem::size_of::<ParticleInstance>())
+                as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let particle_shader = device.create_shader_module(wgpu::include_wgsl!("particles.wgsl"));
+        let particle_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("particle pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &particle_shader,
+                entry_point: "vs_main",
+                buffers: &[ParticleInstance::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &particle_shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        let text_renderer =
+            text::TextRenderer::new(&device, &queue, format, width as f32, height as f32);
+
+        Self {
+            device,
+            queue,
+            format,
+            width,
+            height,
+
+            render_pipeline,
+            text_renderer,
+            ui: text::TextBatch::new(),
+            vertex_buffer,
+            num_vertices,
+
+            particle_pipeline,
+            particle_buffer,
+            num_particles: 0,
+
+            uniforms,
+            theme,
+        }
+    }
+
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+
+        let (w, h) = (width as f32, height as f32);
+        self.uniforms.set_view(&self.queue, w, h, [0., 0.]);
+        self.text_renderer.resize(&self.queue, w, h);
+    }
+
+    /// Draws `scene` into `view`, which has to be the size and format the
+    /// renderer was made for
+    pub fn draw(&mut self, view: &wgpu::TextureView, scene: &Scene) {
+        if self.theme != *scene.theme {
+            self.theme = scene.theme.clone();
+            self.uniforms.set_theme(&self.queue, &self.theme);
+        }
+
+        let render_data = scene.game.render_data();
+        self.num_vertices = render_data.len() as u32;
+        self.queue
+            .write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&render_data));
+
+        let (instances, shake) = match scene.particles {
+            Some(particles) => (particles.instances(), particles.shake()),
+            None => (vec![], [0., 0.]),
+        };
+        self.num_particles = instances.len() as u32;
+        self.queue
+            .write_buffer(&self.particle_buffer, 0, bytemuck::cast_slice(&instances));
+        let (w, h) = (self.width as f32, self.height as f32);
+        self.uniforms.set_view(&self.queue, w, h, shake);
+
+        self.ui.clear();
+        scene.ui(&mut self.ui, self.width as f32, self.height as f32);
+        self.text_renderer.prepare(&self.queue, &self.ui);
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            r: self.theme.background[0] as f64,
+                            g: self.theme.background[1] as f64,
+                            b: self.theme.background[2] as f64,
+                            a: 1.0,
+                        }),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_pass.set_pipeline(&self.render_pipeline);
+            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            render_pass.set_bind_group(0, &self.uniforms.bind_group, &[]);
+            render_pass.draw(0..self.num_vertices, 0..1);
+
+            if self.num_particles > 0 {
+                render_pass.set_pipeline(&self.particle_pipeline);
+                render_pass.set_vertex_buffer(0, self.particle_buffer.slice(..));
+                render_pass.draw(0..6, 0..self.num_particles);
+            }
+
+            self.text_renderer.draw(&mut render_pass);
+        }
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    /// Draws `scene` into a texture of its own and reads it back
+    pub fn capture(&mut self, scene: &Scene) -> Result<Image, String> {
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("capture texture"),
+            size: wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        self.draw(&view, scene);
+        offscreen::read_texture(&self.device, &self.queue, &texture)
+    }
+}
@@ -0,0 +1,388 @@
+// Replays: the seed and options a game started with plus the moves its
+// direction changed on. Games are deterministic given those, so playing the
+// inputs back through `Game::update` reproduces every frame.
+//
+// Saved as TOML:
+//
+//     version = 7
+//     seed = 42
+//     cols = 40
+//     rows = 30
+//     tile_size = 20.0
+//     tick_ms = 100
+//     wrap = false
+//     level = "CLASSIC"
+//     grid = "hex"          # only for hex boards
+//     power_ups = true      # only if they spawn
+//     mode = "puzzle"       # only if it isn't classic
+//     moves = 57
+//
+//     [[inputs]]
+//     move = 0
+//     direction = "right"
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::event::Event;
+use crate::game::{Direction, Game, GameOptions, TailRule};
+use crate::level::Level;
+use crate::mode::GameMode;
+use crate::settings::Settings;
+use crate::topology::Grid;
+
+// Bumped whenever the same seed would play out differently, 2 changed how
+// food is placed, 3 let heads follow their own tail, 4 timed time attacks by
+// game time, 5 gave puzzles the moves to get around walls, 6 brought back
+// the speed power-up and 7 stopped growing snakes from counting a tile they
+// don't have yet
+const VERSION: u32 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Input {
+    #[serde(rename = "move")]
+    pub at: u64,
+    pub direction: Direction,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub cols: usize,
+    pub rows: usize,
+    pub tile_size: f32,
+    pub tick_ms: u64,
+    pub wrap: bool,
+    #[serde(default, skip_serializing_if = "is_square")]
+    pub grid: Grid,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub power_ups: bool,
+    #[serde(default, skip_serializing_if = "is_classic")]
+    pub mode: GameMode,
+    #[serde(default, skip_serializing_if = "is_follow")]
+    pub tail: TailRule,
+    // Name of a built-in level, or of the level stored in `level_map`
+    pub level: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level_map: Option<String>,
+    // Date of the daily challenge it was played as, see `daily`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily: Option<String>,
+    pub moves: u64,
+    pub inputs: Vec<Input>,
+}
+
+fn is_square(grid: &Grid) -> bool {
+    *grid == Grid::Square
+}
+
+fn is_classic(mode: &GameMode) -> bool {
+    *mode == GameMode::Classic
+}
+
+fn is_follow(tail: &TailRule) -> bool {
+    *tail == TailRule::Follow
+}
+
+impl Replay {
+    /// Replay of `game` so far, played at `tick_ms` per move
+    pub fn record(game: &Game, tick_ms: u64) -> Self {
+        Self {
+            version: VERSION,
+            seed: game.seed(),
+            cols: game.cols(),
+            rows: game.rows(),
+            tile_size: game.tile_size(),
+            tick_ms,
+            wrap: game.wraps(),
+            grid: game.grid(),
+            power_ups: game.power_ups(),
+            mode: game.mode(),
+            tail: game.tail_rule(),
+            level: game.level().name.clone(),
+            level_map: game.level().to_text(),
+            daily: None,
+            moves: game.moves(),
+            inputs: game
+                .inputs()
+                .iter()
+                .map(|&(at, direction)| Input { at, direction })
+                .collect(),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let replay: Self = toml::from_str(text).map_err(|e| e.to_string())?;
+        if replay.version != VERSION {
+            return Err(format!(
+                "replay version {} isn't supported, expected {VERSION}",
+                replay.version
+            ));
+        }
+        replay.settings()?.validate()?;
+        Ok(replay)
+    }
+
+    /// What the game was played with, to be checked like any other settings
+    fn settings(&self) -> Result<Settings, String> {
+        Ok(Settings {
+            cols: self.cols,
+            rows: self.rows,
+            tile_size: self.tile_size,
+            tick_ms: self.tick_ms,
+            seed: Some(self.seed),
+            wrap: self.wrap,
+            grid: self.grid,
+            power_ups: self.power_ups,
+            mode: self.mode,
+            tail: self.tail,
+            level: self.level()?,
+            headless: true,
+            ..Settings::default()
+        })
+    }
+
+    fn level(&self) -> Result<Level, String> {
+        match &self.level_map {
+            Some(map) => Level::parse(&self.level, map),
+            None => Level::by_name(&self.level)
+                .ok_or_else(|| format!("no built-in level called '{}'", self.level)),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("couldn't read replay {}: {e}", path.display()))?;
+        Self::parse(&text).map_err(|e| format!("invalid replay {}: {e}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = toml::to_string(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, text)
+            .map_err(|e| format!("couldn't save replay to {}: {e}", path.display()))
+    }
+
+    /// The game as it was before its first move, with tiles `tile_size`
+    /// pixels wide
+    pub fn start(&self, tile_size: f32) -> Result<Game, String> {
+        let options = GameOptions {
+            wrap: self.wrap,
+            level: self.level()?,
+            seed: Some(self.seed),
+            grid: self.grid,
+            power_ups: self.power_ups,
+            mode: self.mode,
+            tail: self.tail,
+        };
+        Ok(Game::with_options(self.cols, self.rows, tile_size, options))
+    }
+
+    /// Plays the replay back one move at a time
+    pub fn play(&self, tile_size: f32) -> Result<Playback<'_>, String> {
+        Ok(Playback {
+            replay: self,
+            game: self.start(tile_size)?,
+            next_input: 0,
+            alive: true,
+        })
+    }
+}
+
+pub struct Playback<'a> {
+    replay: &'a Replay,
+    game: Game,
+    next_input: usize,
+    alive: bool,
+}
+
+impl Playback<'_> {
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// What happened in the game since the last call
+    pub fn drain_events(&mut self) -> impl Iterator<Item = Event> + '_ {
+        self.game.drain_events()
+    }
+
+    /// Makes the next move, false once the replay is over
+    pub fn step(&mut self) -> bool {
+        let moves = self.game.moves();
+        if !self.alive || moves >= self.replay.moves {
+            return false;
+        }
+
+        while let Some(input) = self.replay.inputs.get(self.next_input) {
+            if input.at > moves {
+                break;
+            }
+            self.game.change_direction(input.direction);
+            self.next_input += 1;
+        }
+
+        self.alive = self.game.update();
+        // A replay that never starts moving would otherwise never end
+        self.game.moves() > moves
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai;
+    use crate::game::DeathCause;
+
+    fn played_game(level: Level, grid: Grid, power_ups: bool, mode: GameMode) -> Game {
+        let options = GameOptions {
+            wrap: false,
+            level,
+            seed: Some(9),
+            grid,
+            power_ups,
+            mode,
+            ..Default::default()
+        };
+        let mut game = Game::with_options(12, 10, 8., options);
+        // Ticks before the first move aren't in the replay
+        for _ in 0..5 {
+            game.update();
+        }
+        for _ in 0..200 {
+            game.change_direction(ai::greedy(&game));
+            if !game.update() {
+                break;
+            }
+        }
+        game
+    }
+
+    #[test]
+    fn playback_reproduces_the_game() {
+        let level = Level::parse("maze", "....\n.##.\n").unwrap();
+        for grid in [Grid::Square, Grid::Hex] {
+            for power_ups in [false, true] {
+                let game = played_game(level.clone(), grid, power_ups, GameMode::Classic);
+                assert_replays(&game);
+            }
+        }
+        for mode in GameMode::ALL {
+            let game = played_game(level.clone(), Grid::Square, false, mode);
+            assert_replays(&game);
+        }
+    }
+
+    fn assert_replays(game: &Game) {
+        let replay = Replay::parse(&toml::to_string(&Replay::record(game, 80)).unwrap()).unwrap();
+        assert_eq!(replay.level_map.as_deref(), Some("....\n.##.\n"));
+        assert_eq!(replay.grid, game.grid());
+        assert_eq!(replay.power_ups, game.power_ups());
+        assert_eq!(replay.mode, game.mode());
+
+        let mut playback = replay.play(8.).unwrap();
+        while playback.step() {}
+        let played = playback.game();
+        assert_eq!(played.moves(), game.moves());
+        assert_eq!(played.head(), game.head());
+        assert_eq!(played.food(), game.food());
+        assert_eq!(played.score(), game.score());
+        assert_eq!(played.power_up(), game.power_up());
+        assert_eq!(played.effects(), game.effects());
+        assert_eq!(played.ending(), game.ending());
+        assert_eq!(
+            bytemuck::cast_slice::<_, u8>(&played.render_data()),
+            bytemuck::cast_slice::<_, u8>(&game.render_data())
+        );
+    }
+
+    #[test]
+    fn rejects_other_versions_and_unknown_levels() {
+        let mut replay = Replay::record(
+            &played_game(Level::default(), Grid::Square, false, GameMode::Classic),
+            100,
+        );
+        replay.version = VERSION + 1;
+        assert!(Replay::parse(&toml::to_string(&replay).unwrap()).is_err());
+
+        replay.version = VERSION;
+        replay.level = "NOPE".into();
+        assert!(replay.start(8.).is_err());
+    }
+
+    #[test]
+    fn rejects_replays_that_cant_be_played() {
+        let replay = Replay::record(
+            &played_game(Level::default(), Grid::Square, false, GameMode::Classic),
+            100,
+        );
+        let walled_in = Replay {
+            cols: 2,
+            rows: 1,
+            level_map: Some("##\n".into()),
+            ..replay.clone()
+        };
+        let huge = Replay {
+            cols: 1 << 40,
+            rows: 2,
+            ..replay.clone()
+        };
+        let flat = Replay {
+            tile_size: f32::NAN,
+            ..replay.clone()
+        };
+        let frozen = Replay {
+            tick_ms: 0,
+            ..replay
+        };
+        for bad in [walled_in, huge, flat, frozen] {
+            let text = toml::to_string(&bad).unwrap();
+            assert!(Replay::parse(&text).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn replays_play_out_by_their_tail_rule() {
+        let options = GameOptions {
+            seed: Some(1),
+            tail: TailRule::Follow,
+            ..Default::default()
+        };
+        let mut game = Game::with_options(12, 10, 8., options);
+        // Off after the food, until the head can go where the tail is leaving
+        let mut ate = false;
+        let chased = loop {
+            assert!(game.moves() < 500, "the head never got next to the tail");
+            let tiles: Vec<_> = game.snake_tiles(0).unwrap().collect();
+            let tail = tiles[tiles.len() - 1];
+            let mut directions = game.topology().directions().iter().copied();
+            let chase = if tiles.len() >= 4 && !ate {
+                directions.find(|&d| game.step(tiles[0], d) == Some(tail))
+            } else {
+                None
+            };
+            game.change_direction(chase.unwrap_or_else(|| ai::greedy(&game)));
+            assert!(game.update());
+            ate = game
+                .drain_events()
+                .any(|event| matches!(event, Event::FoodEaten { .. }));
+            if chase.is_some() {
+                break game.moves();
+            }
+        };
+
+        let mut replay = Replay::record(&game, 100);
+        let mut playback = replay.play(8.).unwrap();
+        while playback.step() {}
+        assert_eq!(playback.game().ending(), None);
+
+        // A strict tail is in the way, the same move kills the snake
+        replay.tail = TailRule::Strict;
+        let mut playback = replay.play(8.).unwrap();
+        while playback.step() {}
+        assert_eq!(playback.game().moves(), chased);
+        assert_eq!(playback.game().death_cause(0), Some(DeathCause::Itself));
+    }
+}
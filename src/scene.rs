@@ -0,0 +1,180 @@
+// What gets drawn for a frame and where the HUD and overlay text goes, shared
+// by the GPU renderer and the CPU rasterizer so both lay frames out the same.
+
+use crate::app::{App, Overlay};
+use crate::event::Event;
+use crate::font;
+use crate::game::Game;
+use crate::particles::Particles;
+use crate::text::TextBatch;
+use crate::theme::Theme;
+
+use winit::keyboard::KeyCode;
+
+// Height in pixels of the HUD bar above the board
+pub const HUD_HEIGHT: f32 = 40.;
+const HUD_TEXT_SCALE: f32 = 3.;
+
+/// Everything that ends up on screen for one frame
+pub struct Scene<'a> {
+    pub game: &'a Game,
+    pub theme: &'a Theme,
+    // Drawn in a bar of HUD_HEIGHT above the board, None for just the board
+    pub hud: Option<String>,
+    pub overlay: Option<Overlay>,
+    // Drawn over the board and shaking it, the frontend keeps these
+    pub particles: Option<&'a Particles>,
+}
+
+impl<'a> Scene<'a> {
+    pub fn from_app(app: &'a App) -> Self {
+        Self {
+            game: app.game(),
+            theme: app.theme(),
+            hud: Some(app.hud_text()),
+            overlay: app.overlay(),
+            particles: None,
+        }
+    }
+
+    /// Pixel size of the frame
+    pub fn size(&self) -> (u32, u32) {
+        let hud_height = if self.hud.is_some() { HUD_HEIGHT } else { 0. };
+        let (width, height) = self.game.board_size();
+        (width as u32, (height + hud_height) as u32)
+    }
+}
+
+/// What a frontend shows and sends keys to: the local `App`, or a network
+/// game (`client::Session`)
+pub trait View {
+    fn on_key(&mut self, key: KeyCode);
+    /// Called every `tick_ms`
+    fn tick(&mut self);
+    fn tick_ms(&self) -> u64;
+    fn scene(&self) -> Scene<'_>;
+    fn should_quit(&self) -> bool;
+    /// True once after the screenshot key was pressed
+    fn take_screenshot_request(&mut self) -> bool;
+    /// What happened in the game since the last call, for sound and effects
+    fn take_events(&mut self) -> Vec<Event> {
+        vec![]
+    }
+}
+
+impl View for App {
+    fn on_key(&mut self, key: KeyCode) {
+        App::on_key(self, key)
+    }
+
+    fn tick(&mut self) {
+        App::tick(self)
+    }
+
+    fn tick_ms(&self) -> u64 {
+        App::tick_ms(self)
+    }
+
+    fn scene(&self) -> Scene<'_> {
+        Scene::from_app(self)
+    }
+
+    fn should_quit(&self) -> bool {
+        App::should_quit(self)
+    }
+
+    fn take_screenshot_request(&mut self) -> bool {
+        App::take_screenshot_request(self)
+    }
+
+    fn take_events(&mut self) -> Vec<Event> {
+        App::take_events(self)
+    }
+}
+
+/// Status line for the HUD, `tick_ms` being the time between moves. Then
+/// what's left to go in the game's mode, and power-up effects with the moves
+/// they have left.
+pub fn hud_text(game: &Game, tick_ms: u64) -> String {
+    let mut text = format!(
+        "{}  SCORE {}  LENGTH {}  SPEED {}/S",
+        game.level_name(),
+        game.score(),
+        game.snake_len(),
+        1000 / tick_ms.max(1),
+    );
+    if let Some(status) = game.rules().status(game, tick_ms) {
+        text += &format!("  {status}");
+    }
+    for active in game.effects() {
+        text += &format!("  {} {}", active.effect.power_up().name, active.ticks_left);
+    }
+    text
+}
+
+fn rgba([r, g, b]: [f32; 3]) -> [f32; 4] {
+    [r, g, b, 1.]
+}
+
+impl Scene<'_> {
+    /// Queues the HUD and overlay for a `width` x `height` frame. The board
+    /// itself comes from `Game::render_data`.
+    pub fn ui(&self, batch: &mut TextBatch, width: f32, height: f32) {
+        let mut board_height = height;
+        if let Some(hud) = &self.hud {
+            self.hud(batch, hud, width, height);
+            board_height -= HUD_HEIGHT;
+        }
+        if let Some(overlay) = &self.overlay {
+            self.overlay(batch, &overlay.lines, width, board_height);
+        }
+    }
+
+    fn hud(&self, text: &mut TextBatch, hud: &str, win_width: f32, win_height: f32) {
+        let [r, g, b] = self.theme.hud_background;
+        let text_color = rgba(self.theme.text);
+
+        text.rect(
+            0.,
+            win_height - HUD_HEIGHT,
+            win_width,
+            HUD_HEIGHT,
+            [r, g, b, 1.],
+        );
+
+        let text_height = font::GLYPH_HEIGHT as f32 * HUD_TEXT_SCALE;
+        text.text(
+            hud,
+            10.,
+            win_height - (HUD_HEIGHT + text_height) / 2.,
+            HUD_TEXT_SCALE,
+            text_color,
+        );
+    }
+
+    /// Centered message over the board, first line is drawn larger
+    fn overlay(&self, text: &mut TextBatch, lines: &[String], win_width: f32, board_height: f32) {
+        let [r, g, b] = self.theme.background;
+        let text_color = rgba(self.theme.text);
+
+        text.rect(0., 0., win_width, board_height, [r, g, b, 0.75]);
+
+        // Shrink the text if the lines wouldn't fit on the board (title counts double)
+        let widest = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let fit_width = win_width / (widest.max(1) * font::ADVANCE as usize) as f32;
+        let fit_height = board_height / ((lines.len() + 1) as f32 * font::GLYPH_HEIGHT as f32 * 2.);
+        let scale = HUD_TEXT_SCALE
+            .min(fit_width)
+            .min(fit_height)
+            .floor()
+            .max(1.);
+
+        let line_height = font::GLYPH_HEIGHT as f32 * scale * 2.;
+        let mut y = board_height / 2. + line_height * (lines.len() as f32 - 1.) / 2.;
+        for (i, line) in lines.iter().enumerate() {
+            let line_scale = if i == 0 { scale * 2. } else { scale };
+            text.text_centered(line, win_width / 2., y, line_scale, text_color);
+            y -= line_height;
+        }
+    }
+}
@@ -0,0 +1,514 @@
+// `snake server`: runs the only real multi-snake `Game` at a fixed tick.
+// Clients just send inputs and get the board back (see protocol.rs), so
+// nobody can cheat by changing their copy.
+//
+// Every connection gets a thread that reads its messages into a channel, the
+// tick loop drains the channel, moves the snakes and queues the changes for
+// every connection, each written by an `Outbox` thread of its own so a slow
+// client can't hold the tick up. Dead snakes come back after RESPAWN_TICKS.
+
+use std::collections::BTreeMap;
+use std::io::{self, ErrorKind};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::game::{Direction, Game, GameOptions, SnakeId, Tile};
+use crate::protocol::{self, ClientMessage, Role, ServerMessage, VERSION};
+use crate::transport::{Listener, Outbox, Stream};
+
+// Every client gets a full board this often, besides when it joins
+const KEYFRAME_EVERY: u64 = 50;
+const RESPAWN_TICKS: u64 = 20;
+// A client with this many messages it hasn't taken yet has fallen behind and
+// is dropped
+const MAX_QUEUED: usize = 64;
+// A write that doesn't go through in this long fails, dropping the client
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    pub cols: usize,
+    pub rows: usize,
+    pub tick_ms: u64,
+    pub game: GameOptions,
+    pub max_players: usize,
+    // Ticks between a client sending an input and it being applied
+    pub input_delay: u64,
+}
+
+type ConnId = u64;
+
+enum Event {
+    Hello {
+        conn: ConnId,
+        stream: Stream,
+        version: u16,
+        role: Role,
+        name: String,
+    },
+    Message(ConnId, ClientMessage),
+    Closed(ConnId),
+}
+
+struct Conn {
+    // Dropping it closes the connection, reader thread and all, once the
+    // client has everything sent so far
+    outbox: Outbox,
+    name: String,
+    // None for spectators
+    snake: Option<SnakeId>,
+    needs_keyframe: bool,
+}
+
+pub struct Server {
+    listener: Listener,
+    options: ServerOptions,
+    next_conn: ConnId,
+    events: Receiver<Event>,
+    sender: Sender<Event>,
+    conns: BTreeMap<ConnId, Conn>,
+
+    game: Game,
+    tick: u64,
+    // Board as clients last saw it
+    sent_tiles: Vec<Tile>,
+    // (tick, snake, direction) in the order they arrived
+    inputs: Vec<(u64, SnakeId, Direction)>,
+    respawns: Vec<(u64, SnakeId)>,
+}
+
+// Reads `stream` until it closes, Hello first
+fn read_messages(conn: ConnId, mut stream: Stream, events: Sender<Event>) {
+    let hello = match ClientMessage::read(&mut stream) {
+        Ok(ClientMessage::Hello {
+            version,
+            role,
+            name,
+        }) => stream.try_clone().map(|writer| Event::Hello {
+            conn,
+            stream: writer,
+            version,
+            role,
+            name,
+        }),
+        Ok(message) => {
+            log::warn!("connection {conn} started with {message:?} instead of hello");
+            return;
+        }
+        Err(e) => Err(e),
+    };
+    match hello {
+        Ok(hello) => {
+            if events.send(hello).is_err() {
+                return;
+            }
+        }
+        Err(e) => {
+            log::warn!("connection {conn} failed: {e}");
+            return;
+        }
+    }
+
+    loop {
+        let event = match ClientMessage::read(&mut stream) {
+            Ok(message) => Event::Message(conn, message),
+            Err(e) => {
+                if e.kind() != ErrorKind::UnexpectedEof {
+                    log::warn!("connection {conn} failed: {e}");
+                }
+                Event::Closed(conn)
+            }
+        };
+        let closed = matches!(event, Event::Closed(_));
+        if events.send(event).is_err() || closed {
+            return;
+        }
+    }
+}
+
+impl Server {
+    /// Listens on `addr`, `host:port` or `unix:PATH`
+    pub fn bind(addr: &str, options: ServerOptions) -> io::Result<Self> {
+        let listener = Listener::bind(addr)?;
+
+        let mut game = Game::with_options(
+            options.cols,
+            options.rows,
+            1.,
+            GameOptions {
+                // Everyone gets the same food, whatever seed the options have
+                seed: Some(options.game.seed.unwrap_or_else(rand::random)),
+                ..options.game.clone()
+            },
+        );
+        // Snakes come with players
+        game.remove_snake(0);
+        let (sender, events) = mpsc::channel();
+
+        Ok(Self {
+            listener,
+            next_conn: 0,
+            events,
+            sender,
+            conns: BTreeMap::new(),
+            sent_tiles: game.tiles().to_vec(),
+            game,
+            tick: 0,
+            inputs: vec![],
+            respawns: vec![],
+            options,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<String> {
+        self.listener.local_addr()
+    }
+
+    /// Ticks forever at the configured rate
+    pub fn run(&mut self) -> io::Result<()> {
+        log::info!("serving on {}", self.local_addr()?);
+        let tick = Duration::from_millis(self.options.tick_ms);
+        let mut next_tick = Instant::now() + tick;
+        loop {
+            self.tick()?;
+            std::thread::sleep(next_tick.saturating_duration_since(Instant::now()));
+            next_tick += tick;
+        }
+    }
+
+    /// Handles everything clients sent since the last tick, moves the snakes
+    /// and sends out the result
+    pub fn tick(&mut self) -> io::Result<()> {
+        self.accept()?;
+        while let Ok(event) = self.events.try_recv() {
+            self.handle(event);
+        }
+
+        let tick = self.tick;
+        let (due, later) = self.inputs.drain(..).partition(|(at, ..)| *at <= tick);
+        self.inputs = later;
+        for (_, snake, direction) in due {
+            self.game.steer(snake, direction);
+        }
+
+        for snake in self.game.update_snakes() {
+            self.game.remove_snake(snake);
+            self.respawns.push((tick + RESPAWN_TICKS, snake));
+        }
+        let (due, later) = self.respawns.drain(..).partition(|(at, _)| *at <= tick);
+        self.respawns = later;
+        for (_, snake) in due {
+            if self.conns.values().any(|c| c.snake == Some(snake)) {
+                self.game.spawn_snake(snake);
+            }
+        }
+
+        self.tick += 1;
+        self.broadcast();
+        Ok(())
+    }
+
+    fn accept(&mut self) -> io::Result<()> {
+        while let Some(stream) = self.listener.accept()? {
+            let (conn, events) = (self.next_conn, self.sender.clone());
+            self.next_conn += 1;
+            log::info!("connection {conn}");
+            if let Err(e) = stream.set_write_timeout(Some(WRITE_TIMEOUT)) {
+                log::warn!("dropping connection {conn}: {e}");
+                continue;
+            }
+            std::thread::spawn(move || read_messages(conn, stream, events));
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Hello {
+                conn,
+                stream,
+                version,
+                role,
+                name,
+            } => self.join(conn, stream, version, role, name),
+            Event::Message(conn, ClientMessage::Input { tick, direction }) => {
+                let Some(snake) = self.conns.get(&conn).and_then(|c| c.snake) else {
+                    return;
+                };
+                // Late inputs go in on the next tick, ones too far ahead wait
+                // no longer than the delay
+                let at = tick.clamp(self.tick, self.tick + self.options.input_delay);
+                self.inputs.push((at, snake, direction));
+            }
+            Event::Message(conn, ClientMessage::Leave) | Event::Closed(conn) => self.leave(conn),
+            Event::Message(conn, ClientMessage::Hello { .. }) => {
+                log::warn!("connection {conn} said hello twice");
+            }
+        }
+    }
+
+    fn join(
+        &mut self,
+        conn: ConnId,
+        stream: Stream,
+        version: u16,
+        role: Role,
+        name: String,
+    ) {
+        let outbox = match Outbox::new(stream, MAX_QUEUED) {
+            Ok(outbox) => outbox,
+            Err(e) => return log::warn!("connection {conn} failed: {e}"),
+        };
+        // The connection closes once the outbox is dropped and the reason
+        // has gone out
+        let reject = |reason: String| {
+            log::info!("rejected connection {conn}: {reason}");
+            outbox.send(encode(&ServerMessage::Reject { reason }));
+        };
+        if version != VERSION {
+            return reject(format!(
+                "protocol version {version} isn't supported, the server speaks {VERSION}"
+            ));
+        }
+
+        let snake = match role {
+            Role::Spectator => None,
+            Role::Player => {
+                let taken: Vec<SnakeId> = self.conns.values().filter_map(|c| c.snake).collect();
+                if taken.len() >= self.options.max_players {
+                    return reject(format!(
+                        "the server is full ({} players)",
+                        self.options.max_players
+                    ));
+                }
+                let id = (0..).find(|id| !taken.contains(id)).unwrap();
+                if !self.game.spawn_snake(id) {
+                    return reject("there's no room on the board".into());
+                }
+                Some(id)
+            }
+        };
+
+        let welcome = ServerMessage::Welcome {
+            version: VERSION,
+            snake,
+            cols: self.options.cols,
+            rows: self.options.rows,
+            grid: self.options.game.grid,
+            tick_ms: self.options.tick_ms,
+            input_delay: self.options.input_delay,
+            portals: self.game.portals().to_vec(),
+        };
+        if !outbox.send(encode(&welcome)) {
+            log::warn!("connection {conn} failed before it was welcomed");
+            if let Some(id) = snake {
+                self.game.remove_snake(id);
+            }
+            return;
+        }
+
+        log::info!(
+            "{name} joined as {}",
+            snake.map_or("a spectator".into(), |id| format!("snake {id}"))
+        );
+        // Everyone, the newcomer included, learns who's playing
+        if let Some(id) = snake {
+            self.send_all(&ServerMessage::Joined {
+                snake: id,
+                name: name.clone(),
+            });
+        }
+        let new = Conn {
+            outbox,
+            name,
+            snake,
+            needs_keyframe: true,
+        };
+        for other in self.conns.values() {
+            if let Some(id) = other.snake {
+                let joined = ServerMessage::Joined {
+                    snake: id,
+                    name: other.name.clone(),
+                };
+                new.outbox.send(encode(&joined));
+            }
+        }
+        self.conns.insert(conn, new);
+    }
+
+    fn leave(&mut self, conn: ConnId) {
+        let Some(left) = self.conns.remove(&conn) else {
+            return;
+        };
+        left.outbox.shutdown();
+        log::info!("{} left", left.name);
+
+        if let Some(id) = left.snake {
+            self.game.remove_snake(id);
+            self.inputs.retain(|(_, snake, _)| *snake != id);
+            self.respawns.retain(|(_, snake)| *snake != id);
+            self.send_all(&ServerMessage::Left { snake: id });
+        }
+    }
+
+    // Sends `message` to every connection, dropping the ones that fail
+    fn send_all(&mut self, message: &ServerMessage) {
+        self.send_bytes(encode(message), |_| true);
+    }
+
+    // Queues `bytes` for the connections `to` picks, dropping the ones that
+    // fell behind or failed
+    fn send_bytes(&mut self, bytes: Arc<[u8]>, to: impl Fn(&Conn) -> bool) {
+        let failed: Vec<ConnId> = self
+            .conns
+            .iter()
+            .filter(|(_, c)| to(c) && !c.outbox.send(bytes.clone()))
+            .map(|(id, _)| *id)
+            .collect();
+        for conn in failed {
+            log::warn!("connection {conn} fell behind or failed, dropping it");
+            self.leave(conn);
+        }
+    }
+
+    fn broadcast(&mut self) {
+        let (keyframe, delta) = protocol::encode_tick(self.tick, &self.game, &mut self.sent_tiles);
+        let keyframe_for_all = self.tick.is_multiple_of(KEYFRAME_EVERY);
+        self.send_bytes(keyframe.into(), |c| keyframe_for_all || c.needs_keyframe);
+        self.send_bytes(delta.into(), |c| !(keyframe_for_all || c.needs_keyframe));
+        for conn in self.conns.values_mut() {
+            conn.needs_keyframe = false;
+        }
+    }
+
+    #[cfg(test)]
+    fn game(&self) -> &Game {
+        &self.game
+    }
+}
+
+fn encode(message: &ServerMessage) -> Arc<[u8]> {
+    let mut bytes = vec![];
+    message.write(&mut bytes).unwrap();
+    bytes.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread::JoinHandle;
+
+    use super::*;
+    use crate::client::Client;
+
+    // Ticks the server every few ms on a thread until `stop` is set
+    fn start(max_players: usize) -> (String, Arc<AtomicBool>, JoinHandle<Server>) {
+        let options = ServerOptions {
+            cols: 12,
+            rows: 10,
+            tick_ms: 5,
+            game: GameOptions {
+                wrap: true,
+                seed: Some(1),
+                ..Default::default()
+            },
+            max_players,
+            input_delay: 2,
+        };
+        let mut server = Server::bind("127.0.0.1:0", options).unwrap();
+        let addr = server.local_addr().unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let handle = std::thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                server.tick().unwrap();
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            server
+        });
+        (addr, stop, handle)
+    }
+
+    fn wait_for(client: &mut Client, done: impl Fn(&Client) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done(client) {
+            assert!(client.poll(), "lost the server");
+            assert!(Instant::now() < deadline, "timed out");
+            std::thread::sleep(Duration::from_millis(2));
+        }
+    }
+
+    #[test]
+    fn players_and_spectators_see_the_same_board() {
+        let (addr, stop, handle) = start(2);
+        let mut ann = Client::connect(&addr, Role::Player, "ann", 1.).unwrap();
+        let mut bob = Client::connect(&addr, Role::Player, "bob", 1.).unwrap();
+        let mut watcher = Client::connect(&addr, Role::Spectator, "eve", 1.).unwrap();
+        assert_eq!(
+            (ann.snake(), bob.snake(), watcher.snake()),
+            (Some(0), Some(1), None)
+        );
+
+        let full = Client::connect(&addr, Role::Player, "cat", 1.)
+            .err()
+            .unwrap();
+        assert_eq!(full.kind(), ErrorKind::ConnectionRefused);
+
+        wait_for(&mut watcher, |c| {
+            c.snakes().len() == 2 && c.name(1) == Some("bob")
+        });
+        let heads = |c: &Client| {
+            c.game()
+                .tiles()
+                .iter()
+                .filter(|t| **t == Tile::SnakeHead)
+                .count()
+        };
+        assert_eq!(heads(&watcher), 2);
+
+        // Nothing moves until someone steers
+        wait_for(&mut ann, |c| c.tick() > 0);
+        let before = ann.game().tiles().to_vec();
+        ann.steer(Direction::Up).unwrap();
+        wait_for(&mut ann, |c| c.game().tiles() != before);
+
+        bob.leave().unwrap();
+        wait_for(&mut watcher, |c| {
+            c.snakes().len() == 1 && c.name(1).is_none()
+        });
+
+        // Once the server is gone the mirrors hold its last board
+        stop.store(true, Ordering::Relaxed);
+        let server = handle.join().unwrap();
+        let tiles = server.game().tiles().to_vec();
+        drop(server);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        for client in [&mut ann, &mut watcher] {
+            while client.poll() {
+                assert!(Instant::now() < deadline, "timed out");
+                std::thread::sleep(Duration::from_millis(2));
+            }
+            assert_eq!(client.game().tiles(), tiles);
+        }
+    }
+
+    #[test]
+    fn other_protocol_versions_are_rejected() {
+        let (addr, stop, handle) = start(1);
+        let mut stream = crate::transport::connect(&addr).unwrap();
+        ClientMessage::Hello {
+            version: VERSION + 1,
+            role: Role::Player,
+            name: "future".into(),
+        }
+        .write(&mut stream)
+        .unwrap();
+        let reply = ServerMessage::read(&mut stream, 0).unwrap();
+        assert!(matches!(reply, ServerMessage::Reject { reason } if reason.contains("version")));
+
+        stop.store(true, Ordering::Relaxed);
+        assert_eq!(handle.join().unwrap().game().snakes().count(), 0);
+    }
+}
@@ -0,0 +1,335 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::daily::Date;
+use crate::game::{Game, GameOptions, TailRule};
+use crate::input::ControlScheme;
+use crate::level::Level;
+use crate::mode::GameMode;
+use crate::theme::Theme;
+use crate::topology::Grid;
+
+// Biggest texture side wgpu guarantees to support, and the biggest snapshot
+const MAX_WINDOW_SIDE: f32 = 8192.;
+// Biggest tile that still leaves room for a board
+const MAX_TILE_SIZE: f32 = 256.;
+// Most tiles on a board, as many as a network keyframe can hold
+const MAX_TILES: usize = 1 << 20;
+// Slowest the game goes, a move a minute
+const MAX_TICK_MS: u64 = 60_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Frontend {
+    /// GPU rendered window
+    #[default]
+    Window,
+    /// Colored blocks in the terminal
+    Terminal,
+}
+
+/// When frames are shown, see `wgpu::PresentMode`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum PresentMode {
+    /// Vsync if the driver supports it
+    #[default]
+    Auto,
+    /// Vsync, never tears
+    Fifo,
+    /// Newest frame at the next vblank, falls back to fifo
+    Mailbox,
+    /// No vsync, may tear, falls back to fifo
+    Immediate,
+}
+
+/// Everything needed to start the game, after the config files and
+/// command line have been read. Use `validate` before trusting it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub cols: usize,
+    pub rows: usize,
+    pub tile_size: f32,
+    pub tick_ms: u64,
+    pub seed: Option<u64>,
+    pub wrap: bool,
+    pub grid: Grid,
+    pub power_ups: bool,
+    pub mode: GameMode,
+    pub tail: TailRule,
+    pub level: Level,
+    pub theme: Theme,
+    pub steering: ControlScheme,
+    pub frontend: Frontend,
+    pub fullscreen: bool,
+    pub present_mode: PresentMode,
+    pub mute: bool,
+
+    // Run without any frontend, the built-in bot plays until it dies or
+    // `max_ticks` have passed
+    pub headless: bool,
+    pub max_ticks: u64,
+    // Where to save the final board of a headless game, and the size to
+    // shrink it to
+    pub snapshot: Option<PathBuf>,
+    pub snapshot_size: Option<u32>,
+    // Where to save a replay of each game when it ends
+    pub record: Option<PathBuf>,
+    // Where viewers can watch a headless game, `host:port` or `unix:PATH`
+    pub stream: Option<String>,
+    // Day of the daily challenge being played, see `daily::Daily::apply`
+    pub daily: Option<Date>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            cols: 40,
+            rows: 30,
+            tile_size: 20.,
+            tick_ms: 100,
+            seed: None,
+            wrap: false,
+            grid: Grid::default(),
+            power_ups: false,
+            mode: GameMode::default(),
+            tail: TailRule::default(),
+            level: Level::default(),
+            theme: Theme::default(),
+            steering: ControlScheme::default(),
+            frontend: Frontend::default(),
+            fullscreen: false,
+            present_mode: PresentMode::default(),
+            mute: false,
+            headless: false,
+            max_ticks: 10_000,
+            snapshot: None,
+            snapshot_size: None,
+            record: None,
+            stream: None,
+            daily: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Pixel (width, height) of the board
+    pub fn board_size(&self) -> (f32, f32) {
+        self.grid
+            .topology(self.cols, self.rows, self.wrap)
+            .size(self.tile_size)
+    }
+
+    pub fn game_options(&self) -> GameOptions {
+        GameOptions {
+            wrap: self.wrap,
+            level: self.level.clone(),
+            seed: self.seed,
+            grid: self.grid,
+            power_ups: self.power_ups,
+            mode: self.mode,
+            tail: self.tail,
+        }
+    }
+
+    pub fn new_game(&self) -> Game {
+        Game::with_options(self.cols, self.rows, self.tile_size, self.game_options())
+    }
+
+    /// Checks the settings describe a game that can actually be played
+    pub fn validate(&self) -> Result<(), String> {
+        // Also catches NaN
+        if !(1. ..=MAX_TILE_SIZE).contains(&self.tile_size) {
+            return Err(format!(
+                "tile size must be from 1 to {MAX_TILE_SIZE} pixels, got {}",
+                self.tile_size
+            ));
+        }
+        if !(1..=MAX_TICK_MS).contains(&self.tick_ms) {
+            return Err(format!(
+                "tick rate must be from 1 to {MAX_TICK_MS} ms, got {}",
+                self.tick_ms
+            ));
+        }
+        if self.cols == 0 || self.rows == 0 {
+            return Err(format!("a {}x{} board has no tiles", self.cols, self.rows));
+        }
+        if self
+            .cols
+            .checked_mul(self.rows)
+            .is_none_or(|tiles| tiles > MAX_TILES)
+        {
+            return Err(format!(
+                "a {}x{} board has more than the {MAX_TILES} tiles a board can have",
+                self.cols, self.rows
+            ));
+        }
+        if self.grid == Grid::Hex && self.wrap && self.rows % 2 == 1 {
+            return Err(format!(
+                "a hex board has to have an even number of rows to wrap, not {}",
+                self.rows
+            ));
+        }
+
+        if let Some((width, height)) = self.level.size() {
+            if width > self.cols || height > self.rows {
+                return Err(format!(
+                    "level {} is {width}x{height} but the board is only {}x{}",
+                    self.level.name, self.cols, self.rows
+                ));
+            }
+        }
+
+        // Need room for at least the snake and one food. The snake starts on
+        // whichever free tile is closest to the center.
+        let mut board = self.level.mask(self.cols, self.rows);
+        for wall in self.level.walls(self.cols, self.rows) {
+            board[wall.y * self.cols + wall.x] = false;
+        }
+        for (a, b) in self.level.portals(self.cols, self.rows) {
+            for pos in [a, b] {
+                board[pos.y * self.cols + pos.x] = false;
+            }
+        }
+        for entity in self.level.entities(self.cols, self.rows) {
+            board[entity.pos.y * self.cols + entity.pos.x] = false;
+        }
+        let free = board.iter().filter(|free| **free).count();
+        if free < 2 {
+            return Err(format!(
+                "a {}x{} board with level {} has {free} free tile(s), \
+                 need at least 2 for the snake and its food",
+                self.cols, self.rows, self.level.name
+            ));
+        }
+
+        let windowed = !self.headless && self.frontend == Frontend::Window;
+        if windowed || self.snapshot.is_some() {
+            let (w, h) = self.board_size();
+            if w > MAX_WINDOW_SIDE || h > MAX_WINDOW_SIDE {
+                return Err(format!(
+                    "the board would be {w}x{h} pixels, more than the {MAX_WINDOW_SIDE} \
+                     the renderer supports, try a smaller tile size"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Pos, Tile};
+
+    fn wall_count(game: &Game) -> usize {
+        (0..game.rows())
+            .flat_map(|y| (0..game.cols()).map(move |x| Pos { x, y }))
+            .filter(|pos| game.tile(*pos) == Tile::Wall)
+            .count()
+    }
+
+    fn settings(cols: usize, rows: usize) -> Settings {
+        Settings {
+            cols,
+            rows,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn default_settings_are_valid() {
+        assert_eq!(Settings::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn tiny_boards_are_rejected() {
+        let err = settings(1, 1).validate().unwrap_err();
+        assert!(err.contains("1x1"), "{err}");
+        assert!(settings(0, 10).validate().is_err());
+        assert!(settings(usize::MAX, 2).validate().is_err());
+        assert!(settings(2048, 1024).validate().is_err());
+        assert_eq!(settings(2, 1).validate(), Ok(()));
+    }
+
+    #[test]
+    fn levels_must_leave_room_to_play() {
+        let boxed = Settings {
+            level: Level::by_name("box").unwrap(),
+            ..settings(3, 3)
+        };
+        assert!(boxed.validate().is_err());
+
+        // The snake starts next to a wall in the middle instead
+        let walled_middle = Settings {
+            level: Level::parse("middle", "...\n.#.\n...\n").unwrap(),
+            ..settings(3, 3)
+        };
+        assert_eq!(walled_middle.validate(), Ok(()));
+
+        let too_big = Settings {
+            level: Level::parse("big", &"#".repeat(50)).unwrap(),
+            ..settings(10, 10)
+        };
+        assert!(too_big.validate().unwrap_err().contains("50x1"));
+    }
+
+    #[test]
+    fn huge_windows_are_rejected_unless_headless() {
+        let huge = Settings {
+            tile_size: 100.,
+            ..settings(100, 10)
+        };
+        assert!(huge.validate().is_err());
+        let headless = Settings {
+            headless: true,
+            ..huge
+        };
+        assert_eq!(headless.validate(), Ok(()));
+
+        // Snapshots are rendered at full size first
+        let snapshot = Settings {
+            snapshot: Some("snapshot.png".into()),
+            ..headless
+        };
+        assert!(snapshot.validate().is_err());
+    }
+
+    #[test]
+    fn tick_rates_must_be_sane() {
+        for tick_ms in [0, MAX_TICK_MS + 1, u64::MAX] {
+            let settings = Settings {
+                tick_ms,
+                ..Default::default()
+            };
+            assert!(settings.validate().is_err(), "{tick_ms}");
+        }
+    }
+
+    #[test]
+    fn tile_sizes_must_be_sane() {
+        for tile_size in [0.5, 1e30, f32::INFINITY, f32::NAN] {
+            let settings = Settings {
+                tile_size,
+                headless: true,
+                ..Default::default()
+            };
+            assert!(settings.validate().is_err(), "{tile_size}");
+        }
+    }
+
+    #[test]
+    fn new_game_uses_the_settings() {
+        let game = Settings {
+            level: Level::by_name("box").unwrap(),
+            seed: Some(7),
+            ..settings(12, 8)
+        }
+        .new_game();
+        assert_eq!((game.cols(), game.rows()), (12, 8));
+        assert_eq!(game.seed(), 7);
+        assert_eq!(wall_count(&game), 2 * 12 + 2 * 6);
+    }
+}
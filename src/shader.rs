@@ -31,11 +31,12 @@ impl Vertex {
     }
 }
 
-pub fn square_for_pos(win_width: f32, win_height: f32, x: f32, y: f32, size: f32) -> [f32; 12] {
-    let l = x - size; // (size / 2.);
-    let r = x + size; // (size / 2.);
+// (x, y) is the bottom left corner of the square
+pub fn square_for_pos(x: f32, y: f32, size: f32) -> [f32; 12] {
+    let l = x;
+    let r = x + size;
     let t = y + size;
-    let b = y - size;
+    let b = y;
 
     [
         l, b, // bottom left
@@ -46,3 +47,29 @@ pub fn square_for_pos(win_width: f32, win_height: f32, x: f32, y: f32, size: f32
         l, t, // top left
     ]
 }
+
+// (x, y) is the center of a pointy top hexagon `size` wide, as a fan of 4
+// triangles from its top corner
+pub fn hexagon_for_pos(x: f32, y: f32, size: f32) -> [f32; 24] {
+    let w = size / 2.;
+    let h = size / 3f32.sqrt();
+
+    let top = [x, y + h];
+    let corners = [
+        [x - w, y + h / 2.], // top left
+        [x - w, y - h / 2.], // bottom left
+        [x, y - h],          // bottom
+        [x + w, y - h / 2.], // bottom right
+        [x + w, y + h / 2.], // top right
+    ];
+
+    let mut ps = [0.; 24];
+    for (i, pair) in corners.windows(2).enumerate() {
+        let triangle = [top, pair[0], pair[1]];
+        for (j, [px, py]) in triangle.into_iter().enumerate() {
+            ps[i * 6 + j * 2] = px;
+            ps[i * 6 + j * 2 + 1] = py;
+        }
+    }
+    ps
+}
@@ -1,12 +1,13 @@
+// Snake, background, food, wall, one per portal pair, power-ups and hazards
+// (see `Theme::board_colors`)
 struct ColorUniforms {
-    snake_color: vec3<f32>,
-    bg_color: vec3<f32>,
-    food_color: vec3<f32>,
+    colors: array<vec4<f32>, 10>,
 }
 
-struct WindowRes {
-    width: f32,
-    height: f32,
+// Window size, and how far the board is moved for screen shake
+struct View {
+    size: vec2<f32>,
+    offset: vec2<f32>,
 }
 
 @group(0) @binding(0)
@@ -14,7 +15,7 @@ var<uniform> color_uniforms: ColorUniforms;
 
 // Split from the main one because of alignment stuff
 @group(0) @binding(1)
-var<uniform> window_size: vec2<f32>;
+var<uniform> view: View;
 
 struct VertexInput {
     @location(0) pos: vec2<f32>,
@@ -33,15 +34,9 @@ fn vs_main(
 ) -> VertexOutput {
     var out: VertexOutput;
 
-    if model.coloridx == u32(0) {
-        out.color = color_uniforms.snake_color;
-    } else if model.coloridx == u32(1) {
-        out.color = color_uniforms.bg_color;
-    } else if model.coloridx == u32(2) {
-        out.color = color_uniforms.food_color;
-    }
+    out.color = color_uniforms.colors[model.coloridx].rgb;
 
-    let pos_float = model.pos / window_size;
+    let pos_float = (model.pos + view.offset) / view.size;
     // Convert to clip coordinates
     let x = (pos_float.x - 0.5) * 2.;
     let y = (pos_float.y - 0.5) * 2.;
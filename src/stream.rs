@@ -0,0 +1,277 @@
+// `--stream`: shows a game that isn't played over the network (the headless
+// bot, a tournament) to anyone who connects with `snake watch`. Viewers
+// speak the server protocol (see protocol.rs) as spectators, so they get a
+// Keyframe when they join and a Delta of the changed tiles every tick.
+//
+// The game loop only calls `send` once a tick, handshakes happen on a
+// thread per viewer and ticks are written by an `Outbox` thread per viewer,
+// so a slow one can't hold the game up. Viewers that fall behind are dropped.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::game::{Game, SnakeId, Tile};
+use crate::protocol::{self, ClientMessage, Role, ServerMessage, VERSION};
+use crate::topology::Grid;
+use crate::transport::{Listener, Outbox, Stream};
+
+// Every viewer gets a full board this often, besides when it joins
+const KEYFRAME_EVERY: u64 = 50;
+// A viewer with this many messages it hasn't taken yet has fallen behind and
+// is dropped
+const MAX_QUEUED: usize = 64;
+// A write that doesn't go through in this long fails, dropping the viewer
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub struct Broadcaster {
+    listener: Listener,
+    welcome: ServerMessage,
+    // Viewers that finished the handshake, waiting for their first keyframe
+    joined: Receiver<Stream>,
+    sender: Sender<Stream>,
+    // Dropping them tells viewers the game is over, once they have all of it
+    viewers: Vec<Outbox>,
+
+    names: BTreeMap<SnakeId, String>,
+    tick: u64,
+    // Board as viewers last saw it
+    sent_tiles: Vec<Tile>,
+}
+
+// Answers the viewer's Hello, and hands the stream over if it's welcome
+fn handshake(mut stream: Stream, welcome: ServerMessage, joined: Sender<Stream>) {
+    let reason = match ClientMessage::read(&mut stream) {
+        Ok(ClientMessage::Hello { version, .. }) if version != VERSION => format!(
+            "protocol version {version} isn't supported, the stream speaks {VERSION}"
+        ),
+        Ok(ClientMessage::Hello {
+            role: Role::Player, ..
+        }) => "this is a stream, it can only be watched".into(),
+        Ok(ClientMessage::Hello { name, .. }) => {
+            if welcome.write(&mut stream).is_ok() {
+                log::info!("{name} is watching");
+                let _ = joined.send(stream);
+            }
+            return;
+        }
+        Ok(message) => {
+            log::warn!("viewer started with {message:?} instead of hello");
+            return;
+        }
+        Err(e) => {
+            log::warn!("viewer failed: {e}");
+            return;
+        }
+    };
+    log::info!("rejected a viewer: {reason}");
+    let _ = ServerMessage::Reject { reason }.write(&mut stream);
+    let _ = stream.shutdown();
+}
+
+impl Broadcaster {
+    /// Listens on `addr`, `host:port` or `unix:PATH`, for viewers of a
+    /// `cols` x `rows` board of `grid` tiles ticking every `tick_ms`
+    pub fn bind(
+        addr: &str,
+        cols: usize,
+        rows: usize,
+        grid: Grid,
+        tick_ms: u64,
+    ) -> io::Result<Self> {
+        let listener = Listener::bind(addr)?;
+        let (sender, joined) = mpsc::channel();
+        Ok(Self {
+            listener,
+            welcome: ServerMessage::Welcome {
+                version: VERSION,
+                snake: None,
+                cols,
+                rows,
+                grid,
+                tick_ms,
+                input_delay: 0,
+                portals: vec![],
+            },
+            joined,
+            sender,
+            viewers: vec![],
+            names: BTreeMap::new(),
+            tick: 0,
+            sent_tiles: vec![Tile::Empty; cols * rows],
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<String> {
+        self.listener.local_addr()
+    }
+
+    /// Tells viewers that join from now on where the portals are, they're
+    /// fixed for a game so only the welcome carries them
+    pub fn set_portals(&mut self, pairs: &[(usize, usize)]) {
+        if let ServerMessage::Welcome { portals, .. } = &mut self.welcome {
+            *portals = pairs.to_vec();
+        }
+    }
+
+    /// Names `snake` on viewers' HUDs
+    pub fn set_name(&mut self, snake: SnakeId, name: &str) {
+        self.names.insert(snake, name.into());
+        self.send_bytes(encode(&ServerMessage::Joined {
+            snake,
+            name: name.into(),
+        }));
+    }
+
+    /// Sends this tick's board to every viewer and lets new ones in. Viewers
+    /// that fail are dropped, only a broken listener is an error.
+    pub fn send(&mut self, game: &Game) -> io::Result<()> {
+        while let Some(stream) = self.listener.accept()? {
+            if let Err(e) = stream.set_write_timeout(Some(WRITE_TIMEOUT)) {
+                log::warn!("dropping a viewer: {e}");
+                continue;
+            }
+            let (welcome, sender) = (self.welcome.clone(), self.sender.clone());
+            std::thread::spawn(move || handshake(stream, welcome, sender));
+        }
+
+        self.tick += 1;
+        let (keyframe, delta) = protocol::encode_tick(self.tick, game, &mut self.sent_tiles);
+        let keyframe: Arc<[u8]> = keyframe.into();
+        if self.tick.is_multiple_of(KEYFRAME_EVERY) {
+            self.send_bytes(keyframe.clone());
+        } else {
+            self.send_bytes(delta.into());
+        }
+
+        // Newcomers start from the full board, after everyone else got this
+        // tick so the delta isn't sent twice
+        while let Ok(stream) = self.joined.try_recv() {
+            let viewer = match Outbox::new(stream, MAX_QUEUED) {
+                Ok(viewer) => viewer,
+                Err(e) => {
+                    log::warn!("viewer failed: {e}");
+                    continue;
+                }
+            };
+            let ok = self.names.iter().all(|(&snake, name)| {
+                viewer.send(encode(&ServerMessage::Joined {
+                    snake,
+                    name: name.clone(),
+                }))
+            });
+            if ok && viewer.send(keyframe.clone()) {
+                self.viewers.push(viewer);
+            }
+        }
+        Ok(())
+    }
+
+    // Drops the viewers that fell behind or failed
+    fn send_bytes(&mut self, bytes: Arc<[u8]>) {
+        self.viewers.retain(|viewer| {
+            let sent = viewer.send(bytes.clone());
+            if !sent {
+                log::info!("dropping a viewer that fell behind or failed");
+                viewer.shutdown();
+            }
+            sent
+        });
+    }
+
+    #[cfg(test)]
+    fn viewers(&self) -> usize {
+        self.viewers.len()
+    }
+}
+
+fn encode(message: &ServerMessage) -> Arc<[u8]> {
+    let mut bytes = vec![];
+    message.write(&mut bytes).unwrap();
+    bytes.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::client::Client;
+    use crate::game::{Direction, GameOptions};
+
+    fn watch(addr: &str) {
+        let mut game = Game::with_options(
+            12,
+            8,
+            1.,
+            GameOptions {
+                seed: Some(3),
+                ..GameOptions::default()
+            },
+        );
+        let mut broadcaster = Broadcaster::bind(addr, 12, 8, Grid::Square, 10).unwrap();
+        broadcaster.set_name(0, "bot");
+        let addr = broadcaster.local_addr().unwrap();
+        // The handshake needs the game loop running to be accepted
+        let connecting =
+            std::thread::spawn(move || Client::connect(&addr, Role::Spectator, "viewer", 1.));
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !connecting.is_finished() || broadcaster.viewers() == 0 {
+            assert!(Instant::now() < deadline, "the viewer never joined");
+            broadcaster.send(&game).unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let mut viewer = connecting.join().unwrap().unwrap();
+        // A few ticks of deltas after the keyframe
+        game.change_direction(Direction::Up);
+        for _ in 0..3 {
+            game.update();
+            broadcaster.send(&game).unwrap();
+        }
+
+        while viewer.tick() != broadcaster.tick {
+            assert!(Instant::now() < deadline, "the viewer fell behind");
+            assert!(viewer.poll());
+        }
+        assert_eq!(viewer.game().tiles(), game.tiles());
+        assert_eq!(viewer.snakes(), game.snakes().collect::<Vec<_>>());
+        assert_eq!(viewer.name(0), Some("bot"));
+
+        drop(broadcaster);
+        while viewer.poll() {
+            assert!(Instant::now() < deadline, "the stream never ended");
+        }
+    }
+
+    #[test]
+    fn viewers_see_the_board_over_tcp() {
+        watch("127.0.0.1:0");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn viewers_see_the_board_over_a_unix_socket() {
+        let path = std::env::temp_dir().join(format!("snake-stream-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        watch(&format!("unix:{}", path.display()));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn players_are_turned_away() {
+        let mut broadcaster = Broadcaster::bind("127.0.0.1:0", 4, 4, Grid::Square, 10).unwrap();
+        let addr = broadcaster.local_addr().unwrap();
+        let game = Game::with_options(4, 4, 1., GameOptions::default());
+        let connecting =
+            std::thread::spawn(move || Client::connect(&addr, Role::Player, "player", 1.).err());
+        while !connecting.is_finished() {
+            broadcaster.send(&game).unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let error = connecting.join().unwrap().expect("a player got in");
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionRefused);
+    }
+}
@@ -0,0 +1,318 @@
+use crate::font;
+
+use wgpu::util::DeviceExt;
+
+// Max number of quads (glyphs + rectangles) drawn per frame
+const MAX_QUADS: usize = 4096;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TextVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl TextVertex {
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRS: [wgpu::VertexAttribute; 3] =
+            wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4];
+
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRS,
+        }
+    }
+}
+
+/// Text and flat rectangles as textured quads in window pixels, sampling a
+/// glyph atlas built from the bundled bitmap font. Built every frame, then
+/// drawn by `TextRenderer` or the CPU rasterizer.
+pub struct TextBatch {
+    atlas: font::Atlas,
+    vertices: Vec<TextVertex>,
+}
+
+impl TextBatch {
+    pub fn new() -> Self {
+        Self {
+            atlas: font::Atlas::new(),
+            vertices: Vec::new(),
+        }
+    }
+
+    pub fn atlas(&self) -> &font::Atlas {
+        &self.atlas
+    }
+
+    /// Triangles, three vertices each
+    pub fn vertices(&self) -> &[TextVertex] {
+        &self.vertices
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    fn quad(&mut self, x: f32, y: f32, w: f32, h: f32, uv: [f32; 4], color: [f32; 4]) {
+        if self.vertices.len() + 6 > MAX_QUADS * 6 {
+            return;
+        }
+
+        let [ul, ut, ur, ub] = uv;
+        // y goes up in window space but down in the atlas
+        let bl = TextVertex {
+            position: [x, y],
+            uv: [ul, ub],
+            color,
+        };
+        let br = TextVertex {
+            position: [x + w, y],
+            uv: [ur, ub],
+            color,
+        };
+        let tl = TextVertex {
+            position: [x, y + h],
+            uv: [ul, ut],
+            color,
+        };
+        let tr = TextVertex {
+            position: [x + w, y + h],
+            uv: [ur, ut],
+            color,
+        };
+        self.vertices.extend_from_slice(&[bl, br, tl, br, tr, tl]);
+    }
+
+    /// Flat rectangle, (`x`, `y`) is the bottom left corner in pixels
+    pub fn rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: [f32; 4]) {
+        let uv = self.atlas.uv_rect(font::SOLID);
+        self.quad(x, y, w, h, uv, color);
+    }
+
+    /// Single line of text, (`x`, `y`) is the bottom left corner in pixels
+    /// and `scale` is the size of one font pixel.
+    pub fn text(&mut self, text: &str, x: f32, y: f32, scale: f32, color: [f32; 4]) {
+        let w = font::GLYPH_WIDTH as f32 * scale;
+        let h = font::GLYPH_HEIGHT as f32 * scale;
+
+        // On whole pixels, so font pixels don't straddle screen pixels
+        let (x, y) = (x.round(), y.round());
+        let mut pen = x;
+        for c in text.chars() {
+            if c != ' ' {
+                let uv = self.atlas.uv_for_char(c);
+                self.quad(pen, y, w, h, uv, color);
+            }
+            pen += font::ADVANCE as f32 * scale;
+        }
+    }
+
+    /// Like `text`, but centered on (`cx`, `cy`)
+    pub fn text_centered(&mut self, text: &str, cx: f32, cy: f32, scale: f32, color: [f32; 4]) {
+        let (w, h) = font::text_size(text, scale);
+        self.text(text, cx - w / 2., cy - h / 2., scale, color);
+    }
+}
+
+/// Draws a `TextBatch` on top of the board
+pub struct TextRenderer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    resolution_buffer: wgpu::Buffer,
+    vertex_buffer: wgpu::Buffer,
+
+    num_vertices: u32,
+}
+
+impl TextRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        win_width: f32,
+        win_height: f32,
+    ) -> Self {
+        let atlas = font::Atlas::new();
+
+        let texture_size = wgpu::Extent3d {
+            width: atlas.width,
+            height: atlas.height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("glyph atlas"),
+            size: texture_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &atlas.pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(atlas.width),
+                rows_per_image: Some(atlas.height),
+            },
+            texture_size,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("glyph atlas sampler"),
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let resolution_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("text resolution buffer"),
+            contents: bytemuck::cast_slice(&[win_width, win_height]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("text_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("text bind group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: resolution_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("text vertex buffer"),
+            mapped_at_creation: false,
+            size: (MAX_QUADS * 6 * std::mem::size_of::<TextVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("text.wgsl"));
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Text Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("text pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[TextVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            bind_group,
+            resolution_buffer,
+            vertex_buffer,
+            num_vertices: 0,
+        }
+    }
+
+    pub fn resize(&self, queue: &wgpu::Queue, win_width: f32, win_height: f32) {
+        queue.write_buffer(
+            &self.resolution_buffer,
+            0,
+            bytemuck::cast_slice(&[win_width, win_height]),
+        );
+    }
+
+    /// Uploads the batch to draw next
+    pub fn prepare(&mut self, queue: &wgpu::Queue, batch: &TextBatch) {
+        let vertices = batch.vertices();
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(vertices));
+        self.num_vertices = vertices.len() as u32;
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if self.num_vertices == 0 {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..self.num_vertices, 0..1);
+    }
+}
@@ -0,0 +1,43 @@
+@group(0) @binding(0)
+var atlas: texture_2d<f32>;
+
+@group(0) @binding(1)
+var atlas_sampler: sampler;
+
+@group(0) @binding(2)
+var<uniform> window_size: vec2<f32>;
+
+struct VertexInput {
+    @location(0) pos: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let pos_float = model.pos / window_size;
+    // Same pixel -> clip conversion as the tile shader
+    let x = (pos_float.x - 0.5) * 2.;
+    let y = (pos_float.y - 0.5) * 2.;
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    out.uv = model.uv;
+    out.color = model.color;
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(atlas, atlas_sampler, in.uv).r;
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
@@ -0,0 +1,129 @@
+use crate::game::PORTAL_COLORS;
+
+/// Colors for everything on screen. Board colors are indexed the same way as
+/// the `coloridx` in `shader::Vertex`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub name: &'static str,
+
+    pub snake: [f32; 3],
+    pub background: [f32; 3],
+    pub food: [f32; 3],
+    pub wall: [f32; 3],
+    // One per portal pair, pairs after the last reuse them from the start
+    pub portals: [[f32; 3]; PORTAL_COLORS],
+    pub power_up: [f32; 3],
+    pub hazard: [f32; 3],
+
+    pub hud_background: [f32; 3],
+    pub text: [f32; 3],
+}
+
+impl Theme {
+    pub fn builtin() -> Vec<Theme> {
+        vec![
+            Theme {
+                name: "CLASSIC",
+                snake: [1., 1., 1.],
+                background: [0., 0., 0.],
+                food: [1., 0., 0.],
+                wall: [0.4, 0.4, 0.4],
+                portals: [[0., 0.5, 1.], [1., 0.5, 0.], [0.7, 0.2, 1.], [0., 0.8, 0.3]],
+                power_up: [0.2, 1., 1.],
+                hazard: [1., 0.85, 0.],
+                hud_background: [0.15, 0.15, 0.15],
+                text: [1., 1., 1.],
+            },
+            Theme {
+                name: "NOKIA",
+                snake: [0.05, 0.12, 0.05],
+                background: [0.45, 0.6, 0.25],
+                food: [0.1, 0.2, 0.1],
+                wall: [0.2, 0.3, 0.15],
+                portals: [
+                    [0.15, 0.25, 0.1],
+                    [0.25, 0.35, 0.1],
+                    [0.1, 0.3, 0.2],
+                    [0.3, 0.3, 0.2],
+                ],
+                power_up: [0.35, 0.2, 0.05],
+                hazard: [0.4, 0.1, 0.05],
+                hud_background: [0.3, 0.42, 0.17],
+                text: [0.05, 0.12, 0.05],
+            },
+            Theme {
+                name: "OCEAN",
+                snake: [0.3, 0.85, 0.9],
+                background: [0.02, 0.05, 0.15],
+                food: [1., 0.75, 0.2],
+                wall: [0.15, 0.25, 0.45],
+                portals: [
+                    [0.9, 0.3, 0.6],
+                    [0.5, 1., 0.6],
+                    [1., 1., 0.5],
+                    [0.6, 0.5, 1.],
+                ],
+                power_up: [1., 0.4, 0.3],
+                hazard: [1., 0.2, 0.2],
+                hud_background: [0.05, 0.1, 0.25],
+                text: [0.8, 0.9, 1.],
+            },
+        ]
+    }
+
+    pub fn by_name(name: &str) -> Option<Theme> {
+        Self::builtin()
+            .into_iter()
+            .find(|t| t.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Like `by_name`, but the error lists the themes there are
+    pub fn find(name: &str) -> Result<Theme, String> {
+        Self::by_name(name).ok_or_else(|| {
+            let names: Vec<_> = Self::builtin().iter().map(|t| t.name).collect();
+            format!(
+                "unknown theme '{name}', pick one of {}",
+                names.join(", ").to_lowercase()
+            )
+        })
+    }
+
+    /// Board colors in the order the shader indexes them
+    pub fn board_colors(&self) -> [[f32; 3]; 6 + PORTAL_COLORS] {
+        let [a, b, c, d] = self.portals;
+        [
+            self.snake,
+            self.background,
+            self.food,
+            self.wall,
+            a,
+            b,
+            c,
+            d,
+            self.power_up,
+            self.hazard,
+        ]
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::builtin().remove(0)
+    }
+}
+
+/// Parses a `#rrggbb` color
+pub fn parse_color(text: &str) -> Result<[f32; 3], String> {
+    let hex = text
+        .strip_prefix('#')
+        .filter(|hex| hex.len() == 6 && hex.is_ascii())
+        .ok_or_else(|| format!("expected a color like \"#ff8800\", got \"{text}\""))?;
+
+    let mut color = [0.; 3];
+    for (i, channel) in color.iter_mut().enumerate() {
+        let value = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .map_err(|_| format!("\"{text}\" isn't a hex color"))?;
+        *channel = value as f32 / 255.;
+    }
+    Ok(color)
+}
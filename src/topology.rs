@@ -0,0 +1,362 @@
+// Board shapes. A topology says which tile is next to which, how tiles are
+// numbered and where they're drawn, so `Game` never has to know whether the
+// board is made of squares or hexagons.
+//
+// Tiles are always numbered row by row from the bottom left, `y * cols + x`,
+// whatever their shape. Hex boards are "pointy top" with every odd row pushed
+// half a tile to the right:
+//
+//      / \ / \ / \
+//     | 0 | 1 | 2 |      row 0 and row 1 (above it) of a 3 wide board,
+//      \ / \ / \ / \     with (1, 0) touching (0, 1) and (1, 1)
+//       | 0 | 1 | 2 |
+//        \ / \ / \ /
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::{Direction, Pos, Turn};
+use crate::shader;
+
+/// Tile shape of a board
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Grid {
+    #[default]
+    Square,
+    /// Six neighbours per tile
+    Hex,
+}
+
+impl Grid {
+    pub fn topology(self, cols: usize, rows: usize, wrap: bool) -> Arc<dyn Topology> {
+        match self {
+            Grid::Square => Arc::new(Square { cols, rows, wrap }),
+            Grid::Hex => Arc::new(Hex { cols, rows, wrap }),
+        }
+    }
+}
+
+pub trait Topology: Send + Sync {
+    fn cols(&self) -> usize;
+
+    /// Directions a snake can move in, clockwise from the one a stationary
+    /// snake turns relative to
+    fn directions(&self) -> &'static [Direction];
+
+    /// Tile one step from `pos` in `direction`, None if that leaves the board
+    fn step(&self, pos: Pos, direction: Direction) -> Option<Pos>;
+
+    /// Fewest steps from `a` to `b` on an empty board
+    fn distance(&self, a: Pos, b: Pos) -> usize;
+
+    /// Center of the tile at `pos` in pixels, y up from the bottom left
+    fn center(&self, pos: Pos, tile_size: f32) -> [f32; 2];
+
+    /// Appends triangles (three corners each, in pixels) covering the tile
+    /// at `pos`
+    fn triangles(&self, pos: Pos, tile_size: f32, corners: &mut Vec<[f32; 2]>);
+
+    /// Pixel (width, height) of the whole board
+    fn size(&self, tile_size: f32) -> (f32, f32);
+
+    fn index(&self, pos: Pos) -> usize {
+        pos.y * self.cols() + pos.x
+    }
+
+    fn pos(&self, index: usize) -> Pos {
+        Pos {
+            x: index % self.cols(),
+            y: index / self.cols(),
+        }
+    }
+
+    /// Direction after turning one step, a stationary snake turns as if it
+    /// was heading in the first of `directions`
+    fn turned(&self, direction: Direction, turn: Turn) -> Direction {
+        let directions = self.directions();
+        let n = directions.len();
+        let i = directions.iter().position(|d| *d == direction);
+        match (turn, i) {
+            (Turn::Straight, _) => direction,
+            (Turn::Left, i) => directions[(i.unwrap_or(0) + n - 1) % n],
+            (Turn::Right, i) => directions[(i.unwrap_or(0) + 1) % n],
+        }
+    }
+
+    /// The direction of this board closest to `wanted`, which may be one
+    /// of another board (Up on a hex board). Ties go to the one closest to
+    /// `heading`, so Up while heading right is up and to the right.
+    fn closest(&self, wanted: Direction, heading: Direction) -> Direction {
+        let Some(angle) = degrees(wanted) else {
+            return wanted;
+        };
+        let apart = |a: u32, b: u32| a.abs_diff(b).min(360 - a.abs_diff(b));
+        let heading = degrees(heading);
+        *self
+            .directions()
+            .iter()
+            .min_by_key(|d| {
+                let to = degrees(**d).unwrap();
+                (apart(to, angle), heading.map_or(0, |h| apart(to, h)))
+            })
+            .unwrap()
+    }
+}
+
+// Counterclockwise from the right, None for Stationary
+fn degrees(direction: Direction) -> Option<u32> {
+    use Direction::*;
+
+    Some(match direction {
+        Stationary => return None,
+        Right => 0,
+        UpRight => 60,
+        Up => 90,
+        UpLeft => 120,
+        Left => 180,
+        DownLeft => 240,
+        Down => 270,
+        DownRight => 300,
+    })
+}
+
+// Wraps (x, y) onto a `cols` x `rows` board, or None if it's off the board
+// and the board doesn't wrap
+fn wrapped(x: isize, y: isize, cols: usize, rows: usize, wrap: bool) -> Option<Pos> {
+    let (cols, rows) = (cols as isize, rows as isize);
+    if wrap {
+        Some(Pos {
+            x: x.rem_euclid(cols) as usize,
+            y: y.rem_euclid(rows) as usize,
+        })
+    } else if x < 0 || y < 0 || x >= cols || y >= rows {
+        None
+    } else {
+        Some(Pos {
+            x: x as usize,
+            y: y as usize,
+        })
+    }
+}
+
+/// The classic board, four neighbours per tile
+pub struct Square {
+    cols: usize,
+    rows: usize,
+    wrap: bool,
+}
+
+impl Topology for Square {
+    fn cols(&self) -> usize {
+        self.cols
+    }
+
+    fn directions(&self) -> &'static [Direction] {
+        use Direction::*;
+
+        &[Up, Right, Down, Left]
+    }
+
+    fn step(&self, pos: Pos, direction: Direction) -> Option<Pos> {
+        use Direction::*;
+
+        let (dx, dy) = match direction {
+            Right => (1, 0),
+            Left => (-1, 0),
+            Up => (0, 1),
+            Down => (0, -1),
+            _ => (0, 0),
+        };
+        wrapped(
+            pos.x as isize + dx,
+            pos.y as isize + dy,
+            self.cols,
+            self.rows,
+            self.wrap,
+        )
+    }
+
+    fn distance(&self, a: Pos, b: Pos) -> usize {
+        let (dx, dy) = (a.x.abs_diff(b.x), a.y.abs_diff(b.y));
+        if self.wrap {
+            dx.min(self.cols - dx) + dy.min(self.rows - dy)
+        } else {
+            dx + dy
+        }
+    }
+
+    fn center(&self, pos: Pos, tile_size: f32) -> [f32; 2] {
+        [
+            (pos.x as f32 + 0.5) * tile_size,
+            (pos.y as f32 + 0.5) * tile_size,
+        ]
+    }
+
+    fn triangles(&self, pos: Pos, tile_size: f32, corners: &mut Vec<[f32; 2]>) {
+        let ps = shader::square_for_pos(
+            pos.x as f32 * tile_size,
+            pos.y as f32 * tile_size,
+            tile_size,
+        );
+        corners.extend(ps.chunks(2).map(|p| [p[0], p[1]]));
+    }
+
+    fn size(&self, tile_size: f32) -> (f32, f32) {
+        (self.cols as f32 * tile_size, self.rows as f32 * tile_size)
+    }
+}
+
+/// Hexagons, six neighbours per tile. Wrapping needs an even number of
+/// rows, or the top and bottom rows wouldn't line up.
+pub struct Hex {
+    cols: usize,
+    rows: usize,
+    wrap: bool,
+}
+
+impl Hex {
+    // Point to point height of a hexagon `tile_size` wide
+    fn height(tile_size: f32) -> f32 {
+        tile_size * 2. / 3f32.sqrt()
+    }
+
+    // Cube coordinates (q, r), the third being -q - r
+    fn cube(x: isize, y: isize) -> (isize, isize) {
+        (x - (y - (y & 1)) / 2, y)
+    }
+}
+
+impl Topology for Hex {
+    fn cols(&self) -> usize {
+        self.cols
+    }
+
+    fn directions(&self) -> &'static [Direction] {
+        use Direction::*;
+
+        &[UpRight, Right, DownRight, DownLeft, Left, UpLeft]
+    }
+
+    fn step(&self, pos: Pos, direction: Direction) -> Option<Pos> {
+        use Direction::*;
+
+        // Odd rows are half a tile further right than the ones around them
+        let shift = (pos.y % 2) as isize;
+        let (dx, dy) = match direction {
+            Right => (1, 0),
+            Left => (-1, 0),
+            UpRight => (shift, 1),
+            UpLeft => (shift - 1, 1),
+            DownRight => (shift, -1),
+            DownLeft => (shift - 1, -1),
+            _ => (0, 0),
+        };
+        wrapped(
+            pos.x as isize + dx,
+            pos.y as isize + dy,
+            self.cols,
+            self.rows,
+            self.wrap,
+        )
+    }
+
+    fn distance(&self, a: Pos, b: Pos) -> usize {
+        let (cols, rows) = (self.cols as isize, self.rows as isize);
+        let copies: &[isize] = if self.wrap { &[-1, 0, 1] } else { &[0] };
+
+        let (aq, ar) = Self::cube(a.x as isize, a.y as isize);
+        // The nearest of the copies of `b` around the board, if it wraps
+        let mut best = usize::MAX;
+        for i in copies {
+            for j in copies {
+                let (bq, br) = Self::cube(b.x as isize + i * cols, b.y as isize + j * rows);
+                let (dq, dr) = (aq - bq, ar - br);
+                let steps = (dq.abs() + dr.abs() + (dq + dr).abs()) / 2;
+                best = best.min(steps as usize);
+            }
+        }
+        best
+    }
+
+    fn center(&self, pos: Pos, tile_size: f32) -> [f32; 2] {
+        let height = Self::height(tile_size);
+        let shift = (pos.y % 2) as f32 / 2.;
+        [
+            (pos.x as f32 + 0.5 + shift) * tile_size,
+            height / 2. + pos.y as f32 * height * 0.75,
+        ]
+    }
+
+    fn triangles(&self, pos: Pos, tile_size: f32, corners: &mut Vec<[f32; 2]>) {
+        let [x, y] = self.center(pos, tile_size);
+        let ps = shader::hexagon_for_pos(x, y, tile_size);
+        corners.extend(ps.chunks(2).map(|p| [p[0], p[1]]));
+    }
+
+    fn size(&self, tile_size: f32) -> (f32, f32) {
+        let height = Self::height(tile_size);
+        // Every other row sticks out half a tile, if there is one
+        let shift = if self.rows > 1 { 0.5 } else { 0. };
+        (
+            (self.cols as f32 + shift) * tile_size,
+            height + (self.rows as f32 - 1.) * height * 0.75,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_neighbours_are_each_others_neighbours() {
+        let hex = Grid::Hex.topology(6, 4, true);
+        for index in 0..24 {
+            let pos = hex.pos(index);
+            for &direction in hex.directions() {
+                let next = hex.step(pos, direction).unwrap();
+                assert_eq!(hex.distance(pos, next), 1, "{pos:?} {direction:?}");
+                let back = hex.turned(
+                    hex.turned(hex.turned(direction, Turn::Right), Turn::Right),
+                    Turn::Right,
+                );
+                assert_eq!(hex.step(next, back), Some(pos), "{pos:?} {direction:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn absolute_directions_map_to_the_nearest_one() {
+        use Direction::*;
+
+        let hex = Grid::Hex.topology(6, 4, false);
+        assert_eq!(hex.closest(Up, Right), UpRight);
+        assert_eq!(hex.closest(Up, Left), UpLeft);
+        assert_eq!(hex.closest(Down, Stationary), DownRight);
+        assert_eq!(hex.closest(Left, Up), Left);
+
+        let square = Grid::Square.topology(6, 4, false);
+        assert_eq!(square.closest(UpLeft, Right), Up);
+        assert_eq!(square.closest(Down, Left), Down);
+    }
+
+    #[test]
+    fn hexagons_tile_the_board() {
+        let hex = Grid::Hex.topology(3, 2, false);
+        let (width, height) = hex.size(10.);
+        assert_eq!(width, 35.);
+
+        // Every corner of every tile is on the board
+        let mut corners = vec![];
+        for index in 0..6 {
+            hex.triangles(hex.pos(index), 10., &mut corners);
+        }
+        assert_eq!(corners.len(), 6 * 12);
+        for [x, y] in corners {
+            assert!((-0.001..=width + 0.001).contains(&x), "{x}");
+            assert!((-0.001..=height + 0.001).contains(&y), "{y}");
+        }
+    }
+}
@@ -0,0 +1,534 @@
+// `snake tournament`: ranks controllers (built-in AIs and external bots, see
+// bot.rs) by playing them against each other in pairs. Every pairing plays
+// the same seeds, taking turns at being snake 0, so nobody gets the better
+// spawn every time. Games run on a pool of threads, each game starting its
+// own controllers.
+//
+// Round robin pairs everyone with everyone. Swiss plays a few rounds,
+// pairing entrants with about as many points that haven't met yet, which
+// takes far fewer games when there are many entrants.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::bot::{self, BotResult, Forfeit, Outcome};
+use crate::game::{DeathCause, Game, GameOptions};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    /// Everyone plays everyone
+    #[default]
+    RoundRobin,
+    /// A few rounds between entrants with similar points
+    Swiss,
+}
+
+#[derive(Debug, Clone)]
+pub struct TournamentOptions {
+    pub format: Format,
+    // Swiss rounds, None for enough to tell everyone apart
+    pub rounds: Option<usize>,
+    // Every pairing plays a game on each
+    pub seeds: Vec<u64>,
+    // Games played at once
+    pub jobs: usize,
+    pub move_timeout: Duration,
+    pub max_ticks: u64,
+    pub cols: usize,
+    pub rows: usize,
+    // The seed is replaced by each of `seeds`
+    pub game: GameOptions,
+}
+
+/// How an entrant's games ended
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Endings {
+    pub survived: u32,
+    pub edge: u32,
+    pub wall: u32,
+    pub itself: u32,
+    pub snake: u32,
+    pub hazard: u32,
+    pub timeout: u32,
+    pub crashed: u32,
+    pub invalid_output: u32,
+}
+
+impl Endings {
+    fn count(&mut self, outcome: &Outcome) {
+        let count = match outcome {
+            Outcome::Survived => &mut self.survived,
+            Outcome::Died { cause, .. } => match cause {
+                DeathCause::Edge => &mut self.edge,
+                DeathCause::Wall => &mut self.wall,
+                DeathCause::Itself => &mut self.itself,
+                DeathCause::Snake => &mut self.snake,
+                DeathCause::Hazard => &mut self.hazard,
+            },
+            Outcome::Forfeited { reason, .. } => match reason {
+                Forfeit::Timeout => &mut self.timeout,
+                Forfeit::Crashed(_) => &mut self.crashed,
+                Forfeit::InvalidOutput(_) => &mut self.invalid_output,
+            },
+        };
+        *count += 1;
+    }
+
+    fn named(&self) -> [(&'static str, u32); 9] {
+        [
+            ("survived", self.survived),
+            ("edge", self.edge),
+            ("wall", self.wall),
+            ("itself", self.itself),
+            ("snake", self.snake),
+            ("hazard", self.hazard),
+            ("timeout", self.timeout),
+            ("crashed", self.crashed),
+            ("invalid_output", self.invalid_output),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Standing {
+    pub name: String,
+    pub games: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    // Swiss rounds sat out for lack of an opponent, worth a win
+    pub byes: u32,
+    pub win_rate: f64,
+    pub average_score: f64,
+    pub average_length: f64,
+    pub endings: Endings,
+    pub average_move_ms: f64,
+    pub slowest_move_ms: f64,
+
+    #[serde(skip)]
+    totals: Totals,
+}
+
+// Running sums the averages are worked out from
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Totals {
+    score: u64,
+    length: u64,
+    moves: u64,
+    think_time: Duration,
+    slowest_move: Duration,
+}
+
+impl Standing {
+    // Wins and byes count one, draws half
+    fn points(&self) -> f64 {
+        (self.wins + self.byes) as f64 + self.draws as f64 / 2.
+    }
+
+    fn add(&mut self, result: &BotResult, won: Option<bool>) {
+        self.games += 1;
+        match won {
+            Some(true) => self.wins += 1,
+            Some(false) => self.losses += 1,
+            None => self.draws += 1,
+        }
+        self.endings.count(&result.outcome);
+
+        let totals = &mut self.totals;
+        totals.score += result.score as u64;
+        totals.length += result.length as u64;
+        totals.moves += result.moves;
+        totals.think_time += result.think_time;
+        totals.slowest_move = totals.slowest_move.max(result.slowest_move);
+
+        let games = self.games as f64;
+        self.win_rate = self.wins as f64 / games;
+        self.average_score = totals.score as f64 / games;
+        self.average_length = totals.length as f64 / games;
+        self.average_move_ms = totals.think_time.as_secs_f64() * 1000. / totals.moves.max(1) as f64;
+        self.slowest_move_ms = totals.slowest_move.as_secs_f64() * 1000.;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub format: Format,
+    pub seeds: Vec<u64>,
+    pub games: usize,
+    pub seconds: f64,
+    /// Best first
+    pub standings: Vec<Standing>,
+}
+
+// One game, `players[i]` being the entrant playing snake i
+#[derive(Debug, Clone, Copy)]
+struct Job {
+    seed: u64,
+    players: [usize; 2],
+}
+
+// When a snake's game ended, for telling who lasted longer
+fn ended_at(outcome: &Outcome) -> u64 {
+    match outcome {
+        Outcome::Survived => u64::MAX,
+        Outcome::Died { tick, .. } | Outcome::Forfeited { tick, .. } => *tick,
+    }
+}
+
+// Whoever lasted longest wins, the longer snake if both lasted as long
+fn winner(results: &[BotResult]) -> Option<usize> {
+    let key = |r: &BotResult| (ended_at(&r.outcome), r.length);
+    let (a, b) = (key(&results[0]), key(&results[1]));
+    match a.cmp(&b) {
+        std::cmp::Ordering::Greater => Some(0),
+        std::cmp::Ordering::Less => Some(1),
+        std::cmp::Ordering::Equal => None,
+    }
+}
+
+fn play_game(
+    entrants: &[String],
+    job: Job,
+    options: &TournamentOptions,
+) -> Result<Vec<BotResult>, String> {
+    let mut controllers = job
+        .players
+        .iter()
+        .enumerate()
+        .map(|(id, &player)| bot::start(&entrants[player], job.seed.wrapping_add(id as u64)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let game_options = GameOptions {
+        seed: Some(job.seed),
+        ..options.game.clone()
+    };
+    let mut game = Game::with_options(options.cols, options.rows, 1., game_options);
+    Ok(bot::play(
+        &mut game,
+        &mut controllers,
+        options.move_timeout,
+        options.max_ticks,
+        |_| {},
+    ))
+}
+
+// Plays `jobs` on `options.jobs` threads, results in the same order
+fn play_games(
+    entrants: &[String],
+    jobs: &[Job],
+    options: &TournamentOptions,
+) -> Result<Vec<Vec<BotResult>>, String> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(vec![None; jobs.len()]);
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..options.jobs.max(1))
+            .map(|_| {
+                scope.spawn(|| -> Result<(), String> {
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(&job) = jobs.get(i) else {
+                            return Ok(());
+                        };
+                        let played = play_game(entrants, job, options)?;
+                        results.lock().unwrap()[i] = Some(played);
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .try_for_each(|worker| worker.join().expect("a tournament game panicked"))
+    })?;
+
+    Ok(results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|played| played.expect("every job was played"))
+        .collect())
+}
+
+// Each pairing on every seed, swapping sides every other seed
+fn games_between(pairs: &[(usize, usize)], seeds: &[u64]) -> Vec<Job> {
+    pairs
+        .iter()
+        .flat_map(|&(a, b)| {
+            seeds.iter().enumerate().map(move |(i, &seed)| Job {
+                seed,
+                players: if i % 2 == 0 { [a, b] } else { [b, a] },
+            })
+        })
+        .collect()
+}
+
+// Pairs entrants with similar points that haven't met yet. With an odd
+// number the lowest ranked one that hasn't had a bye sits out.
+fn swiss_pairs(standings: &[Standing], met: &[Vec<bool>]) -> (Vec<(usize, usize)>, Option<usize>) {
+    let mut order: Vec<usize> = (0..standings.len()).collect();
+    order.sort_by(|a, b| standings[*b].points().total_cmp(&standings[*a].points()));
+
+    let mut bye = None;
+    if order.len() % 2 == 1 {
+        let sits_out = order
+            .iter()
+            .rposition(|&e| standings[e].byes == 0)
+            .unwrap_or(order.len() - 1);
+        bye = Some(order.remove(sits_out));
+    }
+
+    let mut pairs = vec![];
+    while let Some(a) = order.first().copied() {
+        order.remove(0);
+        let b = order.iter().position(|&b| !met[a][b]).unwrap_or(0);
+        pairs.push((a, order.remove(b)));
+    }
+    (pairs, bye)
+}
+
+/// Plays the tournament between `entrants`, controller specs as
+/// `bot::start` takes them
+pub fn run(entrants: &[String], options: &TournamentOptions) -> Result<Report, String> {
+    if entrants.len() < 2 {
+        return Err("a tournament needs at least 2 entrants".into());
+    }
+    let started = Instant::now();
+    let mut standings: Vec<Standing> = entrants
+        .iter()
+        .map(|name| Standing {
+            name: name.clone(),
+            ..Default::default()
+        })
+        .collect();
+    let mut met = vec![vec![false; entrants.len()]; entrants.len()];
+    let mut games = 0;
+
+    let rounds = match options.format {
+        Format::RoundRobin => 1,
+        Format::Swiss => options
+            .rounds
+            .unwrap_or(entrants.len().next_power_of_two().ilog2() as usize)
+            .max(1),
+    };
+    for round in 0..rounds {
+        let pairs = match options.format {
+            Format::RoundRobin => (0..entrants.len())
+                .flat_map(|a| (a + 1..entrants.len()).map(move |b| (a, b)))
+                .collect(),
+            Format::Swiss => {
+                let (pairs, bye) = swiss_pairs(&standings, &met);
+                if let Some(bye) = bye {
+                    standings[bye].byes += 1;
+                }
+                pairs
+            }
+        };
+        log::info!("round {} of {rounds}, {} pairings", round + 1, pairs.len());
+
+        let jobs = games_between(&pairs, &options.seeds);
+        for (job, results) in jobs.iter().zip(play_games(entrants, &jobs, options)?) {
+            let [a, b] = job.players;
+            met[a][b] = true;
+            met[b][a] = true;
+            let winner = winner(&results);
+            for (id, result) in results.iter().enumerate() {
+                let won = winner.map(|w| w == id);
+                standings[job.players[id]].add(result, won);
+            }
+        }
+        games += jobs.len();
+    }
+
+    standings.sort_by(|a, b| {
+        b.points()
+            .total_cmp(&a.points())
+            .then(b.average_length.total_cmp(&a.average_length))
+    });
+    Ok(Report {
+        format: options.format,
+        seeds: options.seeds.clone(),
+        games,
+        seconds: started.elapsed().as_secs_f64(),
+        standings,
+    })
+}
+
+impl Report {
+    /// The standings as a table for the terminal
+    pub fn table(&self) -> String {
+        let width = self
+            .standings
+            .iter()
+            .map(|s| s.name.len())
+            .max()
+            .unwrap_or(0)
+            .max("entrant".len());
+        let mut out = format!(
+            "{:>4}  {:<width$}  {:>5}  {:>6}  {:>4}  {:>4}  {:>4}  {:>7}  {:>7}  {:>7}  endings\n",
+            "rank", "entrant", "games", "win %", "W", "D", "L", "avg len", "avg ms", "max ms"
+        );
+        for (rank, s) in self.standings.iter().enumerate() {
+            let endings: Vec<String> = s
+                .endings
+                .named()
+                .iter()
+                .filter(|(_, count)| *count > 0)
+                .map(|(name, count)| format!("{name} {count}"))
+                .collect();
+            writeln!(
+                out,
+                "{:>4}  {:<width$}  {:>5}  {:>6.1}  {:>4}  {:>4}  {:>4}  {:>7.1}  {:>7.2}  {:>7.2}  {}",
+                rank + 1,
+                s.name,
+                s.games,
+                s.win_rate * 100.,
+                s.wins,
+                s.draws,
+                s.losses,
+                s.average_length,
+                s.average_move_ms,
+                s.slowest_move_ms,
+                endings.join(", "),
+            )
+            .unwrap();
+        }
+        write!(
+            out,
+            "{} games on {} seeds in {:.1}s",
+            self.games,
+            self.seeds.len(),
+            self.seconds
+        )
+        .unwrap();
+        out
+    }
+
+    /// One row per entrant, best first
+    pub fn csv(&self) -> String {
+        let mut out = String::from(
+            "rank,name,games,wins,draws,losses,byes,win_rate,average_score,average_length,\
+             average_move_ms,slowest_move_ms",
+        );
+        for (name, _) in Endings::default().named() {
+            write!(out, ",{name}").unwrap();
+        }
+        out.push('\n');
+
+        for (rank, s) in self.standings.iter().enumerate() {
+            write!(
+                out,
+                "{},\"{}\",{},{},{},{},{},{:.4},{:.2},{:.2},{:.3},{:.3}",
+                rank + 1,
+                s.name.replace('"', "\"\""),
+                s.games,
+                s.wins,
+                s.draws,
+                s.losses,
+                s.byes,
+                s.win_rate,
+                s.average_score,
+                s.average_length,
+                s.average_move_ms,
+                s.slowest_move_ms,
+            )
+            .unwrap();
+            for (_, count) in s.endings.named() {
+                write!(out, ",{count}").unwrap();
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("reports are always valid JSON")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(format: Format, seeds: u64) -> TournamentOptions {
+        TournamentOptions {
+            format,
+            rounds: None,
+            seeds: (0..seeds).collect(),
+            jobs: 3,
+            move_timeout: Duration::from_secs(1),
+            max_ticks: 300,
+            cols: 12,
+            rows: 12,
+            game: GameOptions::default(),
+        }
+    }
+
+    fn entrants(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn round_robin_plays_every_pairing_on_every_seed() {
+        let report = run(
+            &entrants(&["ai:random", "ai:greedy"]),
+            &options(Format::RoundRobin, 6),
+        )
+        .unwrap();
+        assert_eq!(report.games, 6);
+
+        for standing in &report.standings {
+            assert_eq!(standing.games, 6);
+            assert_eq!(standing.wins + standing.draws + standing.losses, 6);
+            let endings: u32 = standing.endings.named().iter().map(|(_, n)| n).sum();
+            assert_eq!(endings, 6);
+        }
+        let (a, b) = (&report.standings[0], &report.standings[1]);
+        assert_eq!((a.wins, a.draws), (b.losses, b.draws));
+        assert!(a.points() >= b.points());
+
+        // Random never goes for the food
+        let length = |name: &str| {
+            let standing = report.standings.iter().find(|s| s.name == name);
+            standing.unwrap().average_length
+        };
+        assert!(length("ai:greedy") > length("ai:random"));
+    }
+
+    #[test]
+    fn same_seeds_same_results() {
+        let entrants = entrants(&["ai:greedy", "ai:random", "ai:random"]);
+        let options = options(Format::RoundRobin, 2);
+        let a = run(&entrants, &options).unwrap();
+        let b = run(&entrants, &options).unwrap();
+        let summary = |r: &Report| -> Vec<_> {
+            r.standings
+                .iter()
+                .map(|s| (s.name.clone(), s.wins, s.draws, s.endings, s.average_length))
+                .collect()
+        };
+        assert_eq!(summary(&a), summary(&b));
+        assert_eq!(a.games, 3 * 2);
+    }
+
+    #[test]
+    fn swiss_rounds_give_odd_ones_out_a_bye() {
+        let entrants = entrants(&["ai:greedy", "ai:random", "ai:greedy"]);
+        let report = run(&entrants, &options(Format::Swiss, 2)).unwrap();
+        // Two rounds for three entrants, one pairing each
+        assert_eq!(report.games, 2 * 2);
+        let byes: u32 = report.standings.iter().map(|s| s.byes).sum();
+        assert_eq!(byes, 2);
+        assert!(report.standings.iter().all(|s| s.byes <= 1));
+
+        let csv = report.csv();
+        let mut lines = csv.lines();
+        assert!(lines.next().unwrap().starts_with("rank,name,games,wins"));
+        assert_eq!(lines.count(), 3);
+        let json: serde_json::Value = serde_json::from_str(&report.json()).unwrap();
+        assert_eq!(json["format"], "swiss");
+        assert_eq!(json["standings"].as_array().unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,272 @@
+// Connections for the network protocol, over TCP or (on unix) a Unix domain
+// socket. Addresses are `host:port`, or `unix:PATH` for a socket file.
+
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::time::Duration;
+
+const UNIX_PREFIX: &str = "unix:";
+
+pub enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+/// Connects to `addr`, see the top of this file for the format
+pub fn connect(addr: &str) -> io::Result<Stream> {
+    if let Some(path) = addr.strip_prefix(UNIX_PREFIX) {
+        #[cfg(unix)]
+        return UnixStream::connect(path).map(Stream::Unix);
+        #[cfg(not(unix))]
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("can't connect to {path}, Unix sockets need a unix"),
+        ));
+    }
+
+    let stream = TcpStream::connect(addr)?;
+    stream.set_nodelay(true)?;
+    Ok(Stream::Tcp(stream))
+}
+
+impl Stream {
+    pub fn try_clone(&self) -> io::Result<Stream> {
+        match self {
+            Stream::Tcp(stream) => stream.try_clone().map(Stream::Tcp),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.try_clone().map(Stream::Unix),
+        }
+    }
+
+    /// Closes the connection for every clone of it
+    pub fn shutdown(&self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.shutdown(Shutdown::Both),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.shutdown(Shutdown::Both),
+        }
+    }
+
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.set_write_timeout(timeout),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.set_write_timeout(timeout),
+        }
+    }
+
+    // Accepted streams inherit the listener's non-blocking mode
+    fn set_blocking(&self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => {
+                stream.set_nonblocking(false)?;
+                stream.set_nodelay(true)
+            }
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.set_nonblocking(false),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Writes to a connection from a thread of its own, so sending never waits on
+/// a slow peer. Dropping it closes the connection once what's queued is
+/// written.
+pub struct Outbox {
+    queue: SyncSender<Arc<[u8]>>,
+    stream: Stream,
+}
+
+impl Outbox {
+    /// Queues up to `limit` messages, more than that waiting means the peer
+    /// has fallen behind
+    pub fn new(stream: Stream, limit: usize) -> io::Result<Self> {
+        let writer = stream.try_clone()?;
+        let (queue, messages) = mpsc::sync_channel(limit);
+        std::thread::spawn(move || write_messages(writer, messages));
+        Ok(Self { queue, stream })
+    }
+
+    /// Queues `bytes` to be written, false if the peer has fallen behind or
+    /// the connection failed
+    pub fn send(&self, bytes: Arc<[u8]>) -> bool {
+        self.queue.try_send(bytes).is_ok()
+    }
+
+    /// Closes the connection now, whatever is still queued
+    pub fn shutdown(&self) {
+        let _ = self.stream.shutdown();
+    }
+}
+
+fn write_messages(mut stream: Stream, messages: Receiver<Arc<[u8]>>) {
+    for bytes in messages {
+        if let Err(e) = stream.write_all(&bytes) {
+            log::info!("giving up on a connection: {e}");
+            break;
+        }
+    }
+    let _ = stream.shutdown();
+}
+
+/// Accepts connections without blocking
+pub enum Listener {
+    Tcp(TcpListener),
+    // The socket file is removed again when the listener is dropped
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+}
+
+impl Listener {
+    pub fn bind(addr: &str) -> io::Result<Self> {
+        if let Some(path) = addr.strip_prefix(UNIX_PREFIX) {
+            #[cfg(unix)]
+            {
+                let listener = UnixListener::bind(path)?;
+                listener.set_nonblocking(true)?;
+                return Ok(Listener::Unix(listener, path.into()));
+            }
+            #[cfg(not(unix))]
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("can't listen on {path}, Unix sockets need a unix"),
+            ));
+        }
+
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Listener::Tcp(listener))
+    }
+
+    /// Address to connect to, in the same format `bind` takes
+    pub fn local_addr(&self) -> io::Result<String> {
+        match self {
+            Listener::Tcp(listener) => listener.local_addr().map(|addr| addr.to_string()),
+            #[cfg(unix)]
+            Listener::Unix(_, path) => Ok(format!("{UNIX_PREFIX}{}", path.display())),
+        }
+    }
+
+    /// The next waiting connection, a blocking one, None if there's none.
+    /// Connections that fail before they're handed over are logged and
+    /// skipped, only errors that leave the listener unusable are returned.
+    pub fn accept(&self) -> io::Result<Option<Stream>> {
+        loop {
+            let accepted = match self {
+                Listener::Tcp(listener) => listener.accept().map(|(stream, _)| Stream::Tcp(stream)),
+                #[cfg(unix)]
+                Listener::Unix(listener, _) => {
+                    listener.accept().map(|(stream, _)| Stream::Unix(stream))
+                }
+            };
+            let stream = match accepted {
+                Ok(stream) => stream,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                // Tried again next time
+                Err(e) if is_transient(&e) => {
+                    log::warn!("couldn't accept a connection: {e}");
+                    return Ok(None);
+                }
+                Err(e) => return Err(e),
+            };
+            match stream.set_blocking() {
+                Ok(()) => return Ok(Some(stream)),
+                Err(e) => log::warn!("dropping a connection: {e}"),
+            }
+        }
+    }
+}
+
+// Accept errors about the one connection, or running out of file
+// descriptors for now, rather than about the listener
+fn is_transient(e: &io::Error) -> bool {
+    use io::ErrorKind::*;
+    // ENFILE and EMFILE have no `ErrorKind` of their own
+    let out_of_files = cfg!(unix) && matches!(e.raw_os_error(), Some(23 | 24));
+    out_of_files
+        || matches!(
+            e.kind(),
+            ConnectionAborted | ConnectionReset | Interrupted | TimedOut | OutOfMemory
+        )
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Listener::Unix(_, path) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    #[test]
+    fn outboxes_give_up_on_peers_that_fall_behind() {
+        let listener = Listener::bind("127.0.0.1:0").unwrap();
+        // Never reads
+        let _peer = connect(&listener.local_addr().unwrap()).unwrap();
+        let stream = loop {
+            if let Some(stream) = listener.accept().unwrap() {
+                break stream;
+            }
+        };
+
+        let outbox = Outbox::new(stream, 4).unwrap();
+        let message: Arc<[u8]> = vec![0; 1 << 20].into();
+        let start = Instant::now();
+        // The socket buffers take some, then the queue fills up
+        let sent = (0..1000)
+            .take_while(|_| outbox.send(message.clone()))
+            .count();
+        assert!(sent < 1000);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn only_broken_listeners_are_errors() {
+        assert!(is_transient(&io::ErrorKind::ConnectionAborted.into()));
+        assert!(is_transient(&io::ErrorKind::ConnectionReset.into()));
+        #[cfg(unix)]
+        assert!(is_transient(&io::Error::from_raw_os_error(24)));
+        assert!(!is_transient(&io::ErrorKind::InvalidInput.into()));
+    }
+}
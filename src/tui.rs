@@ -0,0 +1,273 @@
+// Terminal frontend, draws every tile as two colored character cells. Runs
+// the same `App` as the window, crossterm keys are translated to winit key
+// codes so the key bindings work unchanged. Also the frontend for network
+// games, through the same `View`.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode as TermKey, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{self, Color},
+    terminal,
+};
+use winit::keyboard::KeyCode;
+
+use crate::app::App;
+use crate::audio::Audio;
+use crate::client::Session;
+use crate::config::Watcher;
+use crate::game::{Pos, Tile, PORTAL_COLORS};
+use crate::input::Bindings;
+use crate::offscreen;
+use crate::raster;
+use crate::scene::{Scene, View};
+use crate::settings::Settings;
+
+fn color([r, g, b]: [f32; 3]) -> Color {
+    let c = |v: f32| (v.clamp(0., 1.) * 255.) as u8;
+    Color::Rgb {
+        r: c(r),
+        g: c(g),
+        b: c(b),
+    }
+}
+
+/// winit key code for a terminal key, None for keys we can't represent
+pub fn key_code(key: TermKey) -> Option<KeyCode> {
+    use KeyCode::*;
+
+    const LETTERS: [KeyCode; 26] = [
+        KeyA, KeyB, KeyC, KeyD, KeyE, KeyF, KeyG, KeyH, KeyI, KeyJ, KeyK, KeyL, KeyM, KeyN, KeyO,
+        KeyP, KeyQ, KeyR, KeyS, KeyT, KeyU, KeyV, KeyW, KeyX, KeyY, KeyZ,
+    ];
+    const DIGITS: [KeyCode; 10] = [
+        Digit0, Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8, Digit9,
+    ];
+    const F_KEYS: [KeyCode; 12] = [F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12];
+
+    Some(match key {
+        TermKey::Char(' ') => Space,
+        TermKey::Char(c) if c.is_ascii_alphabetic() => {
+            LETTERS[(c.to_ascii_lowercase() as u8 - b'a') as usize]
+        }
+        TermKey::Char(c) if c.is_ascii_digit() => DIGITS[(c as u8 - b'0') as usize],
+        TermKey::F(n @ 1..=12) => F_KEYS[n as usize - 1],
+        TermKey::Up => ArrowUp,
+        TermKey::Down => ArrowDown,
+        TermKey::Left => ArrowLeft,
+        TermKey::Right => ArrowRight,
+        TermKey::Enter => Enter,
+        TermKey::Esc => Escape,
+        TermKey::Backspace => Backspace,
+        TermKey::Tab => Tab,
+        TermKey::Delete => Delete,
+        _ => return None,
+    })
+}
+
+fn draw(out: &mut impl Write, scene: &Scene) -> io::Result<()> {
+    let game = scene.game;
+    let theme = scene.theme;
+    // Sized as if a tile was two cells wide, so odd hex rows are one cell in
+    let topology = game.topology();
+    let width = topology.size(2.).0 as usize;
+
+    queue!(
+        out,
+        cursor::MoveTo(0, 0),
+        style::SetBackgroundColor(color(theme.hud_background)),
+        style::SetForegroundColor(color(theme.text)),
+        style::Print(format!(
+            "{:<width$}",
+            scene.hud.as_deref().unwrap_or_default(),
+        )),
+    )?;
+
+    // Top row of the board first
+    for row in 0..game.rows() {
+        let y = game.rows() - 1 - row;
+        let indent = (topology.center(Pos { x: 0, y }, 2.)[0] - 1.) as usize;
+        queue!(
+            out,
+            cursor::MoveTo(0, row as u16 + 1),
+            style::SetBackgroundColor(color(theme.background)),
+            style::Print(" ".repeat(indent)),
+        )?;
+        for x in 0..game.cols() {
+            let pos = Pos { x, y };
+            let tile_color = match game.tile(pos) {
+                _ if game.entity_at(pos).is_some() => theme.hazard,
+                Tile::Empty | Tile::Void => theme.background,
+                Tile::SnakeBody | Tile::SnakeHead => theme.snake,
+                Tile::Food => theme.food,
+                Tile::Wall => theme.wall,
+                Tile::PowerUp => theme.power_up,
+                Tile::Portal => {
+                    let pair = game.portal_pair(pos).unwrap_or(0);
+                    theme.portals[pair % PORTAL_COLORS]
+                }
+            };
+            queue!(
+                out,
+                style::SetBackgroundColor(color(tile_color)),
+                style::Print("  ")
+            )?;
+        }
+        queue!(
+            out,
+            style::SetBackgroundColor(color(theme.background)),
+            style::Print(" ".repeat(width - indent - game.cols() * 2)),
+        )?;
+    }
+
+    if let Some(overlay) = &scene.overlay {
+        let first_row = (game.rows() + 1).saturating_sub(overlay.lines.len()) / 2 + 1;
+        queue!(
+            out,
+            style::SetBackgroundColor(color(theme.background)),
+            style::SetForegroundColor(color(theme.text)),
+        )?;
+        for (i, line) in overlay.lines.iter().enumerate() {
+            let line: String = line.chars().take(width).collect();
+            queue!(
+                out,
+                cursor::MoveTo(0, (first_row + i) as u16),
+                style::Print(format!("{line:^width$}")),
+            )?;
+        }
+    }
+
+    queue!(out, style::ResetColor)?;
+    out.flush()
+}
+
+// `poll` gets a go at `view` before every key and tick
+fn run_loop<V: View>(
+    view: &mut V,
+    mut poll: impl FnMut(&mut V),
+    audio: &mut Audio,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let mut next_tick = Instant::now() + Duration::from_millis(view.tick_ms());
+    draw(out, &view.scene())?;
+
+    while !view.should_quit() {
+        poll(view);
+
+        let timeout = next_tick.saturating_duration_since(Instant::now());
+        if event::poll(timeout)? {
+            if let Event::Key(key) = event::read()? {
+                // Raw mode eats Ctrl+C, so handle it ourselves
+                if key.code == TermKey::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    break;
+                }
+                if key.kind == KeyEventKind::Press {
+                    if let Some(code) = key_code(key.code) {
+                        view.on_key(code);
+                    }
+                    // No GPU output to grab, render the frame offscreen instead,
+                    // on the CPU if there's no adapter
+                    if view.take_screenshot_request() {
+                        let scene = view.scene();
+                        let image =
+                            offscreen::render(&scene).unwrap_or_else(|| Ok(raster::render(&scene)));
+                        offscreen::save_screenshot(image);
+                    }
+                }
+            }
+            draw(out, &view.scene())?;
+        }
+
+        if Instant::now() >= next_tick {
+            view.tick();
+            audio.play_events(&view.take_events());
+            next_tick += Duration::from_millis(view.tick_ms());
+            draw(out, &view.scene())?;
+        }
+    }
+
+    Ok(())
+}
+
+// Takes over the terminal for `body`, and gives it back even if it fails
+fn with_terminal(body: impl FnOnce(&mut io::Stdout) -> io::Result<()>) -> io::Result<()> {
+    let mut out = io::stdout();
+
+    terminal::enable_raw_mode()?;
+    execute!(
+        out,
+        terminal::EnterAlternateScreen,
+        terminal::Clear(terminal::ClearType::All),
+        cursor::Hide
+    )?;
+
+    let result = body(&mut out);
+
+    execute!(
+        out,
+        style::ResetColor,
+        cursor::Show,
+        terminal::LeaveAlternateScreen
+    )?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+pub fn run(
+    settings: &Settings,
+    bindings: Bindings,
+    bindings_path: Option<PathBuf>,
+    mut watcher: Option<Watcher>,
+) -> io::Result<()> {
+    let (term_cols, term_rows) = terminal::size()?;
+    if (term_cols as usize) < settings.cols * 2 || (term_rows as usize) < settings.rows + 1 {
+        log::warn!(
+            "terminal is {term_cols}x{term_rows}, a {}x{} board needs {}x{}",
+            settings.cols,
+            settings.rows,
+            settings.cols * 2,
+            settings.rows + 1
+        );
+    }
+
+    let mut app = App::new(settings, bindings, bindings_path);
+    // Daily challenge results, printed once the terminal is back to normal
+    let mut shares = Vec::new();
+    let poll = |app: &mut App| {
+        if let Some(watcher) = &mut watcher {
+            watcher.poll(app);
+        }
+        shares.extend(app.take_share());
+    };
+    let mut audio = Audio::new(settings.mute);
+    with_terminal(|out| run_loop(&mut app, poll, &mut audio, out))?;
+    for share in shares {
+        print!("{share}");
+    }
+    Ok(())
+}
+
+/// Plays or watches a network game, see `client::Session`
+pub fn run_session(session: &mut Session) -> io::Result<()> {
+    with_terminal(|out| run_loop(session, |_| {}, &mut Audio::off(), out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terminal_keys_map_to_key_codes() {
+        assert_eq!(key_code(TermKey::Char('w')), Some(KeyCode::KeyW));
+        assert_eq!(key_code(TermKey::Char('W')), Some(KeyCode::KeyW));
+        assert_eq!(key_code(TermKey::Char('7')), Some(KeyCode::Digit7));
+        assert_eq!(key_code(TermKey::Char(' ')), Some(KeyCode::Space));
+        assert_eq!(key_code(TermKey::F(5)), Some(KeyCode::F5));
+        assert_eq!(key_code(TermKey::Left), Some(KeyCode::ArrowLeft));
+        assert_eq!(key_code(TermKey::Char('é')), None);
+    }
+}